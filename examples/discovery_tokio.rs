@@ -0,0 +1,62 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use knx_rust::discovery::{Discovery, DiscoveryConfig, KNX_MULTICAST_ADDRESS, KNX_PORT};
+
+use tokio::select;
+use tokio::time::Instant;
+use tokio::net::UdpSocket;
+
+
+// ------------------------------------------------------------------------------
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> io::Result<()> {
+
+    let multicast_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::from(KNX_MULTICAST_ADDRESS)), KNX_PORT);
+
+    // determine the local address of the interface used for multicast
+    let probe = UdpSocket::bind("0.0.0.0:0").await?;
+    probe.connect(multicast_addr).await?;
+    let ipv4 = match probe.local_addr()?.ip() {
+        IpAddr::V4(ip) => Ok(ip.octets()),
+        _ => Err("Invalid IPv4 Address"),
+    }.unwrap();
+
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(ipv4)), 0)).await?;
+    let addr = socket.local_addr()?;
+    println!("Local Addr {:?}", addr);
+
+    let mut discovery = Discovery::new(ipv4, addr.port(), DiscoveryConfig::default());
+    let mut buf = [0; 1 << 16];
+
+    while let Some(data) = discovery.get_outbound_data() {
+        socket.send_to(data, multicast_addr).await?;
+    }
+
+    while !discovery.finished() {
+        select! {
+            v = socket.recv_from(&mut buf) => {
+                let (packet_size, sender) = v?;
+                if let Some(gateway) = discovery.handle_inbound_message(&buf[..packet_size]) {
+                    match gateway.device {
+                        Some(device) => println!("Found {} ({}) at {:?}:{} (sender {}), programming mode {}, services {:?}",
+                            device.friendly_name,
+                            device.individual_address,
+                            gateway.control.address(),
+                            gateway.control.port(),
+                            sender,
+                            device.programming_mode,
+                            gateway.services.map(|s| s.families)),
+                        None => println!("Found server without device information at {:?}:{} (sender {})",
+                            gateway.control.address(),
+                            gateway.control.port(),
+                            sender),
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(Instant::from(discovery.get_next_time_event())) => {
+                discovery.handle_time_events();
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use std::{io, time};
-use std::net::IpAddr;
+use std::net::SocketAddr;
 use std::time::Duration;
 use knx_rust;
 use knx_rust::address::GroupAddress2;
@@ -16,7 +16,7 @@ const SEND_TIMER: Token = Token(2);
 use mio::{Events, Interest, Poll, Token};
 use mio_timerfd::{ClockId, TimerFd};
 
-use knx_rust::tunnel_connection::{TunnelConnection, TunnelConnectionConfig};
+use knx_rust::tunnel_connection::{Endpoint, TunnelConnection, TunnelConnectionConfig};
 
 
 // ------------------------------------------------------------------------------
@@ -28,16 +28,6 @@ fn main() -> io::Result<()> {
 
     let knx_addr: core::net::SocketAddr = "192.168.1.10:3671".parse().unwrap();
     let mut socket = UdpSocket::bind("0.0.0.0:0".parse().unwrap()).expect("couldn't bind to address");
-    socket
-        .connect(knx_addr)
-        .expect("couldn't connect to address");
-
-    let addr = socket.local_addr().unwrap();
-    println!("Local Addr {:?}", addr);
-    let ipv4 = match addr.ip() {
-        IpAddr::V4(ip) => Ok(ip.octets()),
-        _ => Err("Invalid IPv4 Address"),
-    }.unwrap();
 
 
     let mut lib_timer = TimerFd::new(ClockId::Monotonic).unwrap();
@@ -53,14 +43,18 @@ fn main() -> io::Result<()> {
     poll.registry()
         .register(&mut send_timer, SEND_TIMER, Interest::READABLE)?;
 
-    let mut knx_tunnel = TunnelConnection::new(ipv4, addr.port(), TunnelConnectionConfig::default());
+    // in NAT mode the server answers to the address and port the requests come from
+    let mut knx_tunnel = TunnelConnection::new_nat(TunnelConnectionConfig::default());
 
     let mut buf = [0; 1 << 16];
 
     loop {
         //send out all pending data
         while match knx_tunnel.get_outbound_data() {
-            Some(data) => match socket.send(data) {
+            Some((data, endpoint)) => match socket.send_to(data, match endpoint {
+                Endpoint::Control => knx_addr,
+                Endpoint::Data(addr) => SocketAddr::V4(addr),
+            }) {
                 Ok(count) => {count},
                 Err(e) if e.kind() == io::ErrorKind::WouldBlock => {0},
                 Err(e) => {
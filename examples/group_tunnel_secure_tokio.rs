@@ -0,0 +1,92 @@
+use std::io;
+use std::time::Duration;
+use knx_rust::address::GroupAddress2;
+use knx_rust::group_event::GroupEvent;
+use knx_rust::group_event::GroupEventType::GroupValueRead;
+use knx_rust::knxnet::stream::StreamReassembler;
+use knx_rust::secure::session::{SecureSession, SecureSessionConfig, SecureSessionCredentials};
+use knx_rust::tunnel_connection::{TunnelConnection, TunnelConnectionConfig};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::select;
+use tokio::time::Instant;
+
+
+// ------------------------------------------------------------------------------
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> io::Result<()> {
+    let knx_addr: core::net::SocketAddr = "192.168.1.10:3671".parse().unwrap();
+    let mut stream = TcpStream::connect(knx_addr).await?;
+
+    // passwords of the tunnelling user and the device authentication password from the ETS project
+    let credentials = SecureSessionCredentials::from_passwords(2, "user password", Some("device password"));
+    let mut session = SecureSession::new_tcp(credentials, SecureSessionConfig::default())
+        .map_err(io::Error::other)?;
+    // the tunnel is established once the session is authenticated
+    let mut knx_tunnel: Option<TunnelConnection> = None;
+    let mut reassembler = StreamReassembler::new();
+    let mut buf = [0; 1 << 16];
+    let mut send_interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        if knx_tunnel.is_none() && session.authenticated() {
+            knx_tunnel = Some(TunnelConnection::new_tcp(TunnelConnectionConfig::default()));
+        }
+        //pass all pending data of the tunnel to the session and send out the encrypted data
+        if let Some(tunnel) = knx_tunnel.as_mut() {
+            while let Some((data, _)) = tunnel.get_outbound_data() {
+                session.send(data);
+            }
+        }
+        while let Some(data) = session.get_outbound_data() {
+            stream.write_all(data).await?;
+        }
+        if session.closed() {
+            println!("Session closed: {:?}", session.status());
+            return Ok(());
+        }
+        let next_event = match knx_tunnel.as_ref() {
+            Some(tunnel) => session.get_next_time_event().min(tunnel.get_next_time_event()),
+            None => session.get_next_time_event(),
+        };
+        let timeout = tokio::time::sleep_until(Instant::from(next_event));
+
+        select! {
+            v = stream.read(&mut buf) => {
+                match v {
+                    Ok(0) | Err(_) => {
+                        // sessions are bound to the stream, so everything needs to be set up again
+                        println!("Stream closed");
+                        return Ok(());
+                    }
+                    Ok(len) => {
+                        reassembler.push(&buf[..len]);
+                        while let Ok(Some(frame)) = reassembler.next_frame() {
+                            let Some(frame) = session.handle_inbound_message(&frame) else { continue };
+                            if let Some(event) = knx_tunnel.as_mut().and_then(|tunnel| tunnel.handle_inbound_message(&frame)) {
+                                println!("Received {:?} on {} with value {:02X?}", event.event_type, GroupAddress2::from_u16(event.address), event.data);
+                            }
+                        }
+                    }
+                }
+            }
+            _ = send_interval.tick() => {
+                if let Some(tunnel) = knx_tunnel.as_mut().filter(|tunnel| tunnel.connected()) {
+                    //request data from a group address 8/4
+                    tunnel.send(GroupEvent{
+                        event_type: GroupValueRead,
+                        address: GroupAddress2::new(8, 4).to_u16(),
+                        data: vec![],
+                    });
+                }
+            }
+            _ = timeout => {
+                session.handle_time_events();
+                if let Some(tunnel) = knx_tunnel.as_mut() {
+                    tunnel.handle_time_events();
+                }
+            }
+        }
+    }
+}
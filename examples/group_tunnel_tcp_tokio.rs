@@ -0,0 +1,83 @@
+use std::io;
+use std::time::Duration;
+use knx_rust::address::GroupAddress2;
+use knx_rust::group_event::GroupEvent;
+use knx_rust::group_event::GroupEventType::GroupValueRead;
+use knx_rust::knxnet::stream::StreamReassembler;
+use knx_rust::tunnel_connection::{TunnelConnection, TunnelConnectionConfig};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::select;
+use tokio::time::Instant;
+
+
+// ------------------------------------------------------------------------------
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> io::Result<()> {
+    let knx_addr: core::net::SocketAddr = "192.168.1.10:3671".parse().unwrap();
+    let mut stream = TcpStream::connect(knx_addr).await?;
+
+    let mut knx_tunnel = TunnelConnection::new_tcp(TunnelConnectionConfig::default());
+    let mut reassembler = StreamReassembler::new();
+    let mut buf = [0; 1 << 16];
+    let mut send_interval = tokio::time::interval(Duration::from_secs(60));
+
+    loop {
+        //send out all pending data
+        //everything is sent on the stream, so the endpoint does not matter
+        while let Some((data, _)) = knx_tunnel.get_outbound_data() {
+            stream.write_all(data).await?;
+        }
+        let timeout = tokio::time::sleep_until(Instant::from(knx_tunnel.get_next_time_event()));
+
+        select! {
+            v = stream.read(&mut buf) => {
+                let closed = match v {
+                    // the server closed the stream
+                    Ok(0) | Err(_) => true,
+                    Ok(len) => {
+                        reassembler.push(&buf[..len]);
+                        loop {
+                            match reassembler.next_frame() {
+                                Ok(Some(frame)) => {
+                                    if let Some(event) = knx_tunnel.handle_inbound_message(&frame) {
+                                        println!("Received {:?} on {} with value {:02X?}", event.event_type, GroupAddress2::from_u16(event.address), event.data);
+                                    }
+                                }
+                                Ok(None) => break false,
+                                Err(e) => {
+                                    // the stream lost synchronization, so it can not be used anymore
+                                    println!("Closing stream: {}", e);
+                                    let _ = stream.shutdown().await;
+                                    break true
+                                }
+                            }
+                        }
+                    }
+                };
+                if closed {
+                    // the tunnel needs to be re-established on a new stream
+                    knx_tunnel.handle_stream_closed();
+                    reassembler.clear();
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    stream = TcpStream::connect(knx_addr).await?;
+                    knx_tunnel.handle_stream_opened();
+                }
+            }
+            _ = send_interval.tick() => {
+                if knx_tunnel.connected() {
+                    //request data from a group address 8/4
+                    knx_tunnel.send(GroupEvent{
+                        event_type: GroupValueRead,
+                        address: GroupAddress2::new(8, 4).to_u16(),
+                        data: vec![],
+                    });
+                }
+            }
+            _ = timeout => {
+                knx_tunnel.handle_time_events();
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::io;
 use std::fmt::Display;
-use std::net::IpAddr;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use knx_rust;
@@ -8,7 +8,7 @@ use knx_rust::address::GroupAddress2;
 use knx_rust::group_event::GroupEvent;
 use knx_rust::group_event::GroupEventType::{GroupValueRead, GroupValueWrite};
 use knx_rust::dpt::{DptValueHumidity, DptValueTemp};
-use knx_rust::tunnel_connection::{TunnelConnection, TunnelConnectionConfig};
+use knx_rust::tunnel_connection::{Endpoint, TunnelConnection, TunnelConnectionConfig};
 
 use tokio::select;
 use tokio::time::Instant;
@@ -22,18 +22,10 @@ async fn main() -> io::Result<()> {
 
     let knx_addr: core::net::SocketAddr = "192.168.1.10:3671".parse().unwrap();
     let mut socket = UdpSocket::bind("0.0.0.0:0").await.expect("couldn't bind to address");
-    socket
-        .connect(knx_addr)
-        .await.expect("couldn't connect to address");
 
-    let addr = socket.local_addr().unwrap();
-    println!("Local Addr {:?}", addr);
-    let ipv4 = match addr.ip() {
-        IpAddr::V4(ip) => Ok(ip.octets()),
-        _ => Err("Invalid IPv4 Address"),
-    }.unwrap();
-
-    let mut knx_tunnel = Arc::new(Mutex::new(TunnelConnection::new(ipv4, addr.port(), TunnelConnectionConfig::default())));
+    // in NAT mode the server answers to the address and port the requests come from,
+    // so it works behind docker bridge networks or port forwardings as well
+    let mut knx_tunnel = Arc::new(Mutex::new(TunnelConnection::new_nat(TunnelConnectionConfig::default())));
     let mut buf = [0; 1 << 16];
 
     // spawn a task to send out messages
@@ -68,8 +60,12 @@ async fn main() -> io::Result<()> {
         //send out all pending data
         let timeout = {
             let mut knx_lock = knx_tunnel.lock().await;
-            while let Some(data) = knx_lock.get_outbound_data() {
-                socket.send(data).await?;
+            while let Some((data, endpoint)) = knx_lock.get_outbound_data() {
+                let target = match endpoint {
+                    Endpoint::Control => knx_addr,
+                    Endpoint::Data(addr) => SocketAddr::V4(addr),
+                };
+                socket.send_to(data, target).await?;
             }
             tokio::time::sleep_until(Instant::from(knx_lock.get_next_time_event()))
         };
@@ -2,6 +2,7 @@ use std::fmt::{Display, Formatter};
 use std::ops::Sub;
 
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
 pub struct IndividualAddress {
     addr: u16
 }
@@ -0,0 +1,46 @@
+use crate::cemi::information::InformationType;
+
+/// Frame received in busmonitor mode
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BusmonitorEvent {
+    /// Raw TP1 frame as seen on the bus, including the checksum. Single byte frames are acknowledgements.
+    pub frame: Vec<u8>,
+    pub frame_error: bool,
+    pub bit_error: bool,
+    pub parity_error: bool,
+    /// Frames were lost before this one, e.g. because of an overflow in the interface
+    pub lost: bool,
+    /// Sequence number (0-7) of the frame
+    pub sequence_number: u8,
+    /// Relative timestamp in ticks of the interface
+    pub timestamp: Option<u16>,
+    /// Extended relative timestamp in ticks of the interface
+    pub extended_timestamp: Option<u32>,
+}
+
+impl BusmonitorEvent {
+    pub(crate) fn from_busmon(info: Vec<InformationType>, frame: Vec<u8>) -> BusmonitorEvent {
+        let mut event = BusmonitorEvent{frame, ..BusmonitorEvent::default()};
+        for i in info {
+            match i {
+                // F B P X L s s s
+                InformationType::BusmonitorStatusInfo(status) => {
+                    event.frame_error = status & 0x80 != 0;
+                    event.bit_error = status & 0x40 != 0;
+                    event.parity_error = status & 0x20 != 0;
+                    event.lost = status & 0x08 != 0;
+                    event.sequence_number = status & 0x07;
+                }
+                InformationType::TimestampRelative(timestamp) => event.timestamp = Some(timestamp),
+                InformationType::ExtendedRelativeTimestamp(timestamp) => event.extended_timestamp = Some(timestamp),
+                _ => {}
+            }
+        }
+        event
+    }
+
+    /// The frame is an acknowledgement (ACK, NAK or BUSY) of the previous one
+    pub fn acknowledgement(&self) -> bool {
+        self.frame.len() == 1
+    }
+}
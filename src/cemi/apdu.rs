@@ -21,10 +21,10 @@ pub enum Apdu<D: DPT+Default>{
 }
 
 impl<D: DPT+Default> Apdu<D> {
-    pub(crate) fn length(&self)->u8 {
+    pub(crate) fn length(&self)->u16 {
         match self {
             Apdu::GroupValueWrite(dpt) | Apdu::GroupValueResponse(dpt) => {
-                return if dpt.bit_len() > 6 { (1 + dpt.bit_len() / 8) as u8 } else { 1 }
+                return if dpt.bit_len() > 6 { 1 + dpt.bit_len() / 8 } else { 1 }
             }
             Apdu::GroupValueRead => 1,
             _ => 0
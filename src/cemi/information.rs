@@ -1,30 +1,79 @@
+use byteorder::{BigEndian, ByteOrder};
 use strum_macros::FromRepr;
 use crate::knxnet::KnxNetIpError;
 
-#[derive(FromRepr, Debug, Copy, Clone, PartialEq)]
+/// Additional information of cEMI frames (See 03/06/03 EMI_IMI 4.1.4.3)
+#[derive(FromRepr, Debug, Clone, PartialEq)]
 #[repr(u8)]
 pub enum InformationType{
-    PlMediumInformation = 0x01,
-    RfMediumInformation = 0x02,
-    BusmonitorStatusInfo = 0x03,
-    TimestampRelative = 0x04,
-    TimeDelayUntilSending = 0x05,
-    ExtendedRelativeTimestamp = 0x06,
-    BiBatInformation = 0x07,
-    RfMultiInformation = 0x08,
-    PreambleAndPostamble = 0x09,
-    RfFastAckInformation = 0x0A,
-    ManufacturerSpecific = 0xFE,
-    Reserved = 0xFF
+    /// Domain address of powerline frames
+    PlMediumInformation(u16) = 0x01,
+    RfMediumInformation{
+        rf_info: u8,
+        /// KNX serial number or domain address of the sender
+        serial_number: [u8; 6],
+        /// Data link layer frame number
+        lfn: u8,
+    } = 0x02,
+    BusmonitorStatusInfo(u8) = 0x03,
+    TimestampRelative(u16) = 0x04,
+    TimeDelayUntilSending(u32) = 0x05,
+    ExtendedRelativeTimestamp(u32) = 0x06,
+    BiBatInformation{
+        control: u8,
+        block_number: u8,
+    } = 0x07,
+    RfMultiInformation{
+        transmission_frequency: u8,
+        call_channel: u8,
+        fast_ack: u8,
+        receiver_frequency: u8,
+    } = 0x08,
+    PreambleAndPostamble{
+        preamble_length: u16,
+        postamble_length: u8,
+    } = 0x09,
+    /// Status and information of the fast acknowledgements of each receiver
+    RfFastAckInformation(Vec<(u8, u8)>) = 0x0A,
+    /// Raw data, starting with the manufacturer code
+    ManufacturerSpecific(Vec<u8>) = 0xFE,
+    Reserved(Vec<u8>) = 0xFF
 }
 
+/// Maximum size of the data of an additional information field, type and length take two of the 255 bytes
+pub(crate) const MAX_INFORMATION_LENGTH: usize = 253;
+
 
 impl InformationType {
     pub(crate) fn length(&self)->u8 {
+        // type and length are followed by the information
         return match self {
-            //TODO implement
-            _ => 0
+            Self::PlMediumInformation(_) => 4,
+            Self::RfMediumInformation{..} => 10,
+            Self::BusmonitorStatusInfo(_) => 3,
+            Self::TimestampRelative(_) => 4,
+            Self::TimeDelayUntilSending(_) => 6,
+            Self::ExtendedRelativeTimestamp(_) => 6,
+            Self::BiBatInformation{..} => 4,
+            Self::RfMultiInformation{..} => 6,
+            Self::PreambleAndPostamble{..} => 5,
+            // oversized information saturates, it is rejected by `check`
+            Self::RfFastAckInformation(acks) => u8::try_from(2 + 2 * acks.len()).unwrap_or(u8::MAX),
+            Self::ManufacturerSpecific(data) | Self::Reserved(data) => u8::try_from(2 + data.len()).unwrap_or(u8::MAX),
+        }
+    }
+
+    /// Returns `InvalidSize` if the data of the field exceeds `MAX_INFORMATION_LENGTH`
+    pub(crate) fn check(&self) -> Result<(), KnxNetIpError> {
+        let data_length = match self {
+            Self::RfFastAckInformation(acks) => 2 * acks.len(),
+            Self::ManufacturerSpecific(data) | Self::Reserved(data) => data.len(),
+            _ => 0,
+        };
+        if data_length > MAX_INFORMATION_LENGTH {
+            return Err(KnxNetIpError::InvalidSize)
         }
+        Ok(())
     }
 
     fn identifier(&self) -> u8 {
@@ -34,23 +83,173 @@ impl InformationType {
         unsafe { *<*const _>::from(self).cast::<u8>() }
     }
 
-    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> Result<(), KnxNetIpError> {
+        self.check()?;
+        buf.push(self.identifier());
+        buf.push(self.length() - 2);
         match self {
-            _ => {panic!("not implemented")}
+            Self::PlMediumInformation(domain_address) => buf.extend(domain_address.to_be_bytes()),
+            Self::RfMediumInformation{rf_info, serial_number, lfn} => {
+                buf.push(*rf_info);
+                buf.extend_from_slice(serial_number);
+                buf.push(*lfn);
+            }
+            Self::BusmonitorStatusInfo(status) => buf.push(*status),
+            Self::TimestampRelative(timestamp) => buf.extend(timestamp.to_be_bytes()),
+            Self::TimeDelayUntilSending(delay) => buf.extend(delay.to_be_bytes()),
+            Self::ExtendedRelativeTimestamp(timestamp) => buf.extend(timestamp.to_be_bytes()),
+            Self::BiBatInformation{control, block_number} => buf.extend([*control, *block_number]),
+            Self::RfMultiInformation{transmission_frequency, call_channel, fast_ack, receiver_frequency} =>
+                buf.extend([*transmission_frequency, *call_channel, *fast_ack, *receiver_frequency]),
+            Self::PreambleAndPostamble{preamble_length, postamble_length} => {
+                buf.extend(preamble_length.to_be_bytes());
+                buf.push(*postamble_length);
+            }
+            Self::RfFastAckInformation(acks) => acks.iter().for_each(|(status, info)| buf.extend([*status, *info])),
+            Self::ManufacturerSpecific(data) | Self::Reserved(data) => buf.extend_from_slice(data),
         }
+        Ok(())
+    }
+
+    /// Encodes the length of the additional information field followed by all fields,
+    /// nothing is written if a field or the whole field exceeds its maximum size
+    pub(crate) fn encode_all(infos: &[InformationType], buf: &mut Vec<u8>) -> Result<(), KnxNetIpError> {
+        infos.iter().try_for_each(|i| i.check())?;
+        let length = u8::try_from(infos.iter().map(|i| i.length() as usize).sum::<usize>())
+            .map_err(|_| KnxNetIpError::InvalidSize)?;
+        buf.push(length);
+        infos.iter().try_for_each(|i| i.encode(buf))
     }
 
     pub(crate) fn decode(buf: &[u8]) -> Result<InformationType, KnxNetIpError> {
-        let type_id = InformationType::from_repr(buf[0]);
-        return match type_id{
-            None => Err(KnxNetIpError::Unknown),
-            Some(mut info) => {
-                match info {
-                    // TODO add implementations
-                    _ => Ok(info)
+        if buf.len() < 2 || buf.len() < 2 + buf[1] as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        let data = &buf[2..2 + buf[1] as usize];
+        let mut info = InformationType::from_repr(buf[0]).ok_or(KnxNetIpError::Unknown)?;
+        // all other types have a fixed length
+        let variable = matches!(info, Self::RfFastAckInformation(_) | Self::ManufacturerSpecific(_) | Self::Reserved(_));
+        if !variable && data.len() + 2 != info.length() as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        match info {
+            Self::PlMediumInformation(ref mut domain_address) => *domain_address = BigEndian::read_u16(data),
+            Self::RfMediumInformation{ref mut rf_info, ref mut serial_number, ref mut lfn} => {
+                *rf_info = data[0];
+                serial_number.copy_from_slice(&data[1..7]);
+                *lfn = data[7];
+            }
+            Self::BusmonitorStatusInfo(ref mut status) => *status = data[0],
+            Self::TimestampRelative(ref mut timestamp) => *timestamp = BigEndian::read_u16(data),
+            Self::TimeDelayUntilSending(ref mut delay) => *delay = BigEndian::read_u32(data),
+            Self::ExtendedRelativeTimestamp(ref mut timestamp) => *timestamp = BigEndian::read_u32(data),
+            Self::BiBatInformation{ref mut control, ref mut block_number} => {
+                *control = data[0];
+                *block_number = data[1];
+            }
+            Self::RfMultiInformation{ref mut transmission_frequency, ref mut call_channel, ref mut fast_ack, ref mut receiver_frequency} => {
+                *transmission_frequency = data[0];
+                *call_channel = data[1];
+                *fast_ack = data[2];
+                *receiver_frequency = data[3];
+            }
+            Self::PreambleAndPostamble{ref mut preamble_length, ref mut postamble_length} => {
+                *preamble_length = BigEndian::read_u16(&data[0..2]);
+                *postamble_length = data[2];
+            }
+            Self::RfFastAckInformation(ref mut acks) => {
+                // variable number of acknowledgements with two bytes each
+                if data.len() & 1 != 0 {
+                    return Err(KnxNetIpError::InvalidSize)
                 }
+                *acks = data.chunks(2).map(|ack| (ack[0], ack[1])).collect();
             }
-        };
+            Self::ManufacturerSpecific(ref mut raw) | Self::Reserved(ref mut raw) => *raw = data.to_vec(),
+        }
+        Ok(info)
+    }
+    /// Decodes all additional information fields, fields of unknown types are skipped
+    pub(crate) fn decode_all(mut buf: &[u8]) -> Result<Vec<InformationType>, KnxNetIpError> {
+        let mut infos = vec![];
+        while !buf.is_empty() {
+            if buf.len() < 2 || buf.len() < 2 + buf[1] as usize {
+                return Err(KnxNetIpError::MessageTooShort(buf.len()))
+            }
+            let (field, rest) = buf.split_at(2 + buf[1] as usize);
+            if let Ok(info) = InformationType::decode(field) {
+                infos.push(info);
+            }
+            buf = rest;
+        }
+        Ok(infos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cemi::information::{InformationType, MAX_INFORMATION_LENGTH};
+    use crate::knxnet::KnxNetIpError;
+
+    fn round_trip(info: InformationType, encoded: &[u8]) {
+        let mut buf = vec![];
+        info.encode(&mut buf).unwrap();
+        assert_eq!(buf, encoded);
+        assert_eq!(info.length() as usize, encoded.len());
+        assert_eq!(InformationType::decode(encoded).unwrap(), info);
+    }
+
+    #[test]
+    fn t_fixed_length_types() {
+        round_trip(InformationType::PlMediumInformation(0x1234), &[0x01, 0x02, 0x12, 0x34]);
+        round_trip(InformationType::RfMediumInformation{rf_info: 0x02, serial_number: [0x00, 0xFA, 0x01, 0x02, 0x03, 0x04], lfn: 0x05},
+                   &[0x02, 0x08, 0x02, 0x00, 0xFA, 0x01, 0x02, 0x03, 0x04, 0x05]);
+        round_trip(InformationType::BusmonitorStatusInfo(0x80), &[0x03, 0x01, 0x80]);
+        round_trip(InformationType::TimestampRelative(0xABCD), &[0x04, 0x02, 0xAB, 0xCD]);
+        round_trip(InformationType::TimeDelayUntilSending(0x01020304), &[0x05, 0x04, 0x01, 0x02, 0x03, 0x04]);
+        round_trip(InformationType::ExtendedRelativeTimestamp(0x05060708), &[0x06, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        round_trip(InformationType::BiBatInformation{control: 0x10, block_number: 0x03}, &[0x07, 0x02, 0x10, 0x03]);
+        round_trip(InformationType::RfMultiInformation{transmission_frequency: 0x01, call_channel: 0x02, fast_ack: 0x03, receiver_frequency: 0x04},
+                   &[0x08, 0x04, 0x01, 0x02, 0x03, 0x04]);
+        round_trip(InformationType::PreambleAndPostamble{preamble_length: 0x0102, postamble_length: 0x03}, &[0x09, 0x03, 0x01, 0x02, 0x03]);
     }
 
+    #[test]
+    fn t_variable_length_types() {
+        round_trip(InformationType::RfFastAckInformation(vec![(0x01, 0x02), (0x03, 0x04)]), &[0x0A, 0x04, 0x01, 0x02, 0x03, 0x04]);
+        round_trip(InformationType::RfFastAckInformation(vec![]), &[0x0A, 0x00]);
+        round_trip(InformationType::ManufacturerSpecific(vec![0x00, 0xFA, 0x01]), &[0xFE, 0x03, 0x00, 0xFA, 0x01]);
+        round_trip(InformationType::Reserved(vec![0x01]), &[0xFF, 0x01, 0x01]);
+
+        let mut encoded = vec![0xFE, MAX_INFORMATION_LENGTH as u8];
+        encoded.extend([0x55; MAX_INFORMATION_LENGTH]);
+        round_trip(InformationType::ManufacturerSpecific(vec![0x55; MAX_INFORMATION_LENGTH]), &encoded);
+    }
+
+    #[test]
+    fn t_invalid_information() {
+        // fixed length types with a wrong length
+        assert_eq!(InformationType::decode(&[0x01, 0x03, 0x12, 0x34, 0x56]), Err(KnxNetIpError::InvalidSize));
+        // odd number of fast acknowledgement bytes
+        assert_eq!(InformationType::decode(&[0x0A, 0x03, 0x01, 0x02, 0x03]), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(InformationType::decode(&[0x0B, 0x01, 0x00]), Err(KnxNetIpError::Unknown));
+        assert_eq!(InformationType::decode(&[0x03, 0x01]), Err(KnxNetIpError::MessageTooShort(2)));
+    }
+
+    #[test]
+    fn t_oversized_information() {
+        let mut buf = vec![];
+        let data = vec![0x55; MAX_INFORMATION_LENGTH + 1];
+        assert_eq!(InformationType::ManufacturerSpecific(data.clone()).encode(&mut buf), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(InformationType::Reserved(data).encode(&mut buf), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(InformationType::RfFastAckInformation(vec![(0x01, 0x02); 127]).encode(&mut buf), Err(KnxNetIpError::InvalidSize));
+        assert!(buf.is_empty());
+
+        // the fields fit on their own but not together
+        let infos = vec![InformationType::Reserved(vec![0x01; 200]), InformationType::Reserved(vec![0x02; 100])];
+        assert_eq!(InformationType::encode_all(&infos, &mut buf), Err(KnxNetIpError::InvalidSize));
+        assert!(buf.is_empty());
+
+        InformationType::encode_all(&[InformationType::BusmonitorStatusInfo(0x80)], &mut buf).unwrap();
+        assert_eq!(buf, vec![0x03, 0x03, 0x01, 0x80]);
+    }
 }
@@ -9,6 +9,11 @@ use crate::dpt::DPT;
 use crate::knxnet::{KnxNetIpError, Service};
 
 
+/// Longest APDU fitting into a standard frame, longer ones up to `MAX_APDU_LENGTH` need an extended frame
+pub(crate) const MAX_STANDARD_APDU_LENGTH: u16 = 15;
+/// Longest APDU fitting into the length octet of extended frames, 255 is reserved for escape
+pub(crate) const MAX_APDU_LENGTH: u16 = 254;
+
 #[derive(FromRepr, Debug, Copy, Clone, PartialEq, Default)]
 #[repr(u8)]
 pub enum FrameType{
@@ -124,12 +129,19 @@ impl<D: DPT+Default> Default for LData<D>{
 
 
 impl<D:DPT+Default> LData<D> {
-    pub(crate) fn length(&self)->u8 {
-        return (8 + self.data.length()) as u8
+    pub(crate) fn length(&self)->u16 {
+        return 8 + self.data.length()
+    }
+
+    /// Whether the APDU fits into the length octet of the frame, longer ones can not be encoded
+    pub(crate) fn valid_length(&self) -> bool {
+        self.data.length() <= MAX_APDU_LENGTH
     }
 
     pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
-        buf.push(self.frame_type as u8|self.repetition as u8|self.system_broadcast as u8|self.priority as u8|self.acknowledge as u8|self.confirmation as u8);
+        // APDUs exceeding standard frames are sent in extended frames automatically
+        let frame_type = if self.data.length() > MAX_STANDARD_APDU_LENGTH {FrameType::Extended} else {self.frame_type};
+        buf.push(frame_type as u8|self.repetition as u8|self.system_broadcast as u8|self.priority as u8|self.acknowledge as u8|self.confirmation as u8);
         buf.push(self.destination_address_type as u8 | ((self.hop_count&0x7) << 4) | self.frame_format as u8);
         buf.extend(self.source.to_be_bytes());
         buf.extend(self.destination.to_be_bytes());
@@ -139,7 +151,7 @@ impl<D:DPT+Default> LData<D> {
     }
 
     pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
-        if buf.len() < 8  || buf.len() < (8 + buf[6]) as usize {
+        if buf.len() < 8  || buf.len() < 8 + buf[6] as usize {
             return Err(KnxNetIpError::MessageTooShort(buf.len()))
         }
         self.frame_type = FrameType::from_repr(buf[0]&0x80).unwrap();
@@ -161,4 +173,16 @@ impl<D:DPT+Default> LData<D> {
         return Ok(());
     }
 
+    /// Converts the frame to one with raw APDU data, which can be cloned and queued independent of the DPT
+    pub(crate) fn to_raw(&self) -> Result<LData<Vec<u8>>, KnxNetIpError> {
+        if !self.valid_length() {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        let mut buf = vec![];
+        self.encode(&mut buf);
+        let mut raw = LData::<Vec<u8>>::default();
+        raw.decode(&buf)?;
+        Ok(raw)
+    }
+
 }
\ No newline at end of file
@@ -2,11 +2,13 @@ use strum_macros::FromRepr;
 use crate::dpt::DPT;
 
 pub(crate) mod l_data;
-mod information;
+pub(crate) mod property;
+pub(crate) mod information;
 pub mod apdu;
 
 use crate::cemi::information::InformationType;
 use crate::cemi::l_data::LData;
+use crate::cemi::property::{FunctionProperty, Property};
 use crate::knxnet::KnxNetIpError;
 
 
@@ -18,28 +20,46 @@ pub enum Message<D:DPT+Default> {
     LDataInd(Vec<InformationType>, LData<D>) = 0x29, // received frame
     LDataCon(Vec<InformationType>, LData<D>) = 0x2E, // confirmation
 
-    //for raw mode
-    LRawReq = 0x10,
-    LRawCon = 0x2F,
-    LRawInd = 0x2D,
+    //for raw mode, carrying the raw frame
+    LRawReq(Vec<InformationType>, Vec<u8>) = 0x10,
+    LRawCon(Vec<InformationType>, Vec<u8>) = 0x2F,
+    LRawInd(Vec<InformationType>, Vec<u8>) = 0x2D,
 
     //Common
     MResetReq = 0xF1,
+    MResetInd = 0xF0,
 
-    //For busmon
-    LBusmonInd = 0x2B,
+    //For device management
+    MPropReadReq(Property) = 0xFC,
+    MPropReadCon(Property) = 0xFB,
+    MPropWriteReq(Property) = 0xF6,
+    MPropWriteCon(Property) = 0xF5,
+    MPropInfoInd(Property) = 0xF7,
+    MFuncPropCommandReq(FunctionProperty) = 0xF8,
+    MFuncPropStateReadReq(FunctionProperty) = 0xF9,
+    // confirmation of both, commands and state reads
+    MFuncPropCon(FunctionProperty) = 0xFA,
+
+    //For busmon, carrying the raw frame
+    LBusmonInd(Vec<InformationType>, Vec<u8>) = 0x2B,
 
     #[default]
     None,
 }
 
 impl<D:DPT+Default> Message<D> {
-    pub(crate) fn length(&self)->u8 {
-        return 2 + match self {
-            Self::LDataReq(info, data) => info.iter().map(|i| {i.length()}).sum::<u8>() + data.length(),
-            Self::LDataInd(info, data) => info.iter().map(|i| {i.length()}).sum::<u8>() + data.length(),
-            Self::LDataCon(info, data) => info.iter().map(|i| {i.length()}).sum::<u8>() + data.length(),
-            _ => 0
+    pub(crate) fn length(&self)->u16 {
+        match self {
+            Self::LDataReq(info, data) => 2 + info.iter().map(|i| {i.length() as u16}).sum::<u16>() + data.length(),
+            Self::LDataInd(info, data) => 2 + info.iter().map(|i| {i.length() as u16}).sum::<u16>() + data.length(),
+            Self::LDataCon(info, data) => 2 + info.iter().map(|i| {i.length() as u16}).sum::<u16>() + data.length(),
+            // management messages have no additional info field
+            Self::MPropReadReq(p) | Self::MPropReadCon(p) | Self::MPropWriteReq(p) | Self::MPropWriteCon(p) | Self::MPropInfoInd(p) => 1 + p.length(),
+            Self::MFuncPropCommandReq(p) | Self::MFuncPropStateReadReq(p) | Self::MFuncPropCon(p) => 1 + p.length(),
+            Self::MResetReq | Self::MResetInd => 1,
+            Self::LRawReq(info, frame) | Self::LRawCon(info, frame) | Self::LRawInd(info, frame) |
+            Self::LBusmonInd(info, frame) => 2 + info.iter().map(|i| {i.length() as u16}).sum::<u16>() + frame.len() as u16,
+            _ => 2
         }
     }
 
@@ -54,24 +74,40 @@ impl<D:DPT+Default> Message<D> {
         buf.push(self.identifier());
         match self {
             Self::LDataReq(info, data) => {
-                buf.push(info.iter().map(|i| {i.length()}).sum());
-                info.iter().for_each(|i| {i.encode(buf)});
+                Self::encode_information(info, buf);
                 data.encode(buf);
             },
             Self::LDataInd(info, data) => {
-                buf.push(info.iter().map(|i| {i.length()}).sum());
-                info.iter().for_each(|i| {i.encode(buf)});
+                Self::encode_information(info, buf);
                 data.encode(buf);
             },
             Self::LDataCon(info, data) => {
-                buf.push(info.iter().map(|i| {i.length()}).sum());
-                info.iter().for_each(|i| {i.encode(buf)});
+                Self::encode_information(info, buf);
                 data.encode(buf);
             },
+            Self::MPropReadReq(p) | Self::MPropReadCon(p) | Self::MPropWriteReq(p) | Self::MPropWriteCon(p) | Self::MPropInfoInd(p) => {
+                p.encode(buf);
+            },
+            Self::MFuncPropCommandReq(p) | Self::MFuncPropStateReadReq(p) | Self::MFuncPropCon(p) => {
+                p.encode(buf);
+            },
+            Self::LRawReq(info, frame) | Self::LRawCon(info, frame) | Self::LRawInd(info, frame) |
+            Self::LBusmonInd(info, frame) => {
+                Self::encode_information(info, buf);
+                buf.extend_from_slice(frame);
+            },
             _ => {}
         }
     }
 
+    fn encode_information(info: &[InformationType], buf: &mut Vec<u8>) {
+        // additional information is only built by decoding a field, which always fits,
+        // oversized information is left out instead of corrupting the frame
+        if InformationType::encode_all(info, buf).is_err() {
+            buf.push(0);
+        }
+    }
+
     pub(crate) fn decode(buf: &[u8]) -> Result<Message<D>, KnxNetIpError> {
         if buf.len() < 1 {
             return Err(KnxNetIpError::MessageTooShort(buf.len()))
@@ -84,23 +120,37 @@ impl<D:DPT+Default> Message<D> {
                         if buf.len() < 2 || buf.len() < 2 + buf[1] as usize {
                             return Err(KnxNetIpError::MessageTooShort(buf.len()))
                         }
-                        if buf[1] > 0 {/*TODO implement info vec decoding*/}
+                        *info = InformationType::decode_all(&buf[2..(2+buf[1] as usize)])?;
                         data.decode(&buf[(2+buf[1] as usize)..])?;
                     },
                     Self::LDataInd(ref mut info, ref mut data) => {
                         if buf.len() < 2 || buf.len() < 2 + buf[1] as usize {
                             return Err(KnxNetIpError::MessageTooShort(buf.len()))
                         }
-                        if buf[1] > 0 {/*TODO implement info vec decoding*/}
+                        *info = InformationType::decode_all(&buf[2..(2+buf[1] as usize)])?;
                         data.decode(&buf[(2+buf[1] as usize)..])?;
                     },
                     Self::LDataCon(ref mut info, ref mut data) => {
                         if buf.len() < 2 || buf.len() < 2 + buf[1] as usize {
                             return Err(KnxNetIpError::MessageTooShort(buf.len()))
                         }
-                        if buf[1] > 0 {/*TODO implement info vec decoding*/}
+                        *info = InformationType::decode_all(&buf[2..(2+buf[1] as usize)])?;
                         data.decode(&buf[(2+buf[1] as usize)..])?;
                     },
+                    Self::MPropReadReq(ref mut p) | Self::MPropReadCon(ref mut p) | Self::MPropWriteReq(ref mut p) | Self::MPropWriteCon(ref mut p) | Self::MPropInfoInd(ref mut p) => {
+                        p.decode(&buf[1..])?;
+                    },
+                    Self::MFuncPropCommandReq(ref mut p) | Self::MFuncPropStateReadReq(ref mut p) | Self::MFuncPropCon(ref mut p) => {
+                        p.decode(&buf[1..])?;
+                    },
+                    Self::LRawReq(ref mut info, ref mut frame) | Self::LRawCon(ref mut info, ref mut frame) |
+                    Self::LRawInd(ref mut info, ref mut frame) | Self::LBusmonInd(ref mut info, ref mut frame) => {
+                        if buf.len() < 2 || buf.len() < 2 + buf[1] as usize {
+                            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+                        }
+                        *info = InformationType::decode_all(&buf[2..(2+buf[1] as usize)])?;
+                        *frame = buf[(2+buf[1] as usize)..].to_vec();
+                    },
                     _ => {}
                 };
                 Ok(msg)
@@ -116,8 +166,10 @@ mod tests {
     use crate::dpt::DPT;
     use crate::cemi::l_data::{Acknowledge, AddressType, Confirmation, FrameFormat, FrameType, LData, Priority, Repetition, SystemBroadcast};
     use crate::cemi::Message;
+    use crate::cemi::information::InformationType;
+    use crate::cemi::property::{FunctionProperty, Property};
     use crate::knxnet::connect::ConnectRequest;
-    use crate::knxnet::Service;
+    use crate::knxnet::{KnxNetIpError, Service};
 
     #[test]
     fn t_message_length() {
@@ -195,7 +247,193 @@ mod tests {
     fn t_message_decode() {
     }
 
+    #[test]
+    fn t_message_property() {
+        let read_req = Message::<()>::MPropReadReq(Property{
+            object_type: 11,
+            object_instance: 1,
+            property_id: 76,
+            count: 1,
+            start_index: 1,
+            data: vec![],
+        });
+        let mut data = vec![];
+        read_req.encode(&mut data);
+        assert_eq!(data, vec![0xFC, 0x00, 0x0B, 0x01, 0x4C, 0x10, 0x01]);
+        assert_eq!(read_req.length(), 7);
+        assert_eq!(Message::<()>::decode(&data), Ok(read_req));
+
+        let read_con = Message::<()>::decode(&[0xFB, 0x00, 0x0B, 0x01, 0x34, 0x10, 0x01, 0x11, 0xFA]).unwrap();
+        assert_eq!(read_con, Message::MPropReadCon(Property{
+            object_type: 11,
+            object_instance: 1,
+            property_id: 52,
+            count: 1,
+            start_index: 1,
+            data: vec![0x11, 0xFA],
+        }));
+
+        // negative confirmation with error code for a read only property
+        let write_con = Message::<()>::decode(&[0xF5, 0x00, 0x0B, 0x01, 0x34, 0x00, 0x01, 0x05]).unwrap();
+        match write_con {
+            Message::MPropWriteCon(p) => assert_eq!(p.error(), Some(0x05)),
+            _ => panic!("wrong message type")
+        }
+        assert!(Message::<()>::decode(&[0xF7, 0x00, 0x0B, 0x01]).is_err());
+    }
+
+    #[test]
+    fn t_message_function_property() {
+        let command = Message::<()>::MFuncPropCommandReq(FunctionProperty{
+            object_type: 8,
+            object_instance: 1,
+            property_id: 52,
+            data: vec![0x00, 0x01],
+        });
+        let mut data = vec![];
+        command.encode(&mut data);
+        assert_eq!(data, vec![0xF8, 0x00, 0x08, 0x01, 0x34, 0x00, 0x01]);
+        assert_eq!(command.length(), 7);
+        assert_eq!(Message::<()>::decode(&data), Ok(command));
+
+        let state_read = Message::<()>::decode(&[0xF9, 0x00, 0x08, 0x01, 0x34]).unwrap();
+        assert_eq!(state_read, Message::MFuncPropStateReadReq(FunctionProperty{
+            object_type: 8,
+            object_instance: 1,
+            property_id: 52,
+            data: vec![],
+        }));
+
+        // return code followed by the result
+        let con = Message::<()>::decode(&[0xFA, 0x00, 0x08, 0x01, 0x34, 0x00, 0x01]).unwrap();
+        assert_eq!(con, Message::MFuncPropCon(FunctionProperty{
+            object_type: 8,
+            object_instance: 1,
+            property_id: 52,
+            data: vec![0x00, 0x01],
+        }));
+        assert!(Message::<()>::decode(&[0xFA, 0x00, 0x08, 0x01]).is_err());
+    }
+
+    #[test]
+    fn t_message_reset() {
+        let mut data = vec![];
+        Message::<()>::MResetReq.encode(&mut data);
+        assert_eq!(data, vec![0xF1]);
+        assert_eq!(Message::<()>::MResetReq.length(), 1);
+        assert_eq!(Message::<()>::decode(&[0xF0]), Ok(Message::MResetInd));
+    }
+
     #[test]
     fn t_service_decode_errors() {
     }
+
+    #[test]
+    fn t_message_busmonitor() {
+        let data = [0x2B, 0x0D, 0x03, 0x01, 0x02, 0x04, 0x02, 0x12, 0x34, 0x06, 0x04, 0x00, 0x01, 0x02, 0x03, 0xCC];
+        let msg = Message::<()>::decode(&data).unwrap();
+        assert_eq!(msg, Message::LBusmonInd(vec![
+            InformationType::BusmonitorStatusInfo(0x02),
+            InformationType::TimestampRelative(0x1234),
+            InformationType::ExtendedRelativeTimestamp(0x00010203),
+        ], vec![0xCC]));
+        assert_eq!(msg.length(), 16);
+        let mut buf = vec![];
+        msg.encode(&mut buf);
+        assert_eq!(buf, data);
+
+        // information of unknown types is skipped
+        assert_eq!(Message::<()>::decode(&[0x2B, 0x03, 0x0B, 0x01, 0x00, 0xCC]),
+                   Ok(Message::LBusmonInd(vec![], vec![0xCC])));
+        assert_eq!(Message::<()>::decode(&[0x2B, 0x05, 0x03, 0x01, 0x02, 0x04]), Err(KnxNetIpError::MessageTooShort(6)));
+    }
+
+    #[test]
+    fn t_message_additional_info() {
+        // group value write with the relative timestamp of the gateway
+        let data = [0x29, 0x04, 0x04, 0x02, 0x12, 0x34, 0xBC, 0xE0, 0x11, 0x01, 0x00, 0x0A, 0x01, 0x00, 0x81];
+        let msg = Message::<Vec<u8>>::decode(&data).unwrap();
+        match &msg {
+            Message::LDataInd(info, l_data) => {
+                assert_eq!(info, &vec![InformationType::TimestampRelative(0x1234)]);
+                assert_eq!(l_data.destination, 10);
+            }
+            _ => panic!("unexpected message {:?}", msg)
+        }
+
+        let info = vec![
+            InformationType::PlMediumInformation(0x0102),
+            InformationType::RfMediumInformation{rf_info: 0x02, serial_number: [0x00, 0xFA, 0x01, 0x02, 0x03, 0x04], lfn: 0x05},
+            InformationType::TimeDelayUntilSending(0x01020304),
+            InformationType::BiBatInformation{control: 0x10, block_number: 0x01},
+            InformationType::RfMultiInformation{transmission_frequency: 0x01, call_channel: 0x02, fast_ack: 0x03, receiver_frequency: 0x04},
+            InformationType::PreambleAndPostamble{preamble_length: 0x0100, postamble_length: 0x02},
+            InformationType::RfFastAckInformation(vec![(0x01, 0x02), (0x03, 0x04)]),
+            InformationType::ManufacturerSpecific(vec![0x00, 0x83, 0x01]),
+        ];
+        let msg = Message::<()>::LRawInd(info, vec![0xCC]);
+        let mut buf = vec![];
+        msg.encode(&mut buf);
+        assert_eq!(buf, vec![0x2D, 0x2E,
+            0x01, 0x02, 0x01, 0x02,
+            0x02, 0x08, 0x02, 0x00, 0xFA, 0x01, 0x02, 0x03, 0x04, 0x05,
+            0x05, 0x04, 0x01, 0x02, 0x03, 0x04,
+            0x07, 0x02, 0x10, 0x01,
+            0x08, 0x04, 0x01, 0x02, 0x03, 0x04,
+            0x09, 0x03, 0x01, 0x00, 0x02,
+            0x0A, 0x04, 0x01, 0x02, 0x03, 0x04,
+            0xFE, 0x03, 0x00, 0x83, 0x01,
+            0xCC]);
+        assert_eq!(msg.length() as usize, buf.len());
+        assert_eq!(Message::<()>::decode(&buf), Ok(msg));
+
+        // fields with an invalid length are skipped
+        assert_eq!(Message::<()>::decode(&[0x2D, 0x05, 0x0A, 0x03, 0x01, 0x02, 0x03, 0xCC]), Ok(Message::LRawInd(vec![], vec![0xCC])));
+    }
+
+    #[test]
+    fn t_message_extended_frame() {
+        let l_data = LData::<Vec<u8>>{
+            destination: 10,
+            data: Apdu::GroupValueWrite((0..14).collect()),
+            ..LData::default()
+        };
+        let mut buf = vec![];
+        Message::LDataReq(vec![], l_data).encode(&mut buf);
+        // the longest APDU fitting into a standard frame
+        assert_eq!(buf[2], 0xBE);
+        assert_eq!(buf[8], 15);
+
+        let l_data = LData::<Vec<u8>>{
+            destination: 10,
+            data: Apdu::GroupValueWrite((0..253).map(|i| i as u8).collect()),
+            ..LData::default()
+        };
+        let msg = Message::LDataInd(vec![], l_data);
+        let mut buf = vec![];
+        msg.encode(&mut buf);
+        assert_eq!(msg.length(), 264);
+        assert_eq!(buf.len(), 264);
+        assert_eq!(&buf[..11], &[0x29, 0x00, 0x3E, 0xE0, 0x00, 0x00, 0x00, 0x0A, 0xFE, 0x00, 0x80]);
+        match Message::<Vec<u8>>::decode(&buf) {
+            Ok(Message::LDataInd(_, l_data)) => {
+                assert_eq!(l_data.frame_type, FrameType::Extended);
+                assert_eq!(l_data.data, Apdu::GroupValueWrite((0..253).map(|i| i as u8).collect()));
+            }
+            msg => panic!("unexpected message {:?}", msg)
+        }
+    }
+
+    #[test]
+    fn t_message_raw() {
+        // malformed frame with a wrong checksum is carried verbatim
+        let req = Message::<()>::LRawReq(vec![], vec![0xBC, 0x11, 0x01, 0x00, 0x0A, 0xE1, 0x00, 0x81, 0x00]);
+        let mut buf = vec![];
+        req.encode(&mut buf);
+        assert_eq!(buf, vec![0x10, 0x00, 0xBC, 0x11, 0x01, 0x00, 0x0A, 0xE1, 0x00, 0x81, 0x00]);
+        assert_eq!(req.length(), 11);
+        assert_eq!(Message::<()>::decode(&buf), Ok(req));
+        assert_eq!(Message::<()>::decode(&[0x2D, 0x00, 0xCC]), Ok(Message::LRawInd(vec![], vec![0xCC])));
+        assert_eq!(Message::<()>::decode(&[0x2F, 0x00, 0xBC, 0x11]), Ok(Message::LRawCon(vec![], vec![0xBC, 0x11])));
+    }
 }
@@ -0,0 +1,111 @@
+use byteorder::{BigEndian, ByteOrder};
+use crate::knxnet::KnxNetIpError;
+
+// Property services of the cEMI management server (See 3/6/3 4.1.7.3)
+
+// longest data fitting into a KNXnet/IP frame together with its headers, connection header, message code and property header
+pub(crate) const MAX_PROPERTY_DATA_LENGTH: usize = u16::MAX as usize - 17;
+// the function property header is two octets shorter, as it has no count and start index
+pub(crate) const MAX_FUNCTION_PROPERTY_DATA_LENGTH: usize = u16::MAX as usize - 15;
+// the number of elements and start index share two octets
+const MAX_COUNT: u8 = 0x0F;
+const MAX_START_INDEX: u16 = 0x0FFF;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Property {
+    pub(crate) object_type: u16,
+    pub(crate) object_instance: u8,
+    pub(crate) property_id: u8,
+    // a confirmation with zero elements carries an error code instead of data
+    pub(crate) count: u8,
+    pub(crate) start_index: u16,
+    pub(crate) data: Vec<u8>,
+}
+
+impl Property {
+    pub(crate) fn length(&self) -> u16 {
+        // only reached by invalid properties, which are rejected by `check` before they are sent
+        u16::try_from(self.data.len()).ok().and_then(|len| len.checked_add(6)).unwrap_or(u16::MAX)
+    }
+
+    /// Rejects properties which can not be encoded, as the values do not fit into their fields
+    pub(crate) fn check(&self) -> Result<(), KnxNetIpError> {
+        if self.count > MAX_COUNT || self.start_index > MAX_START_INDEX || self.data.len() > MAX_PROPERTY_DATA_LENGTH {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        Ok(())
+    }
+
+    pub(crate) fn error(&self) -> Option<u8> {
+        if self.count == 0 {
+            return Some(self.data.first().copied().unwrap_or(0))
+        }
+        None
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.object_type.to_be_bytes());
+        buf.push(self.object_instance);
+        buf.push(self.property_id);
+        buf.push((self.count << 4) | ((self.start_index >> 8) as u8 & 0x0F));
+        buf.push(self.start_index as u8);
+        buf.extend_from_slice(&self.data);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 6 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.object_type = BigEndian::read_u16(&buf[0..2]);
+        self.object_instance = buf[2];
+        self.property_id = buf[3];
+        self.count = buf[4] >> 4;
+        self.start_index = BigEndian::read_u16(&buf[4..6]) & 0x0FFF;
+        self.data = buf[6..].to_vec();
+        Ok(())
+    }
+}
+
+// Function property services of the cEMI management server (See 3/6/3 4.1.7.3.8)
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct FunctionProperty {
+    pub(crate) object_type: u16,
+    pub(crate) object_instance: u8,
+    pub(crate) property_id: u8,
+    // a confirmation starts with the return code, a negative one carries no data at all
+    pub(crate) data: Vec<u8>,
+}
+
+impl FunctionProperty {
+    pub(crate) fn length(&self) -> u16 {
+        // only reached by invalid function properties, which are rejected by `check` before they are sent
+        u16::try_from(self.data.len()).ok().and_then(|len| len.checked_add(4)).unwrap_or(u16::MAX)
+    }
+
+    /// Rejects function properties with more data than fits into a frame
+    pub(crate) fn check(&self) -> Result<(), KnxNetIpError> {
+        if self.data.len() > MAX_FUNCTION_PROPERTY_DATA_LENGTH {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        Ok(())
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.object_type.to_be_bytes());
+        buf.push(self.object_instance);
+        buf.push(self.property_id);
+        buf.extend_from_slice(&self.data);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 4 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.object_type = BigEndian::read_u16(&buf[0..2]);
+        self.object_instance = buf[2];
+        self.property_id = buf[3];
+        self.data = buf[4..].to_vec();
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+/// Identifies a group event passed to `send`, returned again with its delivery result
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SendToken(pub(crate) u32);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeliveryResult {
+    /// The server confirmed that the frame was sent to the bus
+    Confirmed,
+    /// The server could not send the frame to the bus, e.g. because it was not acknowledged by any device
+    Failed,
+    /// The server did not acknowledge the tunnelling request, even after it was repeated
+    NotAcknowledged,
+    /// The server acknowledged the tunnelling request but did not confirm the frame in time
+    NotConfirmed,
+    /// The frame was never sent, e.g. because the connection was lost or the frame is not supported by the server
+    Dropped,
+}
+
+/// Outcome of a group event sent through a tunnel
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeliveryEvent {
+    pub token: SendToken,
+    pub result: DeliveryResult,
+}
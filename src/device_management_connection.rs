@@ -0,0 +1,553 @@
+//runtime facing functions:
+
+// get data to be transmitted next
+// get next time event
+// handle next time event
+// handle received data -> returns a property event or none
+// get requests the server rejected or did not confirm
+// read/write properties of the server's interface objects
+// invoke function properties or reset the server
+
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+use strum_macros::FromRepr;
+use crate::cemi::Message;
+use crate::cemi::property::{FunctionProperty, Property};
+use crate::knxnet::connect::ConnectRequest;
+use crate::knxnet::connectionstate::ConnectionstateRequest;
+use crate::knxnet::crd::ConnectionRespType;
+use crate::knxnet::cri::ConnectionReqType;
+use crate::knxnet::device_management::{DeviceConfigurationAck, DeviceConfigurationRequest};
+use crate::knxnet::disconnect::{DisconnectRequest, DisconnectResponse};
+use crate::knxnet::hpai::{HPAI, Protocol};
+use crate::knxnet::{KnxNetIpError, Service};
+use crate::knxnet::status::StatusCode;
+use crate::property_event::{PropertyEvent, PropertyEventType};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DeviceManagementConnectionConfig {
+    request_timeout: Duration,
+    heartbeat_interval: Duration,
+    confirmation_timeout: Duration,
+}
+
+impl Default for DeviceManagementConnectionConfig {
+    fn default() -> DeviceManagementConnectionConfig {
+        // See 3/8/3 5.3 and 3/8/2 5.4
+        DeviceManagementConnectionConfig{
+            request_timeout: Duration::from_secs(10),
+            heartbeat_interval: Duration::from_secs(60),
+            confirmation_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl DeviceManagementConnectionConfig {
+    /// Time the server has to confirm a request after acknowledging it, before `PropertyEventType::Timeout` is reported
+    pub fn with_confirmation_timeout(mut self, timeout: Duration) -> DeviceManagementConnectionConfig {
+        self.confirmation_timeout = timeout;
+        self
+    }
+}
+
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Default)]
+enum DeviceManagementConnectionState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    Disconnecting,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct PendingRequest {
+    data: Vec<u8>,
+    due: bool,
+    attempts_left: u8,
+    // cEMI request of a device configuration request, which is pending until its confirmation arrives
+    request: Option<Message<()>>,
+    acked: bool,
+}
+
+/// Management connection to the cEMI server of a KNXnet/IP device, used to access its interface object properties
+#[derive(Debug)]
+pub struct DeviceManagementConnection {
+    state: DeviceManagementConnectionState,
+    channel: u8,
+    host_info: HPAI,
+    outbound_seq: u8,
+    inbound_seq: u8,
+    requests: VecDeque<Message<()>>,
+    pending: Option<PendingRequest>,
+    ack_queue: VecDeque<Vec<u8>>,
+    current: Vec<u8>,
+    failed_requests: VecDeque<PropertyEvent>,
+    next_timeout: Instant,
+    next_heartbeat: Instant,
+    config: DeviceManagementConnectionConfig,
+}
+
+impl DeviceManagementConnection {
+    /// Create a device management connection which expects responses on the given local address and port
+    pub fn new(ipv4: [u8;4], port: u16, config: DeviceManagementConnectionConfig) -> DeviceManagementConnection {
+        let mut con = DeviceManagementConnection{
+            state: DeviceManagementConnectionState::Disconnected,
+            channel: 0,
+            host_info: HPAI::new(Protocol::Udp4Protocol, ipv4, port),
+            outbound_seq: 0,
+            inbound_seq: 0,
+            requests: VecDeque::new(),
+            pending: None,
+            ack_queue: VecDeque::new(),
+            current: vec![],
+            failed_requests: VecDeque::new(),
+            next_timeout: Instant::now().add(config.request_timeout),
+            next_heartbeat: Instant::now().add(config.heartbeat_interval),
+            config,
+        };
+        con.send_connect_request();
+        con
+    }
+
+    /// Read `count` elements of a property starting at `start_index` (index 0 reads the number of elements).
+    /// Returns `InvalidSize` if `count` exceeds 15 or `start_index` exceeds 4095.
+    pub fn read_property(&mut self, object_type: u16, object_instance: u8, property_id: u8, start_index: u16, count: u8) -> Result<(), KnxNetIpError> {
+        let property = Property{
+            object_type,
+            object_instance,
+            property_id,
+            count,
+            start_index,
+            data: vec![],
+        };
+        property.check()?;
+        self.requests.push_back(Message::MPropReadReq(property));
+        Ok(())
+    }
+
+    /// Write `count` elements of a property starting at `start_index`.
+    /// Returns `InvalidSize` if `count` exceeds 15, `start_index` exceeds 4095 or `data` does not fit into a frame.
+    pub fn write_property(&mut self, object_type: u16, object_instance: u8, property_id: u8, start_index: u16, count: u8, data: Vec<u8>) -> Result<(), KnxNetIpError> {
+        let property = Property{
+            object_type,
+            object_instance,
+            property_id,
+            count,
+            start_index,
+            data,
+        };
+        property.check()?;
+        self.requests.push_back(Message::MPropWriteReq(property));
+        Ok(())
+    }
+
+    /// Invoke a function property of an interface object with the given input data.
+    /// The result is returned as `PropertyEventType::FunctionResponse` starting with the return code.
+    /// Returns `InvalidSize` if `data` does not fit into a frame.
+    pub fn function_property_command(&mut self, object_type: u16, object_instance: u8, property_id: u8, data: Vec<u8>) -> Result<(), KnxNetIpError> {
+        let property = FunctionProperty{
+            object_type,
+            object_instance,
+            property_id,
+            data,
+        };
+        property.check()?;
+        self.requests.push_back(Message::MFuncPropCommandReq(property));
+        Ok(())
+    }
+
+    /// Read the state of a function property of an interface object, `data` is passed to the function unchanged.
+    /// Returns `InvalidSize` if `data` does not fit into a frame.
+    pub fn function_property_state_read(&mut self, object_type: u16, object_instance: u8, property_id: u8, data: Vec<u8>) -> Result<(), KnxNetIpError> {
+        let property = FunctionProperty{
+            object_type,
+            object_instance,
+            property_id,
+            data,
+        };
+        property.check()?;
+        self.requests.push_back(Message::MFuncPropStateReadReq(property));
+        Ok(())
+    }
+
+    /// Reset the cEMI server, which reports `PropertyEventType::Reset` once it restarted.
+    /// If the server closes the connection while resetting, it is reestablished.
+    pub fn reset(&mut self) {
+        self.requests.push_back(Message::MResetReq);
+    }
+
+    /// Close the connection, queued requests which were not sent yet are dropped
+    pub fn disconnect(&mut self) {
+        self.requests.clear();
+        if self.state != DeviceManagementConnectionState::Connected {
+            self.pending = None;
+            self.state = DeviceManagementConnectionState::Disconnected;
+            return
+        }
+        let req: Service<()> = Service::DisconnectRequest(DisconnectRequest{
+            channel: self.channel,
+            control: self.host_info,
+        });
+        self.state = DeviceManagementConnectionState::Disconnecting;
+        self.set_pending(req.encoded(), 1);
+    }
+
+    pub fn connected(&self) -> bool {
+        self.state == DeviceManagementConnectionState::Connected
+    }
+
+    /// Next request the server rejected (`PropertyEventType::Rejected`) or did not confirm in time
+    /// (`PropertyEventType::Timeout`), identified by its object type, instance and property id
+    pub fn get_failed_request(&mut self) -> Option<PropertyEvent> {
+        self.failed_requests.pop_front()
+    }
+
+    /// Data which needs to be sent to the control endpoint of the server
+    pub fn get_outbound_data(&mut self) -> Option<&[u8]> {
+        if let Some(ack) = self.ack_queue.pop_front() {
+            self.current = ack;
+            return Some(&self.current)
+        }
+        // the cEMI server handles only one request at a time, the next one is sent once the previous one is confirmed
+        if self.pending.is_none() && self.state == DeviceManagementConnectionState::Connected {
+            if let Some(msg) = self.requests.pop_front() {
+                let req = Service::DeviceConfigurationRequest(DeviceConfigurationRequest{
+                    channel: self.channel,
+                    seq: self.outbound_seq,
+                    data: msg.clone(),
+                });
+                self.pending = Some(PendingRequest{
+                    data: req.encoded(),
+                    due: true,
+                    attempts_left: 2,
+                    request: Some(msg),
+                    acked: false,
+                });
+            }
+        }
+        let pending = self.pending.as_mut()?;
+        if !pending.due {
+            return None
+        }
+        pending.due = false;
+        pending.attempts_left -= 1;
+        self.next_timeout = Instant::now().add(self.config.request_timeout);
+        Some(&pending.data)
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        let mut next = Instant::now().add(self.config.heartbeat_interval);
+        if self.pending.is_some() {
+            next = min(next, self.next_timeout);
+        }
+        if self.state == DeviceManagementConnectionState::Connected {
+            next = min(next, self.next_heartbeat);
+        }
+        next
+    }
+
+    pub fn handle_time_events(&mut self) {
+        let now = Instant::now();
+        if self.pending.as_ref().is_some_and(|pending| pending.acked) && self.next_timeout <= now {
+            // the server accepted the request but never confirmed it
+            let request = self.pending.take().and_then(|pending| pending.request);
+            self.report_failure(PropertyEventType::Timeout, request);
+        }
+        if let Some(pending) = self.pending.as_mut() {
+            if !pending.due && !pending.acked && self.next_timeout <= now {
+                if pending.attempts_left > 0 {
+                    pending.due = true;
+                } else {
+                    // no response at all, the server probably lost the connection
+                    self.pending = None;
+                    match self.state {
+                        DeviceManagementConnectionState::Disconnecting => self.state = DeviceManagementConnectionState::Disconnected,
+                        _ => self.send_connect_request(),
+                    }
+                }
+            }
+        }
+        if self.state == DeviceManagementConnectionState::Connected && self.pending.is_none() && self.next_heartbeat <= now {
+            let req: Service<()> = Service::ConnectionstateRequest(ConnectionstateRequest{
+                channel: self.channel,
+                control: self.host_info,
+            });
+            self.set_pending(req.encoded(), 3);
+            self.next_heartbeat = now.add(self.config.heartbeat_interval);
+        }
+    }
+
+    pub fn handle_inbound_message(&mut self, data: &[u8]) -> Option<PropertyEvent> {
+        match Service::<()>::decoded(data) {
+            Ok(Service::ConnectResponse(resp)) => {
+                if self.state == DeviceManagementConnectionState::Connecting && resp.status == StatusCode::NoError
+                    && resp.connection_type == ConnectionRespType::DeviceMgmtConnection {
+                    self.channel = resp.channel;
+                    self.pending = None;
+                    self.state = DeviceManagementConnectionState::Connected;
+                    self.next_heartbeat = Instant::now().add(self.config.heartbeat_interval);
+                }
+                None
+            }
+            Ok(Service::ConnectionstateResponse(resp)) if resp.channel == self.channel => {
+                self.pending = None;
+                if resp.status != StatusCode::NoError {
+                    self.send_connect_request();
+                }
+                None
+            }
+            Ok(Service::DisconnectRequest(req)) if req.channel == self.channel => {
+                self.ack_queue.push_back(Service::<()>::DisconnectResponse(DisconnectResponse{
+                    channel: self.channel,
+                    status: StatusCode::NoError,
+                }).encoded());
+                if self.state != DeviceManagementConnectionState::Disconnecting {
+                    self.send_connect_request();
+                }
+                None
+            }
+            Ok(Service::DisconnectResponse(resp)) if resp.channel == self.channel => {
+                if self.state == DeviceManagementConnectionState::Disconnecting {
+                    self.pending = None;
+                    self.state = DeviceManagementConnectionState::Disconnected;
+                }
+                None
+            }
+            Ok(Service::DeviceConfigurationAck(ack)) if ack.channel == self.channel => {
+                let awaiting_ack = self.pending.as_ref().is_some_and(|pending| pending.request.is_some() && !pending.acked);
+                if self.state != DeviceManagementConnectionState::Connected || ack.seq != self.outbound_seq || !awaiting_ack {
+                    return None
+                }
+                if ack.status != StatusCode::NoError {
+                    // rejected requests are not repeated, the server did not advance its sequence counter though
+                    let request = self.pending.take().and_then(|pending| pending.request);
+                    self.report_failure(PropertyEventType::Rejected, request);
+                    return None
+                }
+                self.outbound_seq = self.outbound_seq.wrapping_add(1);
+                match self.pending.as_mut() {
+                    // a reset is not confirmed, the server restarts instead
+                    Some(PendingRequest{request: Some(Message::MResetReq), ..}) => self.pending = None,
+                    Some(pending) => {
+                        pending.acked = true;
+                        self.next_timeout = Instant::now().add(self.config.confirmation_timeout);
+                    }
+                    None => {}
+                }
+                None
+            }
+            Ok(Service::DeviceConfigurationRequest(req)) if req.channel == self.channel => {
+                // repeated requests are acknowledged again but only handled once (See 3/8/3 4.2.6)
+                if req.seq != self.inbound_seq && req.seq.wrapping_add(1) != self.inbound_seq {
+                    return None
+                }
+                self.ack_queue.push_back(Service::<()>::DeviceConfigurationAck(DeviceConfigurationAck{
+                    channel: self.channel,
+                    seq: req.seq,
+                    status: StatusCode::NoError,
+                }).encoded());
+                if req.seq != self.inbound_seq {
+                    return None
+                }
+                self.inbound_seq = self.inbound_seq.wrapping_add(1);
+                if self.pending.as_ref().and_then(|pending| pending.request.as_ref()).is_some_and(|r| confirms(r, &req.data)) {
+                    // the confirmation might overtake the ack, which is ignored then
+                    if self.pending.as_ref().is_some_and(|pending| !pending.acked) {
+                        self.outbound_seq = self.outbound_seq.wrapping_add(1);
+                    }
+                    self.pending = None;
+                }
+                PropertyEvent::from_message(req.data)
+            }
+            _ => None
+        }
+    }
+
+    fn set_pending(&mut self, data: Vec<u8>, attempts: u8) {
+        self.pending = Some(PendingRequest{
+            data,
+            due: true,
+            attempts_left: attempts,
+            request: None,
+            acked: false,
+        });
+    }
+
+    fn report_failure(&mut self, event_type: PropertyEventType, request: Option<Message<()>>) {
+        if let Some(ev) = request.and_then(|r| PropertyEvent::from_request(event_type, r)) {
+            self.failed_requests.push_back(ev);
+        }
+    }
+
+    fn send_connect_request(&mut self) {
+        let req: Service<()> = Service::ConnectRequest(ConnectRequest{
+            data: self.host_info,
+            control: self.host_info,
+            connection_type: ConnectionReqType::DeviceMgmtConnection,
+        });
+        self.inbound_seq = 0;
+        self.outbound_seq = 0;
+        self.ack_queue.clear();
+        self.state = DeviceManagementConnectionState::Connecting;
+        self.set_pending(req.encoded(), 2);
+    }
+}
+
+// whether the message is the confirmation of the request, negative ones included
+fn confirms(request: &Message<()>, con: &Message<()>) -> bool {
+    match (request, con) {
+        (Message::MPropReadReq(r), Message::MPropReadCon(c)) | (Message::MPropWriteReq(r), Message::MPropWriteCon(c)) =>
+            (r.object_type, r.object_instance, r.property_id) == (c.object_type, c.object_instance, c.property_id),
+        (Message::MFuncPropCommandReq(r) | Message::MFuncPropStateReadReq(r), Message::MFuncPropCon(c)) =>
+            (r.object_type, r.object_instance, r.property_id) == (c.object_type, c.object_instance, c.property_id),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::knxnet::KnxNetIpError;
+    use crate::device_management_connection::{DeviceManagementConnection, DeviceManagementConnectionConfig};
+    use crate::property_event::{PropertyError, PropertyEventType, OBJECT_TYPE_KNXNET_IP_PARAMETER, OBJECT_TYPE_SECURITY, PID_FRIENDLY_NAME, PID_KNX_INDIVIDUAL_ADDRESS, PID_SECURITY_MODE};
+
+    fn connect() -> DeviceManagementConnection {
+        let mut con = DeviceManagementConnection::new([192, 168, 1, 10], 3671, DeviceManagementConnectionConfig::default());
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x02, 0x05, 0x00, 0x18,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57,
+            0x02, 0x03][..]));
+        assert!(con.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x12, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x01, 0x0E, 0x57, 0x02, 0x03]).is_none());
+        assert!(con.connected());
+        con
+    }
+
+    #[test]
+    fn t_device_management_read_property() {
+        let mut con = connect();
+        con.read_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_FRIENDLY_NAME, 1, 1).unwrap();
+        con.read_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_KNX_INDIVIDUAL_ADDRESS, 1, 1).unwrap();
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x11, 0x04, 0x15, 0x00, 0x00,
+            0xFC, 0x00, 0x0B, 0x01, 0x4C, 0x10, 0x01][..]));
+        // second request waits for the confirmation of the first one, not only for its ack
+        assert_eq!(con.get_outbound_data(), None);
+        assert!(con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]).is_none());
+        assert_eq!(con.get_outbound_data(), None);
+        let ev = con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x12, 0x04, 0x15, 0x00, 0x00,
+            0xFB, 0x00, 0x0B, 0x01, 0x4C, 0x10, 0x01, 0x4B]).unwrap();
+        assert_eq!(ev.property_id, PID_FRIENDLY_NAME);
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00][..]));
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x11, 0x04, 0x15, 0x01, 0x00,
+            0xFC, 0x00, 0x0B, 0x01, 0x34, 0x10, 0x01][..]));
+        assert!(con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00]).is_none());
+
+        let ev = con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x13, 0x04, 0x15, 0x01, 0x00,
+            0xFB, 0x00, 0x0B, 0x01, 0x34, 0x10, 0x01, 0x11, 0xFA]).unwrap();
+        assert_eq!(ev.event_type, PropertyEventType::ReadResponse);
+        assert_eq!(ev.property_id, PID_KNX_INDIVIDUAL_ADDRESS);
+        assert_eq!(ev.data, vec![0x11, 0xFA]);
+        assert_eq!(ev.error, None);
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00][..]));
+
+        // a repeated confirmation is acknowledged but not reported twice
+        assert!(con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x13, 0x04, 0x15, 0x01, 0x00,
+            0xFB, 0x00, 0x0B, 0x01, 0x34, 0x10, 0x01, 0x11, 0xFA]).is_none());
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00][..]));
+        assert_eq!(con.get_outbound_data(), None);
+
+        // values not fitting into their fields are rejected right away
+        assert_eq!(con.read_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_FRIENDLY_NAME, 1, 16), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(con.read_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_FRIENDLY_NAME, 0x1000, 1), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(con.write_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_FRIENDLY_NAME, 1, 1, vec![0; 0x10000]), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(con.get_outbound_data(), None);
+    }
+
+    #[test]
+    fn t_device_management_failed_requests() {
+        let mut con = DeviceManagementConnection::new([192, 168, 1, 10], 3671,
+            DeviceManagementConnectionConfig::default().with_confirmation_timeout(Duration::ZERO));
+        con.get_outbound_data();
+        con.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x12, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x01, 0x0E, 0x57, 0x02, 0x03]);
+        con.read_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_FRIENDLY_NAME, 1, 1).unwrap();
+        con.read_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_KNX_INDIVIDUAL_ADDRESS, 1, 1).unwrap();
+        con.read_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_FRIENDLY_NAME, 1, 1).unwrap();
+        assert_eq!(con.get_outbound_data().map(|d| (d[8], d[14])), Some((0x00, PID_FRIENDLY_NAME)));
+        con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        std::thread::sleep(Duration::from_millis(1));
+        con.handle_time_events();
+        let ev = con.get_failed_request().unwrap();
+        assert_eq!((ev.event_type, ev.property_id), (PropertyEventType::Timeout, PID_FRIENDLY_NAME));
+
+        // a rejected request is not repeated and its sequence number is used for the next one
+        assert_eq!(con.get_outbound_data().map(|d| (d[8], d[14])), Some((0x01, PID_KNX_INDIVIDUAL_ADDRESS)));
+        con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x29]);
+        let ev = con.get_failed_request().unwrap();
+        assert_eq!((ev.event_type, ev.property_id), (PropertyEventType::Rejected, PID_KNX_INDIVIDUAL_ADDRESS));
+        assert_eq!(con.get_outbound_data().map(|d| (d[8], d[14])), Some((0x01, PID_FRIENDLY_NAME)));
+        assert_eq!(con.get_failed_request(), None);
+    }
+
+    #[test]
+    fn t_device_management_write_property() {
+        let mut con = connect();
+        con.write_property(OBJECT_TYPE_KNXNET_IP_PARAMETER, 1, PID_KNX_INDIVIDUAL_ADDRESS, 1, 1, vec![0x11, 0x05]).unwrap();
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x13, 0x04, 0x15, 0x00, 0x00,
+            0xF6, 0x00, 0x0B, 0x01, 0x34, 0x10, 0x01, 0x11, 0x05][..]));
+        let ev = con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x12, 0x04, 0x15, 0x00, 0x00,
+            0xF5, 0x00, 0x0B, 0x01, 0x34, 0x00, 0x01, 0x05]).unwrap();
+        assert_eq!(ev.event_type, PropertyEventType::WriteResponse);
+        assert_eq!(ev.error, Some(PropertyError::ReadOnly));
+
+        con.disconnect();
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00][..]));
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x02, 0x09, 0x00, 0x10, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57][..]));
+        con.handle_inbound_message(&[0x06, 0x10, 0x02, 0x0A, 0x00, 0x08, 0x15, 0x00]);
+        assert!(!con.connected());
+        assert_eq!(con.get_outbound_data(), None);
+    }
+
+    #[test]
+    fn t_device_management_function_property() {
+        let mut con = connect();
+        // switch on the security mode
+        con.function_property_command(OBJECT_TYPE_SECURITY, 1, PID_SECURITY_MODE, vec![0x00, 0x01]).unwrap();
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x11, 0x04, 0x15, 0x00, 0x00,
+            0xF8, 0x00, 0x11, 0x01, 0x33, 0x00, 0x01][..]));
+        assert!(con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]).is_none());
+        let ev = con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x11, 0x04, 0x15, 0x00, 0x00,
+            0xFA, 0x00, 0x11, 0x01, 0x33, 0x00, 0x00]).unwrap();
+        assert_eq!(ev.event_type, PropertyEventType::FunctionResponse);
+        assert_eq!(ev.data, vec![0x00, 0x00]);
+        assert_eq!(ev.error, None);
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00][..]));
+
+        con.function_property_state_read(OBJECT_TYPE_SECURITY, 1, PID_SECURITY_MODE, vec![0x00]).unwrap();
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x10, 0x04, 0x15, 0x01, 0x00,
+            0xF9, 0x00, 0x11, 0x01, 0x33, 0x00][..]));
+        assert!(con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00]).is_none());
+        // the function property is not supported by the server
+        let ev = con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x0F, 0x04, 0x15, 0x01, 0x00,
+            0xFA, 0x00, 0x11, 0x01, 0x33]).unwrap();
+        assert_eq!(ev.error, Some(PropertyError::Unspecified));
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00][..]));
+
+        assert_eq!(con.function_property_command(OBJECT_TYPE_SECURITY, 1, PID_SECURITY_MODE, vec![0; 0x10000]), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(con.function_property_state_read(OBJECT_TYPE_SECURITY, 1, PID_SECURITY_MODE, vec![0; 0xFFF1]), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(con.get_outbound_data(), None);
+    }
+
+    #[test]
+    fn t_device_management_reset() {
+        let mut con = connect();
+        con.reset();
+        assert_eq!(con.get_outbound_data(), Some(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x0B, 0x04, 0x15, 0x00, 0x00, 0xF1][..]));
+        assert!(con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]).is_none());
+        let ev = con.handle_inbound_message(&[0x06, 0x10, 0x03, 0x10, 0x00, 0x0B, 0x04, 0x15, 0x00, 0x00, 0xF0]).unwrap();
+        assert_eq!(ev.event_type, PropertyEventType::Reset);
+    }
+}
@@ -0,0 +1,396 @@
+//runtime facing functions:
+
+// get search/description request to be sent out
+// handle received data -> returns a found gateway/description or none
+// get next time event
+// handle next time event -> search finishes after the timeout
+
+use std::ops::Add;
+use std::time::{Duration, Instant};
+use crate::knxnet::description::DescriptionRequest;
+use crate::knxnet::dib::{CurrentIpConfig, DeviceInformation, Dib, ExtendedDeviceInformation, IpConfig, KnxAddresses, ManufacturerData, ServiceFamilies, TunnellingInfo};
+use crate::knxnet::hpai::{HPAI, Protocol};
+use crate::knxnet::search::{SearchRequest, SearchRequestExtended};
+use crate::knxnet::srp::Srp;
+use crate::knxnet::Service;
+
+/// Multicast address search requests need to be sent to
+pub const KNX_MULTICAST_ADDRESS: [u8;4] = [224, 0, 23, 12];
+/// Default KNXnet/IP port
+pub const KNX_PORT: u16 = 3671;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DiscoveryConfig {
+    search_timeout: Duration,
+    description_timeout: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> DiscoveryConfig {
+        DiscoveryConfig{
+            search_timeout: Duration::from_secs(3),
+            description_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl DiscoveryConfig {
+    /// Time responses to a search request are collected
+    pub fn with_search_timeout(mut self, timeout: Duration) -> DiscoveryConfig {
+        self.search_timeout = timeout;
+        self
+    }
+
+    /// Time to wait for the response to a description request
+    pub fn with_description_timeout(mut self, timeout: Duration) -> DiscoveryConfig {
+        self.description_timeout = timeout;
+        self
+    }
+}
+
+/// A KNXnet/IP server which answered a search request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gateway {
+    /// Control endpoint of the server. An address of 0.0.0.0:0 means the server is behind a NAT
+    /// and the sender address of the response has to be used instead.
+    pub control: HPAI,
+    /// Device information, missing if the server left it out, e.g. as it was not requested by an extended search
+    pub device: Option<DeviceInformation>,
+    pub services: Option<ServiceFamilies>,
+    /// All DIBs of the response including the ones requested by an extended search
+    pub description: Description,
+}
+
+/// Self description of a KNXnet/IP server
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Description {
+    pub dibs: Vec<Dib>,
+}
+
+impl Description {
+    pub fn device_information(&self) -> Option<&DeviceInformation> {
+        self.dibs.iter().find_map(|d| if let Dib::DeviceInfo(i) = d { Some(i) } else { None })
+    }
+
+    pub fn supported_services(&self) -> Option<&ServiceFamilies> {
+        self.dibs.iter().find_map(|d| if let Dib::SupportedServiceFamilies(s) = d { Some(s) } else { None })
+    }
+
+    pub fn secured_services(&self) -> Option<&ServiceFamilies> {
+        self.dibs.iter().find_map(|d| if let Dib::SecuredServiceFamilies(s) = d { Some(s) } else { None })
+    }
+
+    pub fn ip_config(&self) -> Option<&IpConfig> {
+        self.dibs.iter().find_map(|d| if let Dib::IpConfig(c) = d { Some(c) } else { None })
+    }
+
+    pub fn current_ip_config(&self) -> Option<&CurrentIpConfig> {
+        self.dibs.iter().find_map(|d| if let Dib::CurrentIpConfig(c) = d { Some(c) } else { None })
+    }
+
+    pub fn knx_addresses(&self) -> Option<&KnxAddresses> {
+        self.dibs.iter().find_map(|d| if let Dib::KnxAddresses(a) = d { Some(a) } else { None })
+    }
+
+    pub fn tunnelling_info(&self) -> Option<&TunnellingInfo> {
+        self.dibs.iter().find_map(|d| if let Dib::TunnellingInfo(t) = d { Some(t) } else { None })
+    }
+
+    pub fn extended_device_information(&self) -> Option<&ExtendedDeviceInformation> {
+        self.dibs.iter().find_map(|d| if let Dib::ExtendedDeviceInfo(i) = d { Some(i) } else { None })
+    }
+
+    pub fn manufacturer_data(&self) -> impl Iterator<Item=&ManufacturerData> {
+        self.dibs.iter().filter_map(|d| if let Dib::ManufacturerData(m) = d { Some(m) } else { None })
+    }
+}
+
+#[derive(Debug)]
+pub struct Discovery {
+    search_request: Vec<u8>,
+    request_pending: bool,
+    timeout: Instant,
+    found: Vec<(HPAI, Option<[u8;6]>)>,
+    parameters: Vec<Srp>,
+}
+
+impl Discovery {
+    /// Create a discovery which expects responses on the given local address and port
+    pub fn new(ipv4: [u8;4], port: u16, config: DiscoveryConfig) -> Discovery {
+        let req: Service<()> = Service::SearchRequest(SearchRequest{
+            discovery: HPAI::new(Protocol::Udp4Protocol, ipv4, port),
+        });
+        Discovery{
+            search_request: req.encoded(),
+            request_pending: true,
+            timeout: Instant::now().add(config.search_timeout),
+            found: vec![],
+            parameters: vec![],
+        }
+    }
+
+    /// Create an extended discovery, only servers matching all search request parameters are returned.
+    /// Extended search requests are only answered by servers supporting core version 2.
+    pub fn new_extended(ipv4: [u8;4], port: u16, parameters: Vec<Srp>, config: DiscoveryConfig) -> Discovery {
+        let req: Service<()> = Service::SearchRequestExtended(SearchRequestExtended{
+            discovery: HPAI::new(Protocol::Udp4Protocol, ipv4, port),
+            parameters: parameters.clone(),
+        });
+        let mut discovery = Discovery::new(ipv4, port, config);
+        discovery.search_request = req.encoded();
+        discovery.parameters = parameters;
+        discovery
+    }
+
+    /// Search request which needs to be sent to `KNX_MULTICAST_ADDRESS`:`KNX_PORT`
+    pub fn get_outbound_data(&mut self) -> Option<&[u8]> {
+        if self.request_pending {
+            self.request_pending = false;
+            return Some(&self.search_request)
+        }
+        None
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        self.timeout
+    }
+
+    pub fn handle_time_events(&mut self) {
+        if self.timeout < Instant::now() {
+            self.request_pending = false;
+        }
+    }
+
+    /// Search is finished once the search timeout elapsed, later responses are ignored
+    pub fn finished(&self) -> bool {
+        !self.request_pending && self.timeout < Instant::now()
+    }
+
+    pub fn handle_inbound_message(&mut self, data: &[u8]) -> Option<Gateway> {
+        if self.finished() {
+            return None
+        }
+        match Service::<()>::decoded(data) {
+            Ok(Service::SearchResponse(resp)) | Ok(Service::SearchResponseExtended(resp)) => {
+                let description = Description{dibs: resp.dibs};
+                let device = description.device_information().cloned();
+                let services = description.supported_services().cloned();
+                // servers not supporting the parameters might answer anyway
+                if !self.parameters.iter().all(|p| Self::matches(p, device.as_ref(), services.as_ref())) {
+                    return None
+                }
+                // servers with multiple interfaces might answer more than once
+                let key = (resp.control, device.as_ref().map(|d| d.serial_number));
+                if self.found.contains(&key) {
+                    return None
+                }
+                self.found.push(key);
+                Some(Gateway{
+                    control: resp.control,
+                    device,
+                    services,
+                    description,
+                })
+            }
+            _ => None
+        }
+    }
+
+    fn matches(parameter: &Srp, device: Option<&DeviceInformation>, services: Option<&ServiceFamilies>) -> bool {
+        match parameter {
+            Srp::SelectByProgrammingMode => device.is_some_and(|d| d.programming_mode),
+            Srp::SelectByMacAddress(mac) => device.is_some_and(|d| d.mac_address == *mac),
+            Srp::SelectByService(service) => services.and_then(|s| s.version(service.family)).is_some_and(|v| v >= service.version),
+            Srp::RequestDibs(_) => true,
+        }
+    }
+}
+
+/// Requests the self description of a single KNXnet/IP server
+#[derive(Debug)]
+pub struct DescriptionQuery {
+    description_request: Vec<u8>,
+    request_pending: bool,
+    received: bool,
+    timeout: Instant,
+}
+
+impl DescriptionQuery {
+    /// Create a description query which expects the response on the given local address and port
+    pub fn new(ipv4: [u8;4], port: u16, config: DiscoveryConfig) -> DescriptionQuery {
+        let req: Service<()> = Service::DescriptionRequest(DescriptionRequest{
+            control: HPAI::new(Protocol::Udp4Protocol, ipv4, port),
+        });
+        DescriptionQuery{
+            description_request: req.encoded(),
+            request_pending: true,
+            received: false,
+            timeout: Instant::now().add(config.description_timeout),
+        }
+    }
+
+    /// Description request which needs to be sent to the control endpoint of the server
+    pub fn get_outbound_data(&mut self) -> Option<&[u8]> {
+        if self.request_pending {
+            self.request_pending = false;
+            return Some(&self.description_request)
+        }
+        None
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        self.timeout
+    }
+
+    pub fn handle_time_events(&mut self) {
+        if self.timeout < Instant::now() {
+            self.request_pending = false;
+        }
+    }
+
+    /// Query is finished once a description was received or the timeout elapsed
+    pub fn finished(&self) -> bool {
+        self.received || (!self.request_pending && self.timeout < Instant::now())
+    }
+
+    pub fn handle_inbound_message(&mut self, data: &[u8]) -> Option<Description> {
+        if self.finished() {
+            return None
+        }
+        match Service::<()>::decoded(data) {
+            Ok(Service::DescriptionResponse(resp)) => {
+                self.received = true;
+                Some(Description{dibs: resp.dibs})
+            }
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::address::IndividualAddress;
+    use crate::discovery::{DescriptionQuery, Discovery, DiscoveryConfig};
+    use crate::knxnet::dib::{DibType, ServiceFamily, ServiceFamilyType};
+    use crate::knxnet::hpai::{HPAI, Protocol};
+    use crate::knxnet::srp::Srp;
+
+    const SEARCH_RESPONSE: [u8; 78] = [0x06, 0x10, 0x02, 0x02, 0x00, 0x4E, 0x08, 0x01, 0xC0, 0xA8, 0x2A, 0x0A, 0x0E, 0x57,
+        0x36, 0x01, 0x20, 0x01, 0x11, 0x00, 0x00, 0x00, 0x00, 0x01, 0x31, 0x3B, 0x0F, 0x22,
+        0xE0, 0x00, 0x17, 0x0C, 0x00, 0x01, 0x31, 0x3B, 0x0F, 0x22, 0x47, 0x69, 0x72, 0x61,
+        0x20, 0x4B, 0x4E, 0x58, 0x2F, 0x49, 0x50, 0x2D, 0x52, 0x6F, 0x75, 0x74, 0x65, 0x72,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x0A, 0x02, 0x02, 0x02, 0x03, 0x02, 0x04, 0x02, 0x05, 0x02];
+
+    #[test]
+    fn t_discovery() {
+        let mut discovery = Discovery::new([192, 168, 200, 12], 50100, DiscoveryConfig::default());
+        assert_eq!(discovery.get_outbound_data(), Some(&[0x06, 0x10, 0x02, 0x01, 0x00, 0x0E, 0x08, 0x01, 192, 168, 200, 12, 0xC3, 0xB4][..]));
+        assert_eq!(discovery.get_outbound_data(), None);
+        assert!(!discovery.finished());
+
+        let gateway = discovery.handle_inbound_message(&SEARCH_RESPONSE).unwrap();
+        assert_eq!(gateway.control, HPAI::new(Protocol::Udp4Protocol, [192, 168, 42, 10], 3671));
+        let device = gateway.device.unwrap();
+        assert_eq!(device.friendly_name, "Gira KNX/IP-Router");
+        assert_eq!(device.individual_address, IndividualAddress::new(1, 1, 0));
+        assert_eq!(gateway.services.unwrap().version(ServiceFamilyType::Tunnelling), Some(2));
+        assert_eq!(gateway.description.dibs.len(), 2);
+        // the same server answering again is reported once
+        assert_eq!(discovery.handle_inbound_message(&SEARCH_RESPONSE), None);
+        // another server
+        let mut response = SEARCH_RESPONSE;
+        response[27] = 0x23;
+        assert_eq!(discovery.handle_inbound_message(&response).and_then(|g| g.device).map(|d| d.serial_number),
+                   Some([0x00, 0x01, 0x31, 0x3B, 0x0F, 0x23]));
+        // other services are ignored
+        assert_eq!(discovery.handle_inbound_message(&[0x06, 0x10, 0x02, 0x08, 0x00, 0x08, 0x01, 0x00]), None);
+    }
+
+    #[test]
+    fn t_discovery_timeout() {
+        let mut discovery = Discovery::new([192, 168, 200, 12], 50100, DiscoveryConfig::default().with_search_timeout(Duration::from_millis(10)));
+        assert!(discovery.get_outbound_data().is_some());
+        discovery.handle_time_events();
+        assert!(!discovery.finished());
+
+        std::thread::sleep(Duration::from_millis(15));
+        assert!(discovery.get_next_time_event() < std::time::Instant::now());
+        discovery.handle_time_events();
+        assert!(discovery.finished());
+        // late responses are ignored
+        assert_eq!(discovery.handle_inbound_message(&SEARCH_RESPONSE), None);
+    }
+
+    #[test]
+    fn t_discovery_extended() {
+        let mut discovery = Discovery::new_extended([192, 168, 200, 12], 50100, vec![
+            Srp::SelectByProgrammingMode,
+            Srp::SelectByService(ServiceFamily{family: ServiceFamilyType::Tunnelling, version: 2}),
+        ], DiscoveryConfig::default());
+        assert_eq!(discovery.get_outbound_data(), Some(&[0x06, 0x10, 0x02, 0x0B, 0x00, 0x14, 0x08, 0x01, 192, 168, 200, 12, 0xC3, 0xB4,
+            0x02, 0x81, 0x04, 0x83, 0x04, 0x02][..]));
+
+        // servers not matching the parameters are ignored
+        let mut response = SEARCH_RESPONSE;
+        response[17] = 0x00;
+        assert_eq!(discovery.handle_inbound_message(&response), None);
+        let mut response = SEARCH_RESPONSE;
+        response[75] = 0x01;
+        assert_eq!(discovery.handle_inbound_message(&response), None);
+        assert!(discovery.handle_inbound_message(&SEARCH_RESPONSE).is_some());
+    }
+
+    #[test]
+    fn t_discovery_missing_dibs() {
+        // a server answering an extended search only with the requested tunnelling info
+        let response = [0x06, 0x10, 0x02, 0x0C, 0x00, 0x16, 0x08, 0x01, 192, 168, 42, 10, 0x0E, 0x57,
+            0x08, 0x07, 0x00, 0xFE, 0x11, 0xF1, 0x00, 0x05];
+        let mut discovery = Discovery::new_extended([192, 168, 200, 12], 50100,
+            vec![Srp::RequestDibs(vec![DibType::TunnellingInfo])], DiscoveryConfig::default());
+        let gateway = discovery.handle_inbound_message(&response).unwrap();
+        assert_eq!(gateway.device, None);
+        assert_eq!(gateway.services, None);
+        assert_eq!(gateway.description.tunnelling_info().map(|t| t.max_apdu_length), Some(254));
+        assert_eq!(discovery.handle_inbound_message(&response), None);
+
+        // parameters depending on missing DIBs are not met
+        let mut discovery = Discovery::new_extended([192, 168, 200, 12], 50100,
+            vec![Srp::SelectByProgrammingMode], DiscoveryConfig::default());
+        assert_eq!(discovery.handle_inbound_message(&response), None);
+    }
+
+    #[test]
+    fn t_description_query() {
+        let mut query = DescriptionQuery::new([192, 168, 200, 12], 50100, DiscoveryConfig::default());
+        assert_eq!(query.get_outbound_data(), Some(&[0x06, 0x10, 0x02, 0x03, 0x00, 0x0E, 0x08, 0x01, 192, 168, 200, 12, 0xC3, 0xB4][..]));
+        assert_eq!(query.get_outbound_data(), None);
+        assert!(!query.finished());
+        // search responses are no description
+        assert_eq!(query.handle_inbound_message(&SEARCH_RESPONSE), None);
+
+        // a description without device information
+        let description = query.handle_inbound_message(&[0x06, 0x10, 0x02, 0x04, 0x00, 0x18,
+            0x06, 0x02, 0x02, 0x02, 0x04, 0x02,
+            0x0C, 0x07, 0x00, 0xFE, 0x11, 0xF1, 0x00, 0x05, 0x11, 0xF2, 0x00, 0x04]).unwrap();
+        assert_eq!(description.device_information(), None);
+        assert_eq!(description.supported_services().and_then(|s| s.version(ServiceFamilyType::Tunnelling)), Some(2));
+        assert_eq!(description.tunnelling_info().map(|t| t.slots.len()), Some(2));
+        assert!(query.finished());
+        assert_eq!(query.handle_inbound_message(&[0x06, 0x10, 0x02, 0x04, 0x00, 0x0C, 0x06, 0x02, 0x02, 0x02, 0x04, 0x02]), None);
+    }
+
+    #[test]
+    fn t_description_query_timeout() {
+        let mut query = DescriptionQuery::new([192, 168, 200, 12], 50100, DiscoveryConfig::default().with_description_timeout(Duration::from_millis(10)));
+        assert!(query.get_outbound_data().is_some());
+        query.handle_time_events();
+        assert!(!query.finished());
+
+        std::thread::sleep(Duration::from_millis(15));
+        query.handle_time_events();
+        assert!(query.finished());
+        assert_eq!(query.handle_inbound_message(&[0x06, 0x10, 0x02, 0x04, 0x00, 0x0C, 0x06, 0x02, 0x02, 0x02, 0x04, 0x02]), None);
+    }
+}
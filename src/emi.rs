@@ -0,0 +1,160 @@
+use strum_macros::FromRepr;
+use crate::cemi::information::InformationType;
+use crate::cemi::l_data::MAX_STANDARD_APDU_LENGTH;
+use crate::cemi::Message;
+use crate::dpt::DPT;
+use crate::group_event::GroupEvent;
+use crate::knxnet::KnxNetIpError;
+use crate::tp1;
+
+// External message interfaces of KNX interfaces (See 3/6/3). EMI1 and EMI2 carry the TP1 frame without checksum
+// and only support standard frames, their messages are converted from and to the cEMI ones.
+
+const L_DATA_REQ: u8 = 0x11;
+const EMI1_L_DATA_CON: u8 = 0x4E;
+const EMI1_L_DATA_IND: u8 = 0x49;
+const EMI2_L_DATA_CON: u8 = 0x2E;
+const EMI2_L_DATA_IND: u8 = 0x29;
+const EMI2_L_BUSMON_IND: u8 = 0x2B;
+
+/// Message format of an interface, the values are the EMI IDs used by USB interfaces
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EmiType {
+    Emi1 = 0x01,
+    Emi2 = 0x02,
+    Cemi = 0x03,
+}
+
+/// Converts a message from one format to another, e.g. to pass frames of a cEMI tunnel to an EMI2 interface.
+/// Additional information of cEMI messages is lost when converting them to EMI1 or EMI2.
+pub fn convert(msg: &[u8], from: EmiType, to: EmiType) -> Result<Vec<u8>, KnxNetIpError> {
+    let mut buf = vec![];
+    encode(&decode(msg, from)?, to, &mut buf)?;
+    Ok(buf)
+}
+
+/// Encodes a group event as L_Data request in the given format
+pub fn encode_group_event<T: DPT+Default>(ev: GroupEvent<T>, emi: EmiType) -> Result<Vec<u8>, KnxNetIpError> {
+    let mut buf = vec![];
+    encode(&Message::LDataReq(vec![], ev.into_l_data()), emi, &mut buf)?;
+    Ok(buf)
+}
+
+/// Decodes the group event of an L_Data indication in the given format, other valid messages return `None`
+pub fn decode_group_event(msg: &[u8], emi: EmiType) -> Result<Option<GroupEvent<Vec<u8>>>, KnxNetIpError> {
+    match decode(msg, emi)? {
+        Message::LDataInd(_, l_data) => Ok(GroupEvent::from_l_data(l_data)),
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn encode<D: DPT+Default>(msg: &Message<D>, emi: EmiType, buf: &mut Vec<u8>) -> Result<(), KnxNetIpError> {
+    let (con, ind) = match emi {
+        EmiType::Cemi => {
+            msg.encode(buf);
+            return Ok(())
+        }
+        EmiType::Emi1 => (EMI1_L_DATA_CON, EMI1_L_DATA_IND),
+        EmiType::Emi2 => (EMI2_L_DATA_CON, EMI2_L_DATA_IND),
+    };
+    let (code, l_data) = match msg {
+        Message::LDataReq(_, l_data) => (L_DATA_REQ, l_data),
+        Message::LDataCon(_, l_data) => (con, l_data),
+        Message::LDataInd(_, l_data) => (ind, l_data),
+        Message::LBusmonInd(info, frame) if emi == EmiType::Emi2 => {
+            buf.push(EMI2_L_BUSMON_IND);
+            buf.push(info.iter().find_map(|i| match i {
+                InformationType::BusmonitorStatusInfo(status) => Some(*status),
+                _ => None,
+            }).unwrap_or(0));
+            buf.extend(info.iter().find_map(|i| match i {
+                InformationType::TimestampRelative(timestamp) => Some(*timestamp),
+                _ => None,
+            }).unwrap_or(0).to_be_bytes());
+            buf.extend_from_slice(frame);
+            return Ok(())
+        }
+        _ => return Err(KnxNetIpError::NotImplemented),
+    };
+    if l_data.data.length() > MAX_STANDARD_APDU_LENGTH {
+        return Err(KnxNetIpError::InvalidSize)
+    }
+    buf.push(code);
+    let start = buf.len();
+    tp1::encode_frame(l_data, buf);
+    // the confirmation flag is only part of the control field of EMI messages
+    buf[start] |= l_data.confirmation as u8;
+    Ok(())
+}
+
+pub(crate) fn decode(buf: &[u8], emi: EmiType) -> Result<Message<Vec<u8>>, KnxNetIpError> {
+    if buf.is_empty() {
+        return Err(KnxNetIpError::MessageTooShort(buf.len()))
+    }
+    if emi == EmiType::Cemi {
+        return Message::decode(buf)
+    }
+    match (emi, buf[0]) {
+        (_, L_DATA_REQ) => Ok(Message::LDataReq(vec![], tp1::decode_frame(&buf[1..])?)),
+        (EmiType::Emi1, EMI1_L_DATA_CON) | (EmiType::Emi2, EMI2_L_DATA_CON) => Ok(Message::LDataCon(vec![], tp1::decode_frame(&buf[1..])?)),
+        (EmiType::Emi1, EMI1_L_DATA_IND) | (EmiType::Emi2, EMI2_L_DATA_IND) => Ok(Message::LDataInd(vec![], tp1::decode_frame(&buf[1..])?)),
+        (EmiType::Emi2, EMI2_L_BUSMON_IND) => {
+            if buf.len() < 4 {
+                return Err(KnxNetIpError::MessageTooShort(buf.len()))
+            }
+            let info = vec![
+                InformationType::BusmonitorStatusInfo(buf[1]),
+                InformationType::TimestampRelative(u16::from_be_bytes([buf[2], buf[3]])),
+            ];
+            Ok(Message::LBusmonInd(info, buf[4..].to_vec()))
+        }
+        _ => Err(KnxNetIpError::NotImplemented),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emi::{convert, decode_group_event, encode_group_event, EmiType};
+    use crate::group_event::{GroupEvent, GroupEventType};
+    use crate::knxnet::KnxNetIpError;
+
+    #[test]
+    fn t_emi_group_event() {
+        let ev = GroupEvent{address: 0x4808, event_type: GroupEventType::GroupValueWrite, data: true};
+        assert_eq!(encode_group_event(ev, EmiType::Emi2), Ok(vec![0x11, 0xBC, 0x00, 0x00, 0x48, 0x08, 0xE1, 0x00, 0x81]));
+        let ev = GroupEvent{address: 0x4808, event_type: GroupEventType::GroupValueWrite, data: true};
+        assert_eq!(encode_group_event(ev, EmiType::Cemi), Ok(vec![0x11, 0x00, 0xBE, 0xE0, 0x00, 0x00, 0x48, 0x08, 0x01, 0x00, 0x81]));
+
+        let ev = decode_group_event(&[0x49, 0xBC, 0x11, 0x0A, 0x48, 0x08, 0xE1, 0x00, 0x00], EmiType::Emi1).unwrap().unwrap();
+        assert_eq!((ev.address, ev.event_type), (0x4808, GroupEventType::GroupValueRead));
+        // confirmations carry no group events, and codes differ between EMI1 and EMI2
+        assert!(decode_group_event(&[0x2E, 0xBC, 0x11, 0x0A, 0x48, 0x08, 0xE1, 0x00, 0x80], EmiType::Emi2).unwrap().is_none());
+        assert_eq!(decode_group_event(&[0x4E, 0xBC], EmiType::Emi2).err(), Some(KnxNetIpError::NotImplemented));
+    }
+
+    #[test]
+    fn t_emi_convert() {
+        let cemi = [0x29, 0x00, 0xBE, 0xE0, 0x11, 0x0A, 0x48, 0x08, 0x02, 0x00, 0x80, 0x01];
+        let emi2 = [0x29, 0xBC, 0x11, 0x0A, 0x48, 0x08, 0xE2, 0x00, 0x80, 0x01];
+        let emi1 = [0x49, 0xBC, 0x11, 0x0A, 0x48, 0x08, 0xE2, 0x00, 0x80, 0x01];
+        assert_eq!(convert(&cemi, EmiType::Cemi, EmiType::Emi2), Ok(emi2.to_vec()));
+        assert_eq!(convert(&emi2, EmiType::Emi2, EmiType::Emi1), Ok(emi1.to_vec()));
+        assert_eq!(convert(&emi1, EmiType::Emi1, EmiType::Cemi), Ok(cemi.to_vec()));
+
+        // negative confirmation
+        let con = convert(&[0x2E, 0x00, 0xBF, 0xE0, 0x11, 0x0A, 0x48, 0x08, 0x02, 0x00, 0x80, 0x01], EmiType::Cemi, EmiType::Emi2).unwrap();
+        assert_eq!(con[..2], [0x2E, 0xBD]);
+        assert_eq!(convert(&con, EmiType::Emi2, EmiType::Cemi).map(|c| c[2]), Ok(0xBF));
+
+        // status and timestamp of busmonitor frames become additional information
+        let busmon = [0x2B, 0x03, 0x12, 0x34, 0xCC];
+        let cemi = convert(&busmon, EmiType::Emi2, EmiType::Cemi).unwrap();
+        assert_eq!(cemi, vec![0x2B, 0x07, 0x03, 0x01, 0x03, 0x04, 0x02, 0x12, 0x34, 0xCC]);
+        assert_eq!(convert(&cemi, EmiType::Cemi, EmiType::Emi2), Ok(busmon.to_vec()));
+
+        // extended frames are not supported by EMI1 and EMI2
+        let ev = GroupEvent{address: 0x4808, event_type: GroupEventType::GroupValueWrite, data: vec![0; 20]};
+        assert_eq!(encode_group_event(ev, EmiType::Emi2), Err(KnxNetIpError::InvalidSize));
+    }
+}
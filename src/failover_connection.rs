@@ -0,0 +1,266 @@
+//runtime facing functions:
+
+// get data to be transmitted next together with the gateway and its endpoint
+// get next time event
+// handle next time event of all gateways
+// handle received data of a gateway -> returns a group event of the active gateway or none
+// send data through the active gateway
+
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::net::SocketAddrV4;
+use std::time::{Duration, Instant};
+use crate::cemi::l_data::LData;
+use crate::dpt::DPT;
+use crate::group_event::GroupEvent;
+use crate::routing_connection::RoutingConnection;
+use crate::tunnel_connection::{Endpoint, TunnelConnection};
+
+// upper bound of the time events, in case there are no gateways at all
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Connection to one of the redundant gateways of an installation
+#[derive(Debug)]
+pub enum Gateway {
+    Tunnel(Box<TunnelConnection>),
+    /// Routing has no connection which could fail, so it is always considered available
+    Routing(RoutingConnection),
+}
+
+impl Gateway {
+    fn available(&self) -> bool {
+        match self {
+            Gateway::Tunnel(tunnel) => tunnel.connected(),
+            Gateway::Routing(_) => true,
+        }
+    }
+
+    fn send_l_data(&mut self, l_data: LData<Vec<u8>>) {
+        match self {
+            Gateway::Tunnel(tunnel) => tunnel.send_l_data(l_data, None),
+            // frames are checked by `to_raw` before they are queued, so they always fit
+            Gateway::Routing(routing) => {
+                let _ = routing.send_l_data(l_data);
+            }
+        }
+    }
+
+    fn take_pending_l_data(&mut self) -> Vec<LData<Vec<u8>>> {
+        match self {
+            Gateway::Tunnel(tunnel) => tunnel.take_pending_l_data(),
+            Gateway::Routing(routing) => routing.take_pending_l_data(),
+        }
+    }
+}
+
+/// Destination of outbound data of a gateway
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Destination {
+    /// Control endpoint of the tunnelling server
+    Control,
+    /// Data endpoint assigned by the tunnelling server
+    Data(SocketAddrV4),
+    /// KNX multicast address `224.0.23.12:3671`
+    Multicast,
+}
+
+/// Sends group events through the first available of several gateways of the same installation.
+/// If the active gateway fails, e.g. it stops answering heartbeats, its unacknowledged frames are moved to the next
+/// available one. Once a gateway of higher priority is available again it takes over new frames, so frames might be
+/// sent twice if a failure is detected after the gateway already forwarded them.
+#[derive(Debug)]
+pub struct FailoverConnection {
+    gateways: Vec<Gateway>,
+    active: Option<usize>,
+    pending: VecDeque<LData<Vec<u8>>>,
+    current: Vec<u8>,
+}
+
+impl FailoverConnection {
+    /// Create a connection using the gateways in the given order of priority, the first one being the primary
+    pub fn new(gateways: Vec<Gateway>) -> FailoverConnection {
+        let mut con = FailoverConnection{
+            gateways,
+            active: None,
+            pending: VecDeque::new(),
+            current: vec![],
+        };
+        con.update_active();
+        con
+    }
+
+    /// Send a group event through the active gateway. It is queued while no gateway is available.
+    pub fn send<T: DPT+Default>(&mut self, ev: GroupEvent<T>) {
+        if let Ok(l_data) = ev.into_l_data().to_raw() {
+            self.pending.push_back(l_data);
+        }
+        self.update_active();
+    }
+
+    /// Index of the gateway frames are currently sent through
+    pub fn active(&self) -> Option<usize> {
+        self.active
+    }
+
+    pub fn connected(&self) -> bool {
+        self.active.is_some()
+    }
+
+    pub fn gateway(&self, index: usize) -> Option<&Gateway> {
+        self.gateways.get(index)
+    }
+
+    /// Data which needs to be sent to the returned destination of the gateway with the returned index
+    pub fn get_outbound_data(&mut self) -> Option<(usize, &[u8], Destination)> {
+        for (i, gateway) in self.gateways.iter_mut().enumerate() {
+            let outbound = match gateway {
+                Gateway::Tunnel(tunnel) => tunnel.get_outbound_data().map(|(data, endpoint)| (data.to_vec(), match endpoint {
+                    Endpoint::Control => Destination::Control,
+                    Endpoint::Data(address) => Destination::Data(address),
+                })),
+                Gateway::Routing(routing) => routing.get_outbound_data().map(|data| (data.to_vec(), Destination::Multicast)),
+            };
+            if let Some((data, destination)) = outbound {
+                self.current = data;
+                return Some((i, &self.current, destination))
+            }
+        }
+        None
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        self.gateways.iter().map(|gateway| match gateway {
+            Gateway::Tunnel(tunnel) => tunnel.get_next_time_event(),
+            Gateway::Routing(routing) => routing.get_next_time_event(),
+        }).fold(Instant::now() + IDLE_INTERVAL, min)
+    }
+
+    pub fn handle_time_events(&mut self) {
+        for gateway in self.gateways.iter_mut() {
+            match gateway {
+                Gateway::Tunnel(tunnel) => tunnel.handle_time_events(),
+                Gateway::Routing(routing) => routing.handle_time_events(),
+            }
+        }
+        self.update_active();
+    }
+
+    /// Handles data received from the gateway with the given index.
+    /// All gateways receive the frames of the bus, so only the ones of the active gateway are returned.
+    pub fn handle_inbound_message(&mut self, gateway: usize, data: &[u8]) -> Option<GroupEvent<Vec<u8>>> {
+        let ev = match self.gateways.get_mut(gateway)? {
+            Gateway::Tunnel(tunnel) => tunnel.handle_inbound_message(data),
+            Gateway::Routing(routing) => routing.handle_inbound_message(data),
+        };
+        self.update_active();
+        ev.filter(|_| self.active == Some(gateway))
+    }
+
+    fn update_active(&mut self) {
+        let available = self.gateways.iter().position(|g| g.available());
+        if available != self.active {
+            if let Some(previous) = self.active {
+                // a gateway which is still available completes its queue, a failed one drops it on reconnect
+                if !self.gateways[previous].available() {
+                    let mut moved = self.gateways[previous].take_pending_l_data();
+                    moved.extend(self.pending.drain(..));
+                    self.pending = moved.into();
+                }
+            }
+            self.active = available;
+        }
+        if let Some(active) = self.active {
+            while let Some(l_data) = self.pending.pop_front() {
+                self.gateways[active].send_l_data(l_data);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::address::IndividualAddress;
+    use crate::failover_connection::{Destination, FailoverConnection, Gateway};
+    use crate::group_event::{GroupEvent, GroupEventType};
+    use crate::routing_connection::{RoutingConnection, RoutingConnectionConfig};
+    use crate::tunnel_connection::{TunnelConnection, TunnelConnectionConfig};
+
+    const CONNECT_RESPONSE: [u8; 20] = [0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+        0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A];
+
+    fn write(address: u16) -> GroupEvent<Vec<u8>> {
+        GroupEvent{address, event_type: GroupEventType::GroupValueWrite, data: vec![0x01, 0x02]}
+    }
+
+    fn tunnel() -> Gateway {
+        Gateway::Tunnel(Box::new(TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default())))
+    }
+
+    #[test]
+    fn t_failover_tunnels() {
+        let mut con = FailoverConnection::new(vec![tunnel(), tunnel()]);
+        assert_eq!(con.get_outbound_data().map(|(i, d, dest)| (i, d[3], dest)), Some((0, 0x05, Destination::Control)));
+        assert_eq!(con.get_outbound_data().map(|(i, d, _)| (i, d[3])), Some((1, 0x05)));
+        assert!(!con.connected());
+        // queued until a gateway is available
+        con.send(write(10));
+        con.handle_inbound_message(1, &CONNECT_RESPONSE);
+        assert_eq!(con.active(), Some(1));
+        assert_eq!(con.get_outbound_data().map(|(i, d, dest)| (i, d[17], dest)), Some((1, 0x0A, Destination::Data("192.168.1.10:3671".parse().unwrap()))));
+        con.handle_inbound_message(1, &[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+
+        // the primary takes over once it is connected
+        con.handle_inbound_message(0, &CONNECT_RESPONSE);
+        assert_eq!(con.active(), Some(0));
+        con.send(write(11));
+        assert_eq!(con.get_outbound_data().map(|(i, d, _)| (i, d[17])), Some((0, 0x0B)));
+        let ind = [0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x15, 0x00, 0x00,
+            0x29, 0x00, 0xBC, 0xE0, 0x11, 0x01, 0x00, 0x0C, 0x01, 0x00, 0x81];
+        // frames of the bus are received through both gateways, only the active one delivers them
+        assert!(con.handle_inbound_message(1, &ind).is_none());
+        assert_eq!(con.handle_inbound_message(0, &ind).map(|ev| ev.address), Some(12));
+
+        // the server of the primary closes the tunnel before the frame was acknowledged, so it is sent by the backup
+        con.handle_inbound_message(0, &[0x06, 0x10, 0x02, 0x09, 0x00, 0x10, 0x15, 0x00, 0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57]);
+        assert_eq!(con.active(), Some(1));
+        let mut outbound = vec![];
+        while let Some((i, d, _)) = con.get_outbound_data() {
+            outbound.push((i, d[3], d.get(17).copied()));
+        }
+        // the primary reconnects, the backup acknowledges the indication and sends the moved frame
+        assert_eq!(outbound.iter().map(|(i, service, _)| (*i, *service)).collect::<Vec<_>>(), vec![(0, 0x05), (1, 0x21), (1, 0x20)]);
+        assert_eq!(outbound[2].2, Some(0x0B));
+    }
+
+    #[test]
+    fn t_failover_unreachable_gateway() {
+        let config = TunnelConnectionConfig::default().with_response_timeout(Duration::from_millis(10));
+        let backup = Gateway::Tunnel(Box::new(TunnelConnection::new([192, 168, 1, 100], 3671, config)));
+        let mut con = FailoverConnection::new(vec![tunnel(), backup]);
+        assert_eq!(con.get_outbound_data().map(|(i, d, _)| (i, d[3])), Some((0, 0x05)));
+        assert_eq!(con.get_outbound_data().map(|(i, d, _)| (i, d[3])), Some((1, 0x05)));
+        con.handle_inbound_message(0, &CONNECT_RESPONSE);
+        assert_eq!(con.active(), Some(0));
+
+        // the backup never answers, so its connect request is repeated while the primary stays in use
+        std::thread::sleep(Duration::from_millis(15));
+        con.handle_time_events();
+        assert_eq!(con.active(), Some(0));
+        assert_eq!(con.get_outbound_data().map(|(i, d, _)| (i, d[3])), Some((1, 0x05)));
+        con.send(write(10));
+        assert_eq!(con.get_outbound_data().map(|(i, d, _)| (i, d[17])), Some((0, 0x0A)));
+    }
+
+    #[test]
+    fn t_failover_routing() {
+        let routing = Gateway::Routing(RoutingConnection::new(IndividualAddress::new(1, 1, 250), RoutingConnectionConfig::default()));
+        let mut con = FailoverConnection::new(vec![tunnel(), routing]);
+        // routing is available right away
+        assert_eq!(con.active(), Some(1));
+        con.send(write(10));
+        assert_eq!(con.get_outbound_data().map(|(i, d, _)| (i, d[3])), Some((0, 0x05)));
+        assert_eq!(con.get_outbound_data().map(|(i, d, dest)| (i, d[3], dest)), Some((1, 0x30, Destination::Multicast)));
+        assert_eq!(con.get_outbound_data(), None);
+    }
+}
@@ -0,0 +1,99 @@
+use strum_macros::FromRepr;
+use crate::knxnet::tunnel::TunnellingFeature;
+
+/// Interface features of a tunnelling server (See 3/8/4 and 3/6/3)
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InterfaceFeature {
+    /// Bitset of the supported EMI types (2 bytes)
+    SupportedEmiType = 0x01,
+    /// Device descriptor type 0 / mask version (2 bytes)
+    DeviceDescriptorType0 = 0x02,
+    /// Whether the interface is connected to the KNX bus (1 byte)
+    BusConnectionStatus = 0x03,
+    /// KNX manufacturer code (2 bytes)
+    ManufacturerCode = 0x04,
+    /// Active EMI type (1 byte)
+    ActiveEmiType = 0x05,
+    /// Individual address of the tunnel (2 bytes)
+    IndividualAddress = 0x06,
+    /// Maximum APDU length (2 bytes)
+    MaxApduLength = 0x07,
+    /// Whether the server reports feature changes with info services (1 byte)
+    InterfaceFeatureInfoServiceEnable = 0x08,
+}
+
+/// cEMI return codes of feature responses
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum ReturnCode {
+    Success = 0x00,
+    SuccessWithCrc = 0x01,
+    MemoryError = 0xF1,
+    InvalidCommand = 0xF2,
+    ImpossibleCommand = 0xF3,
+    ExceedsMaxApduLength = 0xF4,
+    DataOverflow = 0xF5,
+    OutOfMinRange = 0xF6,
+    OutOfMaxRange = 0xF7,
+    DataVoid = 0xF8,
+    TemporarilyNotAvailable = 0xF9,
+    AccessWriteOnly = 0xFA,
+    AccessReadOnly = 0xFB,
+    AccessDenied = 0xFC,
+    AddressVoid = 0xFD,
+    DataTypeConflict = 0xFE,
+    #[default]
+    GenericError = 0xFF,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FeatureEventType {
+    /// Answer to a get or set request
+    Response,
+    /// Feature value changed on the server
+    Info,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureEvent {
+    pub event_type: FeatureEventType,
+    pub feature: InterfaceFeature,
+    /// Always `Success` for info services
+    pub return_code: ReturnCode,
+    pub data: Vec<u8>,
+}
+
+impl FeatureEvent {
+    pub(crate) fn from_tunnelling_feature(event_type: FeatureEventType, f: TunnellingFeature) -> Option<FeatureEvent> {
+        Some(FeatureEvent{
+            event_type,
+            feature: InterfaceFeature::from_repr(f.feature)?,
+            return_code: match event_type {
+                FeatureEventType::Response => ReturnCode::from_repr(f.return_code).unwrap_or_default(),
+                FeatureEventType::Info => ReturnCode::Success,
+            },
+            data: f.data,
+        })
+    }
+
+    pub fn success(&self) -> bool {
+        matches!(self.return_code, ReturnCode::Success | ReturnCode::SuccessWithCrc)
+    }
+
+    /// Bus connection status carried by the event, if it is about it
+    pub fn bus_connected(&self) -> Option<bool> {
+        if self.feature != InterfaceFeature::BusConnectionStatus || !self.success() {
+            return None
+        }
+        self.data.first().map(|status| *status != 0)
+    }
+
+    /// Max APDU length of the server carried by the event, if it is about it
+    pub fn max_apdu_length(&self) -> Option<u16> {
+        if self.feature != InterfaceFeature::MaxApduLength || !self.success() || self.data.len() < 2 {
+            return None
+        }
+        Some(u16::from_be_bytes([self.data[0], self.data[1]]))
+    }
+}
@@ -0,0 +1,426 @@
+//runtime facing functions:
+
+// get data to be written to the serial port next
+// get next time event
+// handle next time event
+// handle data read from the serial port -> returns the received payloads
+// send payload (EMI2 or cEMI message)
+
+use std::collections::VecDeque;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+use crate::knxnet::KnxNetIpError;
+
+// FT1.2 frame format of IEC 60870-5-2 as used by serial KNX interfaces
+
+const ACK: u8 = 0xE5;
+const FIXED_START: u8 = 0x10;
+const VARIABLE_START: u8 = 0x68;
+const END: u8 = 0x16;
+
+// control field
+const DIRECTION: u8 = 0x80;
+const PRIMARY: u8 = 0x40;
+const FRAME_COUNT_BIT: u8 = 0x20;
+const FRAME_COUNT_VALID: u8 = 0x10;
+const FUNCTION: u8 = 0x0F;
+const RESET_REMOTE_LINK: u8 = 0x00;
+const SEND_USER_DATA: u8 = 0x03;
+
+// the length field covers the control field as well
+const MAX_PAYLOAD_LENGTH: usize = 254;
+
+// upper bound of the time events while there is nothing to repeat
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ft12Frame {
+    /// Single character acknowledgement
+    Ack,
+    /// Frame of fixed length carrying only a control field, e.g. a link reset
+    Fixed(u8),
+    /// Frame carrying a control field and up to 254 bytes of data
+    Variable(u8, Vec<u8>),
+}
+
+impl Ft12Frame {
+    /// Encodes the frame, returns `InvalidSize` if the data of a variable frame exceeds 254 bytes
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<(), KnxNetIpError> {
+        match self {
+            Ft12Frame::Ack => buf.push(ACK),
+            Ft12Frame::Fixed(control) => buf.extend([FIXED_START, *control, *control, END]),
+            Ft12Frame::Variable(control, data) => {
+                if data.len() > MAX_PAYLOAD_LENGTH {
+                    return Err(KnxNetIpError::InvalidSize)
+                }
+                let length = 1 + data.len() as u8;
+                buf.extend([VARIABLE_START, length, length, VARIABLE_START, *control]);
+                buf.extend_from_slice(data);
+                buf.push(checksum(*control, data));
+                buf.push(END);
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the frame at the start of `buf` together with its length, `None` means the frame is not complete yet
+    pub fn decode(buf: &[u8]) -> Result<Option<(Ft12Frame, usize)>, KnxNetIpError> {
+        match buf.first() {
+            None => Ok(None),
+            Some(&ACK) => Ok(Some((Ft12Frame::Ack, 1))),
+            Some(&FIXED_START) => {
+                if buf.len() < 4 {
+                    return Ok(None)
+                }
+                if buf[3] != END {
+                    return Err(KnxNetIpError::InvalidSize)
+                }
+                if buf[2] != buf[1] {
+                    return Err(KnxNetIpError::InvalidChecksum(buf[2]))
+                }
+                Ok(Some((Ft12Frame::Fixed(buf[1]), 4)))
+            }
+            Some(&VARIABLE_START) => {
+                if buf.len() < 4 {
+                    return Ok(None)
+                }
+                if buf[1] != buf[2] || buf[1] == 0 || buf[3] != VARIABLE_START {
+                    return Err(KnxNetIpError::InvalidSize)
+                }
+                let total = 6 + buf[1] as usize;
+                if buf.len() < total {
+                    return Ok(None)
+                }
+                if buf[total - 1] != END {
+                    return Err(KnxNetIpError::InvalidSize)
+                }
+                let data = &buf[5..total - 2];
+                if checksum(buf[4], data) != buf[total - 2] {
+                    return Err(KnxNetIpError::InvalidChecksum(buf[total - 2]))
+                }
+                Ok(Some((Ft12Frame::Variable(buf[4], data.to_vec()), total)))
+            }
+            Some(_) => Err(KnxNetIpError::Unknown),
+        }
+    }
+}
+
+// arithmetic sum of the control field and the data
+fn checksum(control: u8, data: &[u8]) -> u8 {
+    data.iter().fold(control, |cs, b| cs.wrapping_add(*b))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ft12ConnectionConfig {
+    ack_timeout: Duration,
+    repetitions: u8,
+}
+
+impl Default for Ft12ConnectionConfig {
+    fn default() -> Ft12ConnectionConfig {
+        Ft12ConnectionConfig{
+            ack_timeout: Duration::from_millis(500),
+            repetitions: 3,
+        }
+    }
+}
+
+impl Ft12ConnectionConfig {
+    /// Time the interface has to acknowledge a frame before it is repeated
+    pub fn with_ack_timeout(mut self, timeout: Duration) -> Ft12ConnectionConfig {
+        self.ack_timeout = timeout;
+        self
+    }
+
+    /// Number of times an unacknowledged frame is repeated before the link is reset
+    pub fn with_repetitions(mut self, repetitions: u8) -> Ft12ConnectionConfig {
+        self.repetitions = repetitions;
+        self
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+enum Ft12ConnectionState {
+    #[default]
+    Resetting,
+    Ready,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct PendingFrame {
+    data: Vec<u8>,
+    due: bool,
+    attempts_left: u8,
+}
+
+/// Link to a serial KNX interface (e.g. a BCU or an FT1.2 USB stick) using FT1.2 frames.
+/// The payloads are EMI2 or cEMI messages, depending on the interface, which are passed through unchanged.
+#[derive(Debug)]
+pub struct Ft12Connection {
+    state: Ft12ConnectionState,
+    out_queue: VecDeque<Vec<u8>>,
+    pending: Option<PendingFrame>,
+    acks: usize,
+    current: Vec<u8>,
+    // frame count bit of the next frame sent, and of the last one received
+    outbound_fcb: bool,
+    inbound_fcb: Option<bool>,
+    buf: Vec<u8>,
+    next_timeout: Instant,
+    config: Ft12ConnectionConfig,
+}
+
+impl Ft12Connection {
+    /// Create a connection, which resets the link of the interface first
+    pub fn new(config: Ft12ConnectionConfig) -> Ft12Connection {
+        let mut con = Ft12Connection{
+            state: Ft12ConnectionState::Resetting,
+            out_queue: VecDeque::new(),
+            pending: None,
+            acks: 0,
+            current: vec![],
+            outbound_fcb: true,
+            inbound_fcb: None,
+            buf: vec![],
+            next_timeout: Instant::now().add(config.ack_timeout),
+            config,
+        };
+        con.reset();
+        con
+    }
+
+    /// Send a payload to the interface, frames are sent one by one once the previous one was acknowledged.
+    /// Payloads longer than 254 bytes do not fit into a frame and are rejected with `InvalidSize`.
+    pub fn send(&mut self, payload: Vec<u8>) -> Result<(), KnxNetIpError> {
+        if payload.len() > MAX_PAYLOAD_LENGTH {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.out_queue.push_back(payload);
+        Ok(())
+    }
+
+    /// The link was reset successfully and payloads are sent
+    pub fn connected(&self) -> bool {
+        self.state == Ft12ConnectionState::Ready
+    }
+
+    /// Data which needs to be written to the serial port
+    pub fn get_outbound_data(&mut self) -> Option<&[u8]> {
+        if self.acks > 0 {
+            self.acks -= 1;
+            self.current.clear();
+            Ft12Frame::Ack.encode(&mut self.current).unwrap();
+            return Some(&self.current)
+        }
+        if self.pending.is_none() && self.state == Ft12ConnectionState::Ready {
+            if let Some(payload) = self.out_queue.pop_front() {
+                let fcb = if self.outbound_fcb {FRAME_COUNT_BIT} else {0};
+                let mut data = vec![];
+                // payloads are checked by `send`, so they always fit
+                Ft12Frame::Variable(PRIMARY | fcb | FRAME_COUNT_VALID | SEND_USER_DATA, payload).encode(&mut data).unwrap();
+                self.set_pending(data);
+            }
+        }
+        let pending = self.pending.as_mut()?;
+        if !pending.due {
+            return None
+        }
+        pending.due = false;
+        pending.attempts_left -= 1;
+        self.next_timeout = Instant::now().add(self.config.ack_timeout);
+        Some(&pending.data)
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        match self.pending {
+            Some(ref pending) if !pending.due => self.next_timeout,
+            _ => Instant::now().add(IDLE_INTERVAL),
+        }
+    }
+
+    pub fn handle_time_events(&mut self) {
+        if let Some(pending) = self.pending.as_mut() {
+            if !pending.due && self.next_timeout <= Instant::now() {
+                if pending.attempts_left > 0 {
+                    pending.due = true;
+                } else {
+                    // the interface does not respond, an unacknowledged payload is dropped
+                    self.reset();
+                }
+            }
+        }
+    }
+
+    /// Handles data read from the serial port, which might contain partial or several frames.
+    /// Returns the payloads of the complete frames received from the interface.
+    pub fn handle_inbound_message(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buf.extend_from_slice(data);
+        let mut payloads = vec![];
+        loop {
+            match Ft12Frame::decode(&self.buf) {
+                Ok(Some((frame, length))) => {
+                    self.buf.drain(..length);
+                    if let Some(payload) = self.handle_frame(frame) {
+                        payloads.push(payload);
+                    }
+                }
+                Ok(None) => break,
+                // skip to the next possible start of a frame
+                Err(_) => {
+                    self.buf.remove(0);
+                }
+            }
+        }
+        payloads
+    }
+
+    fn handle_frame(&mut self, frame: Ft12Frame) -> Option<Vec<u8>> {
+        match frame {
+            Ft12Frame::Ack => {
+                if self.pending.as_ref().is_some_and(|pending| !pending.due) {
+                    self.pending = None;
+                    match self.state {
+                        Ft12ConnectionState::Resetting => {
+                            self.state = Ft12ConnectionState::Ready;
+                            self.outbound_fcb = true;
+                        }
+                        Ft12ConnectionState::Ready => self.outbound_fcb = !self.outbound_fcb,
+                    }
+                }
+                None
+            }
+            Ft12Frame::Fixed(control) => {
+                self.acks += 1;
+                if control & (DIRECTION | FUNCTION) == DIRECTION | RESET_REMOTE_LINK {
+                    self.inbound_fcb = None;
+                }
+                None
+            }
+            Ft12Frame::Variable(control, data) => {
+                self.acks += 1;
+                if control & FUNCTION != SEND_USER_DATA {
+                    return None
+                }
+                // a repeated frame was already received, only its acknowledgement got lost
+                if control & FRAME_COUNT_VALID != 0 {
+                    let fcb = control & FRAME_COUNT_BIT != 0;
+                    if self.inbound_fcb == Some(fcb) {
+                        return None
+                    }
+                    self.inbound_fcb = Some(fcb);
+                }
+                Some(data)
+            }
+        }
+    }
+
+    fn set_pending(&mut self, data: Vec<u8>) {
+        self.pending = Some(PendingFrame{
+            data,
+            due: true,
+            attempts_left: 1 + self.config.repetitions,
+        });
+    }
+
+    fn reset(&mut self) {
+        self.state = Ft12ConnectionState::Resetting;
+        self.inbound_fcb = None;
+        let mut data = vec![];
+        Ft12Frame::Fixed(PRIMARY | RESET_REMOTE_LINK).encode(&mut data).unwrap();
+        self.set_pending(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::ft12::{Ft12Connection, Ft12ConnectionConfig, Ft12Frame};
+    use crate::knxnet::KnxNetIpError;
+
+    // cEMI L_Data.req of a group value write
+    const PAYLOAD: [u8; 11] = [0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x0A, 0x01, 0x01, 0x00, 0x81];
+
+    fn connected() -> Ft12Connection {
+        let mut con = Ft12Connection::new(Ft12ConnectionConfig::default().with_ack_timeout(Duration::ZERO).with_repetitions(1));
+        assert_eq!(con.get_outbound_data(), Some(&[0x10, 0x40, 0x40, 0x16][..]));
+        assert!(!con.connected());
+        assert!(con.handle_inbound_message(&[0xE5]).is_empty());
+        assert!(con.connected());
+        con
+    }
+
+    #[test]
+    fn t_ft12_frame() {
+        let mut buf = vec![];
+        Ft12Frame::Variable(0x73, PAYLOAD.to_vec()).encode(&mut buf).unwrap();
+        assert_eq!(buf, vec![0x68, 0x0C, 0x0C, 0x68, 0x73, 0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x0A, 0x01, 0x01, 0x00, 0x81, 0xAD, 0x16]);
+        assert_eq!(Ft12Frame::decode(&buf), Ok(Some((Ft12Frame::Variable(0x73, PAYLOAD.to_vec()), 18))));
+        assert_eq!(Ft12Frame::decode(&buf[..10]), Ok(None));
+        buf[16] = 0xAE;
+        assert_eq!(Ft12Frame::decode(&buf), Err(KnxNetIpError::InvalidChecksum(0xAE)));
+
+        assert_eq!(Ft12Frame::decode(&[0x10, 0x40, 0x40, 0x16]), Ok(Some((Ft12Frame::Fixed(0x40), 4))));
+        assert_eq!(Ft12Frame::decode(&[0xE5, 0x10]), Ok(Some((Ft12Frame::Ack, 1))));
+        assert!(Ft12Frame::decode(&[0x68, 0x03, 0x04, 0x68]).is_err());
+
+        // the length field includes the control field
+        let mut buf = vec![];
+        Ft12Frame::Variable(0x73, vec![0; 254]).encode(&mut buf).unwrap();
+        assert_eq!(buf[1..3], [0xFF, 0xFF]);
+        assert_eq!(Ft12Frame::decode(&buf), Ok(Some((Ft12Frame::Variable(0x73, vec![0; 254]), 261))));
+        buf.clear();
+        assert_eq!(Ft12Frame::Variable(0x73, vec![0; 255]).encode(&mut buf), Err(KnxNetIpError::InvalidSize));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn t_ft12_send() {
+        let mut con = connected();
+        con.send(PAYLOAD.to_vec()).unwrap();
+        con.send(PAYLOAD.to_vec()).unwrap();
+        assert_eq!(con.get_outbound_data().map(|d| d[4]), Some(0x73));
+        // waiting for the ack of the first frame
+        assert_eq!(con.get_outbound_data(), None);
+        std::thread::sleep(Duration::from_millis(1));
+        con.handle_time_events();
+        // repeated with the same frame count bit
+        assert_eq!(con.get_outbound_data().map(|d| d[4]), Some(0x73));
+        con.handle_inbound_message(&[0xE5]);
+        assert_eq!(con.get_outbound_data().map(|d| d[4]), Some(0x53));
+
+        // no ack at all, so the frame is dropped and the link is reset
+        std::thread::sleep(Duration::from_millis(1));
+        con.handle_time_events();
+        assert_eq!(con.get_outbound_data().map(|d| d[4]), Some(0x53));
+        std::thread::sleep(Duration::from_millis(1));
+        con.handle_time_events();
+        assert!(!con.connected());
+        assert_eq!(con.get_outbound_data(), Some(&[0x10, 0x40, 0x40, 0x16][..]));
+
+        con.handle_inbound_message(&[0xE5]);
+        assert_eq!(con.send(vec![0; 255]), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(con.get_outbound_data(), None);
+    }
+
+    #[test]
+    fn t_ft12_receive() {
+        let mut con = connected();
+        let mut frame = vec![];
+        Ft12Frame::Variable(0xF3, PAYLOAD.to_vec()).encode(&mut frame).unwrap();
+        // split over several reads and preceded by garbage
+        assert!(con.handle_inbound_message(&[0x00, 0x68]).is_empty());
+        assert!(con.handle_inbound_message(&frame[1..7]).is_empty());
+        assert_eq!(con.handle_inbound_message(&frame[7..]), vec![PAYLOAD.to_vec()]);
+        assert_eq!(con.get_outbound_data(), Some(&[0xE5][..]));
+
+        // the repetition of a frame is acknowledged but not returned again
+        assert!(con.handle_inbound_message(&frame).is_empty());
+        assert_eq!(con.get_outbound_data(), Some(&[0xE5][..]));
+        let mut next = vec![];
+        Ft12Frame::Variable(0xD3, PAYLOAD.to_vec()).encode(&mut next).unwrap();
+        frame.extend(next);
+        assert_eq!(con.handle_inbound_message(&frame[18..]), vec![PAYLOAD.to_vec()]);
+        assert_eq!(con.get_outbound_data(), Some(&[0xE5][..]));
+        assert_eq!(con.get_outbound_data(), None);
+    }
+}
@@ -1,4 +1,6 @@
 use strum_macros::FromRepr;
+use crate::cemi::apdu::Apdu;
+use crate::cemi::l_data::LData;
 use crate::dpt::DPT;
 
 #[derive(FromRepr, Debug, Copy, Clone, PartialEq)]
@@ -15,3 +17,42 @@ pub struct GroupEvent<D: DPT> {
     pub data: D,
 }
 
+impl<D: DPT+Default> GroupEvent<D> {
+    pub(crate) fn into_l_data(self) -> LData<D> {
+        let data = match self.event_type {
+            GroupEventType::GroupValueRead => Apdu::GroupValueRead,
+            GroupEventType::GroupValueWrite => Apdu::GroupValueWrite(self.data),
+            GroupEventType::GroupValueResponse => Apdu::GroupValueResponse(self.data),
+        };
+        LData::<D>{data, destination: self.address, ..LData::<D>::default()}
+    }
+}
+
+impl GroupEvent<Vec<u8>> {
+    pub(crate) fn from_l_data(l_data: LData<Vec<u8>>) -> Option<GroupEvent<Vec<u8>>> {
+        match l_data.data {
+            Apdu::GroupValueRead => {
+                Some(GroupEvent::<Vec<u8>> {
+                    data: vec![],
+                    address: l_data.destination,
+                    event_type: GroupEventType::GroupValueRead,
+                })
+            }
+            Apdu::GroupValueResponse(data) => {
+                Some(GroupEvent::<Vec<u8>> {
+                    data,
+                    address: l_data.destination,
+                    event_type: GroupEventType::GroupValueResponse,
+                })
+            }
+            Apdu::GroupValueWrite(data) => {
+                Some(GroupEvent::<Vec<u8>> {
+                    data,
+                    address: l_data.destination,
+                    event_type: GroupEventType::GroupValueWrite,
+                })
+            }
+            _ => None
+        }
+    }
+}
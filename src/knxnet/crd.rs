@@ -1,5 +1,6 @@
 use byteorder::{BigEndian, ByteOrder};
 use strum_macros::FromRepr;
+use crate::address::IndividualAddress;
 use crate::knxnet::KnxNetIpError;
 
 
@@ -8,7 +9,7 @@ use crate::knxnet::KnxNetIpError;
 pub enum ConnectionRespType {
     DeviceMgmtConnection = 0x03,
     TunnelConnection{
-        address: u16 // TODO use individual address type
+        address: IndividualAddress
     } = 0x04,
     RemlogConnection = 0x06,
     RemconfConnection = 0x07,
@@ -17,7 +18,7 @@ pub enum ConnectionRespType {
 
 impl Default for ConnectionRespType{
     fn default() -> Self {
-        ConnectionRespType::TunnelConnection {address: 0}
+        ConnectionRespType::TunnelConnection {address: IndividualAddress::default()}
     }
 }
 
@@ -25,7 +26,7 @@ impl ConnectionRespType {
     pub(crate) fn length(&self) -> u16{
         match self {
             ConnectionRespType::TunnelConnection{address}  => 4, // tunnel CRD has length, type and an u16 address
-            _ => 2 // others are not yet implemented
+            _ => 2 // others have no additional data
         }
     }
 
@@ -41,13 +42,22 @@ impl ConnectionRespType {
         buf.push(self.identifier());
         match self {
             ConnectionRespType::TunnelConnection{address} => {
-                buf.extend(address.to_be_bytes());
+                buf.extend(address.to_u16().to_be_bytes());
             }
-            _ => {panic!("Not yet implemented")}
+            _ => {}
         }
     }
 
     pub(crate) fn decode(buf: &[u8]) -> Result<ConnectionRespType, KnxNetIpError> {
+        if buf.len() < 2 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        if buf[1] == ConnectionRespType::DeviceMgmtConnection.identifier() {
+            if buf[0] != 2 {
+                return Err(KnxNetIpError::InvalidSize)
+            }
+            return Ok(ConnectionRespType::DeviceMgmtConnection)
+        }
         if buf[1] != 4 {
             return Err(KnxNetIpError::NotImplemented)
         }
@@ -55,7 +65,7 @@ impl ConnectionRespType {
             return Err(KnxNetIpError::InvalidSize)
         }
         return Ok(ConnectionRespType::TunnelConnection{
-            address: BigEndian::read_u16(&buf[2..4])
+            address: IndividualAddress::from_u16(BigEndian::read_u16(&buf[2..4]))
         });
     }
 }
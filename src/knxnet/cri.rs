@@ -1,4 +1,6 @@
+use byteorder::{BigEndian, ByteOrder};
 use strum_macros::FromRepr;
+use crate::address::IndividualAddress;
 use crate::knxnet::KnxNetIpError;
 
 #[derive(FromRepr, Debug, Copy, Clone, PartialEq)]
@@ -6,7 +8,9 @@ use crate::knxnet::KnxNetIpError;
 pub enum ConnectionReqType {
     DeviceMgmtConnection = 0x03,
     TunnelConnection{
-        layer: TunnelingLayer
+        layer: TunnelingLayer,
+        /// Requested individual address of the tunnel (extended CRI)
+        address: Option<IndividualAddress>,
     } = 0x04,
     RemlogConnection = 0x06,
     RemconfConnection = 0x07,
@@ -15,7 +19,7 @@ pub enum ConnectionReqType {
 
 impl Default for ConnectionReqType{
     fn default() -> Self {
-        ConnectionReqType::TunnelConnection{layer: TunnelingLayer::default() }
+        ConnectionReqType::TunnelConnection{layer: TunnelingLayer::default(), address: None}
     }
 }
 
@@ -32,8 +36,10 @@ pub enum TunnelingLayer {
 impl ConnectionReqType {
     pub(crate) fn length(&self) -> u16{
         match self {
-            ConnectionReqType::TunnelConnection{layer}  => 4, // tunnel CRI has length, type, link and one reserved byte
-            _ => 2 // others are not yet implemented
+            // tunnel CRI has length, type, link and one reserved byte, the extended one is followed by the address
+            ConnectionReqType::TunnelConnection{address: None, ..} => 4,
+            ConnectionReqType::TunnelConnection{address: Some(_), ..} => 6,
+            _ => 2 // others have no additional data
         }
     }
 
@@ -48,26 +54,39 @@ impl ConnectionReqType {
         buf.push(self.length() as u8); // length
         buf.push(self.identifier());
         match self {
-            ConnectionReqType::TunnelConnection{layer} => {
+            ConnectionReqType::TunnelConnection{layer, address} => {
                 buf.push(*layer as u8);
                 buf.push(0);
+                if let Some(address) = address {
+                    buf.extend(address.to_u16().to_be_bytes());
+                }
             }
-            _ => {panic!("Not yet implemented")}
+            _ => {}
         }
     }
 
     pub(crate) fn decode(buf: &[u8]) -> Result<ConnectionReqType, KnxNetIpError> {
+        if buf.len() < 2 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        if buf[1] == ConnectionReqType::DeviceMgmtConnection.identifier() {
+            if buf[0] != 2 {
+                return Err(KnxNetIpError::InvalidSize)
+            }
+            return Ok(ConnectionReqType::DeviceMgmtConnection)
+        }
         if buf[1] != 4 {
             return Err(KnxNetIpError::NotImplemented)
         }
-        if buf[0] != 4 {
+        if (buf[0] != 4 && buf[0] != 6) || buf.len() < buf[0] as usize {
             return Err(KnxNetIpError::InvalidSize)
         }
         return Ok(ConnectionReqType::TunnelConnection{
             layer: match TunnelingLayer::from_repr(buf[2]){
                 Some(layer) => layer,
                 None => return Err(KnxNetIpError::UnknownLayer(buf[2]))
-            }
+            },
+            address: if buf[0] == 6 { Some(IndividualAddress::from_u16(BigEndian::read_u16(&buf[4..6]))) } else { None },
         });
     }
 }
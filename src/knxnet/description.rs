@@ -0,0 +1,50 @@
+use crate::knxnet::dib::Dib;
+use crate::knxnet::hpai::HPAI;
+use crate::knxnet::KnxNetIpError;
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct DescriptionRequest {
+    pub(crate) control: HPAI,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct DescriptionResponse {
+    pub(crate) dibs: Vec<Dib>,
+}
+
+impl DescriptionRequest {
+    pub(crate) fn payload_length(&self) -> u16 {
+        HPAI::length()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+        self.control.encode(buf);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < HPAI::length() as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.control = HPAI::decode(&buf[0..8])?;
+        Ok(())
+    }
+}
+
+impl DescriptionResponse {
+    pub(crate) fn payload_length(&self) -> u16 {
+        self.dibs.iter().filter(|d| d.check().is_ok()).map(|d| d.length()).sum()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+        // oversized DIBs are left out
+        self.dibs.iter().for_each(|d| { let _ = d.encode(buf); });
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 2 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.dibs = Dib::decode_all(buf)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use crate::cemi;
+use crate::cemi::Message;
+use crate::dpt::DPT;
+use crate::knxnet::KnxNetIpError;
+use crate::knxnet::status::StatusCode;
+
+// Device management uses the same connection header as tunnelling (See 3/8/3 4.2.6)
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct DeviceConfigurationRequest<D: DPT+Default> {
+    pub(crate) channel: u8,
+    pub(crate) seq: u8,
+    pub(crate) data: cemi::Message<D>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct DeviceConfigurationAck {
+    pub(crate) channel: u8,
+    pub(crate) seq: u8,
+    pub(crate) status: StatusCode,
+}
+
+impl<D: DPT+Default> DeviceConfigurationRequest<D> {
+    pub(crate) fn payload_length(&self) -> u16 {
+        4 + self.data.length()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(0x4);
+        buf.push(self.channel);
+        buf.push(self.seq);
+        buf.push(0x00); // reserved
+        self.data.encode(buf);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 0x5usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.channel = buf[1];
+        self.seq = buf[2];
+        self.data = Message::decode(&buf[4..])?;
+        Ok(())
+    }
+}
+
+impl DeviceConfigurationAck {
+    pub(crate) fn payload_length(&self) -> u16 {
+        4
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(0x4);
+        buf.push(self.channel);
+        buf.push(self.seq);
+        buf.push(self.status as u8);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 0x4 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.channel = buf[1];
+        self.seq = buf[2];
+        self.status = match StatusCode::from_repr(buf[3]) {
+            Some(status) => status,
+            None => return Err(KnxNetIpError::UnknownStatus(buf[3]))
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,514 @@
+use byteorder::{BigEndian, ByteOrder};
+use strum_macros::FromRepr;
+use crate::address::IndividualAddress;
+use crate::knxnet::KnxNetIpError;
+
+// Description Information Blocks (See 3/8/2 7.5.4 and 3/8/4 5.5)
+
+const FRIENDLY_NAME_LENGTH: usize = 30;
+
+#[derive(FromRepr, Debug, Clone, PartialEq)]
+#[repr(u8)]
+pub enum Dib {
+    DeviceInfo(DeviceInformation) = 0x01,
+    SupportedServiceFamilies(ServiceFamilies) = 0x02,
+    IpConfig(IpConfig) = 0x03,
+    CurrentIpConfig(CurrentIpConfig) = 0x04,
+    KnxAddresses(KnxAddresses) = 0x05,
+    SecuredServiceFamilies(ServiceFamilies) = 0x06,
+    TunnellingInfo(TunnellingInfo) = 0x07,
+    ExtendedDeviceInfo(ExtendedDeviceInformation) = 0x08,
+    ManufacturerData(ManufacturerData) = 0xFE,
+}
+
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum DibType {
+    DeviceInfo = 0x01,
+    SupportedServiceFamilies = 0x02,
+    IpConfig = 0x03,
+    CurrentIpConfig = 0x04,
+    KnxAddresses = 0x05,
+    SecuredServiceFamilies = 0x06,
+    TunnellingInfo = 0x07,
+    ExtendedDeviceInfo = 0x08,
+    ManufacturerData = 0xFE,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum KnxMedium {
+    Tp0,
+    #[default]
+    Tp1,
+    Pl110,
+    Pl132,
+    Rf,
+    KnxIp,
+    /// Medium code not known to this library
+    Unknown(u8),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ServiceFamilyType {
+    #[default]
+    Core,
+    DeviceManagement,
+    Tunnelling,
+    Routing,
+    RemoteLogging,
+    RemoteConfiguration,
+    ObjectServer,
+    Security,
+    /// Service family code not known to this library, e.g. of a newer specification or a manufacturer
+    Unknown(u8),
+}
+
+impl KnxMedium {
+    pub fn from_u8(code: u8) -> KnxMedium {
+        match code {
+            0x01 => KnxMedium::Tp0,
+            0x02 => KnxMedium::Tp1,
+            0x04 => KnxMedium::Pl110,
+            0x08 => KnxMedium::Pl132,
+            0x10 => KnxMedium::Rf,
+            0x20 => KnxMedium::KnxIp,
+            code => KnxMedium::Unknown(code),
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            KnxMedium::Tp0 => 0x01,
+            KnxMedium::Tp1 => 0x02,
+            KnxMedium::Pl110 => 0x04,
+            KnxMedium::Pl132 => 0x08,
+            KnxMedium::Rf => 0x10,
+            KnxMedium::KnxIp => 0x20,
+            KnxMedium::Unknown(code) => *code,
+        }
+    }
+}
+
+impl ServiceFamilyType {
+    pub fn from_u8(code: u8) -> ServiceFamilyType {
+        match code {
+            0x02 => ServiceFamilyType::Core,
+            0x03 => ServiceFamilyType::DeviceManagement,
+            0x04 => ServiceFamilyType::Tunnelling,
+            0x05 => ServiceFamilyType::Routing,
+            0x06 => ServiceFamilyType::RemoteLogging,
+            0x07 => ServiceFamilyType::RemoteConfiguration,
+            0x08 => ServiceFamilyType::ObjectServer,
+            0x09 => ServiceFamilyType::Security,
+            code => ServiceFamilyType::Unknown(code),
+        }
+    }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            ServiceFamilyType::Core => 0x02,
+            ServiceFamilyType::DeviceManagement => 0x03,
+            ServiceFamilyType::Tunnelling => 0x04,
+            ServiceFamilyType::Routing => 0x05,
+            ServiceFamilyType::RemoteLogging => 0x06,
+            ServiceFamilyType::RemoteConfiguration => 0x07,
+            ServiceFamilyType::ObjectServer => 0x08,
+            ServiceFamilyType::Security => 0x09,
+            ServiceFamilyType::Unknown(code) => *code,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ServiceFamily {
+    pub family: ServiceFamilyType,
+    pub version: u8,
+}
+
+/// Device information DIB as sent in search and description responses
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DeviceInformation {
+    pub medium: KnxMedium,
+    pub programming_mode: bool,
+    pub individual_address: IndividualAddress,
+    pub project_installation_id: u16,
+    pub serial_number: [u8; 6],
+    pub multicast_address: [u8; 4],
+    pub mac_address: [u8; 6],
+    pub friendly_name: String,
+}
+
+/// Supported or secured service families DIB listing the KNXnet/IP services of a device
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ServiceFamilies {
+    pub families: Vec<ServiceFamily>,
+}
+
+/// Configured IP settings, `capabilities` and `assignment_method` hold the raw bit fields
+/// (BootP 0x01, DHCP 0x02, AutoIP 0x04 for capabilities and
+/// manual 0x01, BootP 0x02, DHCP 0x04, AutoIP 0x08 for the assignment method)
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct IpConfig {
+    pub ip_address: [u8; 4],
+    pub subnet_mask: [u8; 4],
+    pub default_gateway: [u8; 4],
+    pub capabilities: u8,
+    pub assignment_method: u8,
+}
+
+/// IP settings currently in use
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct CurrentIpConfig {
+    pub ip_address: [u8; 4],
+    pub subnet_mask: [u8; 4],
+    pub default_gateway: [u8; 4],
+    pub dhcp_server: [u8; 4],
+    pub assignment_method: u8,
+}
+
+/// Individual address of the device and the additional addresses e.g. used for tunnelling
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KnxAddresses {
+    pub individual_address: IndividualAddress,
+    pub additional_addresses: Vec<IndividualAddress>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct TunnellingSlot {
+    pub address: IndividualAddress,
+    pub free: bool,
+    pub authorised: bool,
+    pub usable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TunnellingInfo {
+    pub max_apdu_length: u16,
+    pub slots: Vec<TunnellingSlot>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ExtendedDeviceInformation {
+    pub medium_status: u8,
+    pub max_local_apdu_length: u16,
+    pub device_descriptor: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ManufacturerData {
+    pub manufacturer_id: u16,
+    pub data: Vec<u8>,
+}
+
+impl Dib {
+    pub(crate) fn length(&self) -> u16 {
+        // oversized DIBs saturate, they are rejected by `check`
+        2u16.saturating_add(match self {
+            Self::DeviceInfo(d) => d.payload_length(),
+            Self::SupportedServiceFamilies(d) => d.payload_length(),
+            Self::IpConfig(d) => d.payload_length(),
+            Self::CurrentIpConfig(d) => d.payload_length(),
+            Self::KnxAddresses(d) => d.payload_length(),
+            Self::SecuredServiceFamilies(d) => d.payload_length(),
+            Self::TunnellingInfo(d) => d.payload_length(),
+            Self::ExtendedDeviceInfo(d) => d.payload_length(),
+            Self::ManufacturerData(d) => d.payload_length(),
+        })
+    }
+
+    /// Returns `InvalidSize` if the DIB does not fit into its one byte length field
+    pub(crate) fn check(&self) -> Result<(), KnxNetIpError> {
+        if self.length() > u8::MAX as u16 {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        Ok(())
+    }
+
+    pub fn dib_type(&self) -> DibType {
+        // every variant has a matching DibType
+        DibType::from_repr(self.identifier()).unwrap()
+    }
+
+    fn identifier(&self) -> u8 {
+        // SAFETY: Because `Self` is marked `repr(u8)`, its layout is a `repr(C)` `union`
+        // between `repr(C)` structs, each of which has the `u8` discriminant as its first
+        // field, so we can read the discriminant without offsetting the pointer.
+        unsafe { *<*const _>::from(self).cast::<u8>() }
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> Result<(), KnxNetIpError> {
+        let length = u8::try_from(self.length()).map_err(|_| KnxNetIpError::InvalidSize)?;
+        buf.push(length);
+        buf.push(self.identifier());
+        match self {
+            Self::DeviceInfo(d) => d.encode(buf),
+            Self::SupportedServiceFamilies(d) => d.encode(buf),
+            Self::IpConfig(d) => d.encode(buf),
+            Self::CurrentIpConfig(d) => d.encode(buf),
+            Self::KnxAddresses(d) => d.encode(buf),
+            Self::SecuredServiceFamilies(d) => d.encode(buf),
+            Self::TunnellingInfo(d) => d.encode(buf),
+            Self::ExtendedDeviceInfo(d) => d.encode(buf),
+            Self::ManufacturerData(d) => d.encode(buf),
+        }
+        Ok(())
+    }
+
+    /// Decode a single DIB, trailing data after the DIB is ignored. Unknown DIB types return `None`.
+    pub(crate) fn decode(buf: &[u8]) -> Result<Option<Dib>, KnxNetIpError> {
+        if buf.len() < 2 || buf[0] < 2 || buf.len() < buf[0] as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        let payload = &buf[2..buf[0] as usize];
+        let mut dib = match Dib::from_repr(buf[1]) {
+            Some(dib) => dib,
+            None => return Ok(None)
+        };
+        match dib {
+            Self::DeviceInfo(ref mut d) => d.decode(payload)?,
+            Self::SupportedServiceFamilies(ref mut d) => d.decode(payload)?,
+            Self::IpConfig(ref mut d) => d.decode(payload)?,
+            Self::CurrentIpConfig(ref mut d) => d.decode(payload)?,
+            Self::KnxAddresses(ref mut d) => d.decode(payload)?,
+            Self::SecuredServiceFamilies(ref mut d) => d.decode(payload)?,
+            Self::TunnellingInfo(ref mut d) => d.decode(payload)?,
+            Self::ExtendedDeviceInfo(ref mut d) => d.decode(payload)?,
+            Self::ManufacturerData(ref mut d) => d.decode(payload)?,
+        };
+        Ok(Some(dib))
+    }
+
+    /// Decode a sequence of DIBs filling the whole buffer, DIBs of unknown types are skipped
+    pub(crate) fn decode_all(buf: &[u8]) -> Result<Vec<Dib>, KnxNetIpError> {
+        let mut dibs = vec![];
+        let mut offset = 0;
+        while offset < buf.len() {
+            let dib = Dib::decode(&buf[offset..])?;
+            offset += buf[offset] as usize;
+            dibs.extend(dib);
+        }
+        Ok(dibs)
+    }
+}
+
+impl DeviceInformation {
+    pub(crate) fn payload_length(&self) -> u16 {
+        52
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.medium.to_u8());
+        buf.push(self.programming_mode as u8);
+        buf.extend(self.individual_address.to_u16().to_be_bytes());
+        buf.extend(self.project_installation_id.to_be_bytes());
+        buf.extend_from_slice(&self.serial_number);
+        buf.extend_from_slice(&self.multicast_address);
+        buf.extend_from_slice(&self.mac_address);
+        // friendly name is ISO 8859-1 encoded and padded with zeros
+        let mut name: Vec<u8> = self.friendly_name.chars()
+            .map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' })
+            .take(FRIENDLY_NAME_LENGTH)
+            .collect();
+        name.resize(FRIENDLY_NAME_LENGTH, 0);
+        buf.extend(name);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() != self.payload_length() as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.medium = KnxMedium::from_u8(buf[0]);
+        self.programming_mode = buf[1] & 0x01 != 0;
+        self.individual_address = IndividualAddress::from_u16(BigEndian::read_u16(&buf[2..4]));
+        self.project_installation_id = BigEndian::read_u16(&buf[4..6]);
+        self.serial_number = [buf[6], buf[7], buf[8], buf[9], buf[10], buf[11]];
+        self.multicast_address = [buf[12], buf[13], buf[14], buf[15]];
+        self.mac_address = [buf[16], buf[17], buf[18], buf[19], buf[20], buf[21]];
+        self.friendly_name = buf[22..52].iter()
+            .take_while(|c| **c != 0)
+            .map(|c| *c as char)
+            .collect();
+        Ok(())
+    }
+}
+
+impl ServiceFamilies {
+    pub(crate) fn payload_length(&self) -> u16 {
+        2 * self.families.len() as u16
+    }
+
+    pub fn supports(&self, family: ServiceFamilyType) -> bool {
+        self.families.iter().any(|f| f.family == family)
+    }
+
+    /// Highest announced version of the given service family
+    pub fn version(&self, family: ServiceFamilyType) -> Option<u8> {
+        self.families.iter().filter(|f| f.family == family).map(|f| f.version).max()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        self.families.iter().for_each(|f| {
+            buf.push(f.family.to_u8());
+            buf.push(f.version);
+        });
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() & 1 != 0 {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.families = buf.chunks(2)
+            .map(|f| ServiceFamily {family: ServiceFamilyType::from_u8(f[0]), version: f[1]})
+            .collect();
+        Ok(())
+    }
+}
+
+impl IpConfig {
+    pub(crate) fn payload_length(&self) -> u16 {
+        14
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.ip_address);
+        buf.extend_from_slice(&self.subnet_mask);
+        buf.extend_from_slice(&self.default_gateway);
+        buf.push(self.capabilities);
+        buf.push(self.assignment_method);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() != self.payload_length() as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.ip_address = [buf[0], buf[1], buf[2], buf[3]];
+        self.subnet_mask = [buf[4], buf[5], buf[6], buf[7]];
+        self.default_gateway = [buf[8], buf[9], buf[10], buf[11]];
+        self.capabilities = buf[12];
+        self.assignment_method = buf[13];
+        Ok(())
+    }
+}
+
+impl CurrentIpConfig {
+    pub(crate) fn payload_length(&self) -> u16 {
+        18
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.ip_address);
+        buf.extend_from_slice(&self.subnet_mask);
+        buf.extend_from_slice(&self.default_gateway);
+        buf.extend_from_slice(&self.dhcp_server);
+        buf.push(self.assignment_method);
+        buf.push(0x00); // reserved
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() != self.payload_length() as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.ip_address = [buf[0], buf[1], buf[2], buf[3]];
+        self.subnet_mask = [buf[4], buf[5], buf[6], buf[7]];
+        self.default_gateway = [buf[8], buf[9], buf[10], buf[11]];
+        self.dhcp_server = [buf[12], buf[13], buf[14], buf[15]];
+        self.assignment_method = buf[16];
+        Ok(())
+    }
+}
+
+impl KnxAddresses {
+    pub(crate) fn payload_length(&self) -> u16 {
+        2 + 2 * self.additional_addresses.len() as u16
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.individual_address.to_u16().to_be_bytes());
+        self.additional_addresses.iter().for_each(|a| buf.extend(a.to_u16().to_be_bytes()));
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 2 || buf.len() & 1 != 0 {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.individual_address = IndividualAddress::from_u16(BigEndian::read_u16(&buf[0..2]));
+        self.additional_addresses = buf[2..].chunks(2)
+            .map(|a| IndividualAddress::from_u16(BigEndian::read_u16(a)))
+            .collect();
+        Ok(())
+    }
+}
+
+impl TunnellingInfo {
+    pub(crate) fn payload_length(&self) -> u16 {
+        2 + 4 * self.slots.len() as u16
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.max_apdu_length.to_be_bytes());
+        self.slots.iter().for_each(|s| {
+            buf.extend(s.address.to_u16().to_be_bytes());
+            buf.push(0x00);
+            buf.push(if s.usable {0x04} else {0} | if s.authorised {0x02} else {0} | if s.free {0x01} else {0});
+        });
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 2 || (buf.len() - 2) & 3 != 0 {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.max_apdu_length = BigEndian::read_u16(&buf[0..2]);
+        self.slots = buf[2..].chunks(4)
+            .map(|s| TunnellingSlot {
+                address: IndividualAddress::from_u16(BigEndian::read_u16(&s[0..2])),
+                usable: s[3] & 0x04 != 0,
+                authorised: s[3] & 0x02 != 0,
+                free: s[3] & 0x01 != 0,
+            })
+            .collect();
+        Ok(())
+    }
+}
+
+impl ExtendedDeviceInformation {
+    pub(crate) fn payload_length(&self) -> u16 {
+        6
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.medium_status);
+        buf.push(0x00); // reserved
+        buf.extend(self.max_local_apdu_length.to_be_bytes());
+        buf.extend(self.device_descriptor.to_be_bytes());
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() != self.payload_length() as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.medium_status = buf[0];
+        self.max_local_apdu_length = BigEndian::read_u16(&buf[2..4]);
+        self.device_descriptor = BigEndian::read_u16(&buf[4..6]);
+        Ok(())
+    }
+}
+
+impl ManufacturerData {
+    pub(crate) fn payload_length(&self) -> u16 {
+        u16::try_from(self.data.len()).ok().and_then(|l| l.checked_add(2)).unwrap_or(u16::MAX)
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.manufacturer_id.to_be_bytes());
+        buf.extend_from_slice(&self.data);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 2 {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.manufacturer_id = BigEndian::read_u16(&buf[0..2]);
+        self.data = buf[2..].to_vec();
+        Ok(())
+    }
+}
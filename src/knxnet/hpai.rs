@@ -27,6 +27,18 @@ impl HPAI {
         }
     }
 
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    pub fn address(&self) -> [u8;4] {
+        self.address
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
     pub(crate) fn length() -> u16{
         8
     }
@@ -1,11 +1,19 @@
-pub(crate) mod hpai;
+pub mod hpai;
 pub mod status;
+pub mod dib;
+pub mod srp;
 pub(crate) mod connectionstate;
 pub(crate) mod connect;
 pub(crate) mod cri;
 pub(crate) mod crd;
 pub(crate) mod disconnect;
 pub(crate) mod tunnel;
+pub(crate) mod device_management;
+pub(crate) mod search;
+pub(crate) mod description;
+pub(crate) mod routing;
+pub mod stream;
+pub mod secure;
 
 use strum_macros::FromRepr;
 use thiserror::Error;
@@ -15,10 +23,15 @@ use crate::dpt::DPT;
 use crate::knxnet::connect::{ConnectRequest, ConnectResponse};
 use crate::knxnet::connectionstate::{ConnectionstateRequest, ConnectionstateResponse};
 use crate::knxnet::disconnect::{DisconnectRequest, DisconnectResponse};
-use crate::knxnet::tunnel::{TunnelRequest, TunnelAck};
+use crate::knxnet::tunnel::{TunnelRequest, TunnelAck, TunnellingFeature};
+use crate::knxnet::device_management::{DeviceConfigurationRequest, DeviceConfigurationAck};
+use crate::knxnet::search::{SearchRequest, SearchRequestExtended, SearchResponse};
+use crate::knxnet::description::{DescriptionRequest, DescriptionResponse};
+use crate::knxnet::routing::{RoutingBusy, RoutingIndication, RoutingLostMessage};
+use crate::knxnet::secure::{SecureWrapper, SessionAuthenticate, SessionRequest, SessionResponse, SessionStatus, TimerNotify};
 
-const HEADER_LENGTH: u8 = 0x06;
-const KNXNET_VERSION: u8 = 0x10;
+pub(crate) const HEADER_LENGTH: u8 = 0x06;
+pub(crate) const KNXNET_VERSION: u8 = 0x10;
 
 /// Errors that can arise here
 #[derive(Debug, Error, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -52,42 +65,83 @@ pub enum KnxNetIpError {
     InvalidSize,
     #[error("unknown layer {0:#x}")]
     UnknownLayer(u8),
+    #[error("unknown search request parameter type {0:#x}")]
+    UnknownSrp(u8),
+    #[error("invalid checksum {0:#x}")]
+    InvalidChecksum(u8),
+    #[error("no random source available")]
+    NoRandomSource,
 }
 
 
 #[derive(PartialEq, FromRepr, Debug)]
 #[repr(u16)]
-pub enum Service<D: DPT + Default = ()> {
-    SearchRequest = 0x0201,
-    SearchResponse = 0x0202,
-    DescriptionRequest = 0x0203,
-    DescriptionResponse = 0x0204,
+pub(crate) enum Service<D: DPT + Default = ()> {
+    SearchRequest(SearchRequest) = 0x0201,
+    SearchResponse(SearchResponse) = 0x0202,
+    DescriptionRequest(DescriptionRequest) = 0x0203,
+    DescriptionResponse(DescriptionResponse) = 0x0204,
     ConnectRequest(ConnectRequest) = 0x0205,
     ConnectResponse(ConnectResponse) = 0x0206,
     ConnectionstateRequest(ConnectionstateRequest) = 0x0207,
     ConnectionstateResponse(ConnectionstateResponse) = 0x0208,
     DisconnectRequest(DisconnectRequest) = 0x0209,
     DisconnectResponse(DisconnectResponse) = 0x020A,
+    SearchRequestExtended(SearchRequestExtended) = 0x020B,
+    SearchResponseExtended(SearchResponse) = 0x020C,
     TunnelRequest(TunnelRequest<D>) = 0x0420,
     TunnelAck(TunnelAck) = 0x0421,
-    DeviceConfigurationRequest = 0x0310,
-    DeviceConfigurationAck = 0x0311,
-    RoutingIndication = 0x0530,
-    RoutingLostMessage = 0x0531,
+    TunnellingFeatureGet(TunnellingFeature) = 0x0422,
+    TunnellingFeatureResponse(TunnellingFeature) = 0x0423,
+    TunnellingFeatureSet(TunnellingFeature) = 0x0424,
+    TunnellingFeatureInfo(TunnellingFeature) = 0x0425,
+    DeviceConfigurationRequest(DeviceConfigurationRequest<D>) = 0x0310,
+    DeviceConfigurationAck(DeviceConfigurationAck) = 0x0311,
+    RoutingIndication(RoutingIndication<D>) = 0x0530,
+    RoutingLostMessage(RoutingLostMessage) = 0x0531,
+    RoutingBusy(RoutingBusy) = 0x0532,
+    RoutingSystemBroadcast(RoutingIndication<D>) = 0x0533,
+    SecureWrapper(SecureWrapper) = 0x0950,
+    SessionRequest(SessionRequest) = 0x0951,
+    SessionResponse(SessionResponse) = 0x0952,
+    SessionAuthenticate(SessionAuthenticate) = 0x0953,
+    SessionStatus(SessionStatus) = 0x0954,
+    TimerNotify(TimerNotify) = 0x0955,
 }
 
 impl<D:DPT+Default> Service<D> {
     pub(crate) fn length(&self)->u16 {
         return 6 + match self {
+            Self::SearchRequest(r) => r.payload_length(),
+            Self::SearchResponse(r) => r.payload_length(),
+            Self::DescriptionRequest(r) => r.payload_length(),
+            Self::DescriptionResponse(r) => r.payload_length(),
             Self::ConnectRequest(r) => r.payload_length(),
             Self::ConnectResponse(r) => r.payload_length(),
             Self::ConnectionstateRequest(r) => r.payload_length(),
             Self::ConnectionstateResponse(r) => r.payload_length(),
             Self::DisconnectRequest(r) => r.payload_length(),
             Self::DisconnectResponse(r) => r.payload_length(),
+            Self::SearchRequestExtended(r) => r.payload_length(),
+            Self::SearchResponseExtended(r) => r.payload_length(),
             Self::TunnelRequest(r) => r.payload_length(),
             Self::TunnelAck(a) => a.payload_length(),
-            _ => 0
+            Self::TunnellingFeatureGet(r) => r.payload_length(),
+            Self::TunnellingFeatureResponse(r) => r.payload_length(),
+            Self::TunnellingFeatureSet(r) => r.payload_length(),
+            Self::TunnellingFeatureInfo(r) => r.payload_length(),
+            Self::DeviceConfigurationRequest(r) => r.payload_length(),
+            Self::DeviceConfigurationAck(a) => a.payload_length(),
+            Self::RoutingIndication(r) => r.payload_length(),
+            Self::RoutingLostMessage(r) => r.payload_length(),
+            Self::RoutingBusy(r) => r.payload_length(),
+            Self::RoutingSystemBroadcast(r) => r.payload_length(),
+            Self::SecureWrapper(r) => r.payload_length(),
+            Self::SessionRequest(r) => r.payload_length(),
+            Self::SessionResponse(r) => r.payload_length(),
+            Self::SessionAuthenticate(r) => r.payload_length(),
+            Self::SessionStatus(r) => r.payload_length(),
+            Self::TimerNotify(r) => r.payload_length(),
         }
     }
 
@@ -98,22 +152,48 @@ impl<D:DPT+Default> Service<D> {
         unsafe { *<*const _>::from(self).cast::<u16>() }
     }
 
+    /// KNXnet/IP header of the encoded service
+    pub(crate) fn header(&self) -> [u8; 6] {
+        let mut header = [HEADER_LENGTH, KNXNET_VERSION, 0, 0, 0, 0];
+        header[2..4].copy_from_slice(&self.identifier().to_be_bytes());
+        header[4..6].copy_from_slice(&self.length().to_be_bytes());
+        header
+    }
+
     pub(crate) fn encoded(&self) -> Vec<u8>{
         let mut buf = Vec::<u8>::with_capacity(self.length() as usize);
-        buf.push(HEADER_LENGTH); // header length
-        buf.push(KNXNET_VERSION); // version
-        buf.extend(self.identifier().to_be_bytes());
-        buf.extend(self.length().to_be_bytes());
+        buf.extend(self.header());
         match self {
+            Self::SearchRequest(r) => r.encode(&mut buf),
+            Self::SearchResponse(r) => r.encode(&mut buf),
+            Self::DescriptionRequest(r) => r.encode(&mut buf),
+            Self::DescriptionResponse(r) => r.encode(&mut buf),
             Self::ConnectRequest(r) => r.encode(&mut buf),
             Self::ConnectResponse(r) => r.encode(&mut buf),
             Self::ConnectionstateRequest(r) => r.encode(&mut buf),
             Self::ConnectionstateResponse(r) => r.encode(&mut buf),
             Self::DisconnectRequest(r) => r.encode(&mut buf),
             Self::DisconnectResponse(r) => r.encode(&mut buf),
+            Self::SearchRequestExtended(r) => r.encode(&mut buf),
+            Self::SearchResponseExtended(r) => r.encode(&mut buf),
             Self::TunnelRequest(r) => r.encode(&mut buf),
             Self::TunnelAck(a) => a.encode(&mut buf),
-            _ => {}
+            Self::TunnellingFeatureGet(r) => r.encode(&mut buf),
+            Self::TunnellingFeatureResponse(r) => r.encode(&mut buf),
+            Self::TunnellingFeatureSet(r) => r.encode(&mut buf),
+            Self::TunnellingFeatureInfo(r) => r.encode(&mut buf),
+            Self::DeviceConfigurationRequest(r) => r.encode(&mut buf),
+            Self::DeviceConfigurationAck(a) => a.encode(&mut buf),
+            Self::RoutingIndication(r) => r.encode(&mut buf),
+            Self::RoutingLostMessage(r) => r.encode(&mut buf),
+            Self::RoutingBusy(r) => r.encode(&mut buf),
+            Self::RoutingSystemBroadcast(r) => r.encode(&mut buf),
+            Self::SecureWrapper(r) => r.encode(&mut buf),
+            Self::SessionRequest(r) => r.encode(&mut buf),
+            Self::SessionResponse(r) => r.encode(&mut buf),
+            Self::SessionAuthenticate(r) => r.encode(&mut buf),
+            Self::SessionStatus(r) => r.encode(&mut buf),
+            Self::TimerNotify(r) => r.encode(&mut buf),
         }
         return buf;
     }
@@ -138,6 +218,22 @@ impl<D:DPT+Default> Service<D> {
             None => Err(KnxNetIpError::UnknownService(service_identifier)),
             Some(mut service) => {
                 match service {
+                    Self::SearchRequest(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::SearchResponse(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::DescriptionRequest(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::DescriptionResponse(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
                     Self::ConnectRequest(mut r) => {
                         r.decode(&buf[6..])?;
                         Ok(Self::ConnectRequest(r))
@@ -162,6 +258,14 @@ impl<D:DPT+Default> Service<D> {
                         r.decode(&buf[6..])?;
                         Ok(Self::DisconnectResponse(r))
                     },
+                    Self::SearchRequestExtended(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::SearchResponseExtended(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
                     Self::TunnelRequest(ref mut r) => {
                         r.decode(&buf[6..])?;
                         Ok(service)
@@ -170,7 +274,70 @@ impl<D:DPT+Default> Service<D> {
                         r.decode(&buf[6..])?;
                         Ok(service)
                     }
-                    _ => Ok(service)
+                    Self::TunnellingFeatureGet(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::TunnellingFeatureResponse(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::TunnellingFeatureSet(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::TunnellingFeatureInfo(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::DeviceConfigurationRequest(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::DeviceConfigurationAck(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::RoutingIndication(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::RoutingLostMessage(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::RoutingBusy(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::RoutingSystemBroadcast(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::SecureWrapper(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::SessionRequest(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::SessionResponse(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::SessionAuthenticate(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::SessionStatus(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
+                    Self::TimerNotify(ref mut r) => {
+                        r.decode(&buf[6..])?;
+                        Ok(service)
+                    }
                 }
             }
         };
@@ -192,7 +359,19 @@ mod tests {
     use crate::knxnet::disconnect::{DisconnectRequest, DisconnectResponse};
     use crate::knxnet::hpai::{HPAI, Protocol};
     use crate::knxnet::status::StatusCode;
-    use crate::knxnet::tunnel::{TunnelAck, TunnelRequest};
+    use crate::knxnet::tunnel::{TunnelAck, TunnelRequest, TunnellingFeature};
+    use crate::address::IndividualAddress;
+    use crate::knxnet::dib::{Dib, DeviceInformation, ExtendedDeviceInformation, IpConfig, KnxAddresses, KnxMedium, ManufacturerData, ServiceFamilies, ServiceFamily, ServiceFamilyType, TunnellingInfo, TunnellingSlot};
+    use crate::knxnet::search::{SearchRequest, SearchRequestExtended, SearchResponse};
+    use crate::knxnet::srp::Srp;
+    use crate::knxnet::routing::{RoutingBusy, RoutingLostMessage};
+    use crate::knxnet::dib::DibType;
+    use crate::knxnet::description::{DescriptionRequest, DescriptionResponse};
+    use crate::knxnet::device_management::{DeviceConfigurationAck, DeviceConfigurationRequest};
+    use crate::cemi::property::Property;
+    use crate::knxnet::stream::StreamReassembler;
+    use crate::knxnet::KnxNetIpError;
+    use crate::knxnet::secure::{SecureWrapper, SessionAuthenticate, SessionRequest, SessionResponse, SessionStatus, SessionStatusCode, TimerNotify};
 
     #[test]
     fn t_service_length() {
@@ -204,7 +383,7 @@ mod tests {
         assert_eq!(Service::<()>::DisconnectResponse(DisconnectResponse::default()).length(), 8);
         assert_eq!(Service::<()>::TunnelRequest(TunnelRequest::<()>::default()).length(), 12);
         assert_eq!(Service::<()>::TunnelAck(TunnelAck::default()).length(), 10);
-        assert_eq!(Service::<()>::SearchRequest.length(), 6); // TODO change after implementation
+        assert_eq!(Service::<()>::SearchRequest(SearchRequest::default()).length(), 14);
     }
 
     #[test]
@@ -222,6 +401,7 @@ mod tests {
             },
             connection_type: ConnectionReqType::TunnelConnection {
                 layer: TunnelingLayer::TunnelLinkLayer,
+                address: None,
             }
         }).encoded(), vec![0x06, 0x10, 0x02, 0x05, 0x00, 0x1A, 0x08, 0x01, 192, 168, 200, 12, 0xC3, 0xB4, 0x08, 0x01, 192, 168, 200, 20, 0xC3, 0xB4, 0x04, 0x04, 0x02, 0x00]);
         assert_eq!(Service::<()>::ConnectResponse(ConnectResponse{
@@ -231,7 +411,7 @@ mod tests {
                 address: [192,168,200,20],
             },
             connection_type: ConnectionRespType::TunnelConnection {
-                address: (2<<11)|(1<<8)|(10),
+                address: IndividualAddress::new(1, 1, 10),
             },
             status: StatusCode::NoError,
             channel: 21,
@@ -319,6 +499,7 @@ mod tests {
                        },
                        connection_type: ConnectionReqType::TunnelConnection {
                            layer: TunnelingLayer::TunnelLinkLayer,
+                           address: None,
                        }
                    })));
         assert_eq!(Service::<()>::decoded(&vec![0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00, 0x08, 0x01, 192, 168, 200, 20, 0xC3, 0xB4, 0x04, 0x04, 0x11, 0x0A]),
@@ -329,7 +510,7 @@ mod tests {
                            address: [192,168,200,20],
                        },
                        connection_type: ConnectionRespType::TunnelConnection {
-                           address: (2<<11)|(1<<8)|(10),
+                           address: IndividualAddress::new(1, 1, 10),
                        },
                        status: StatusCode::NoError,
                        channel: 21,
@@ -342,7 +523,7 @@ mod tests {
                            address: [0,0,0,0],
                        },
                        connection_type: ConnectionRespType::TunnelConnection {
-                           address: (0),
+                           address: IndividualAddress::default(),
                        },
                        status: StatusCode::ErrNoMoreConnections,
                        channel: 0,
@@ -417,4 +598,349 @@ mod tests {
         assert_eq!(Service::<()>::decoded(&vec![0x06, 0x10, 0x02, 0x08, 0x00, 0x08, 0x00, 0xff]).unwrap_err().to_string(),
                    "unknown status code 255");
     }
+
+    #[test]
+    fn t_service_search() {
+        let search_request = vec![0x06, 0x10, 0x02, 0x01, 0x00, 0x0E, 0x08, 0x01, 224, 0, 23, 12, 0x0E, 0x57];
+        assert_eq!(Service::<()>::SearchRequest(SearchRequest{
+            discovery: HPAI{
+                protocol: Protocol::Udp4Protocol,
+                port: 3671,
+                address: [224, 0, 23, 12],
+            }
+        }).encoded(), search_request);
+
+        let search_response = vec![0x06, 0x10, 0x02, 0x02, 0x00, 0x4E, 0x08, 0x01, 0xC0, 0xA8, 0x2A, 0x0A, 0x0E, 0x57,
+                                   0x36, 0x01, 0x20, 0x01, 0x11, 0x00, 0x00, 0x00, 0x00, 0x01, 0x31, 0x3B, 0x0F, 0x22,
+                                   0xE0, 0x00, 0x17, 0x0C, 0x00, 0x01, 0x31, 0x3B, 0x0F, 0x22, 0x47, 0x69, 0x72, 0x61,
+                                   0x20, 0x4B, 0x4E, 0x58, 0x2F, 0x49, 0x50, 0x2D, 0x52, 0x6F, 0x75, 0x74, 0x65, 0x72,
+                                   0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                                   0x0A, 0x02, 0x02, 0x02, 0x03, 0x02, 0x04, 0x02, 0x05, 0x02];
+        let device_info = DeviceInformation{
+            medium: KnxMedium::KnxIp,
+            programming_mode: true,
+            individual_address: IndividualAddress::new(1, 1, 0),
+            project_installation_id: 0,
+            serial_number: [0x00, 0x01, 0x31, 0x3B, 0x0F, 0x22],
+            multicast_address: [224, 0, 23, 12],
+            mac_address: [0x00, 0x01, 0x31, 0x3B, 0x0F, 0x22],
+            friendly_name: "Gira KNX/IP-Router".to_string(),
+        };
+        let response = SearchResponse{
+            control: HPAI{
+                protocol: Protocol::Udp4Protocol,
+                port: 3671,
+                address: [192, 168, 42, 10],
+            },
+            dibs: vec![Dib::DeviceInfo(device_info.clone()),
+            Dib::SupportedServiceFamilies(ServiceFamilies{
+                families: vec![
+                    ServiceFamily{family: ServiceFamilyType::Core, version: 2},
+                    ServiceFamily{family: ServiceFamilyType::DeviceManagement, version: 2},
+                    ServiceFamily{family: ServiceFamilyType::Tunnelling, version: 2},
+                    ServiceFamily{family: ServiceFamilyType::Routing, version: 2},
+                ]
+            })],
+        };
+        assert_eq!(Service::<()>::decoded(&search_response), Ok(Service::SearchResponse(response)));
+        match Service::<()>::decoded(&search_response) {
+            Ok(service) => assert_eq!(service.encoded(), search_response),
+            Err(e) => panic!("{}", e)
+        }
+        assert_eq!(Service::<()>::decoded(&search_response[..40]).unwrap_err().to_string(),
+                   "size in header 78 does not match message length 40");
+
+        // unknown media are kept as they are
+        let mut search_response = search_response;
+        search_response[16] = 0x40;
+        match Service::<()>::decoded(&search_response) {
+            Ok(Service::SearchResponse(response)) => {
+                assert_eq!(response.dibs[0], Dib::DeviceInfo(DeviceInformation{medium: KnxMedium::Unknown(0x40), ..device_info}));
+                assert_eq!(Service::<()>::SearchResponse(response).encoded(), search_response);
+            }
+            r => panic!("{:?}", r)
+        }
+    }
+
+    #[test]
+    fn t_service_description() {
+        assert_eq!(Service::<()>::DescriptionRequest(DescriptionRequest{
+            control: HPAI{
+                protocol: Protocol::Udp4Protocol,
+                port: 50100,
+                address: [192, 168, 200, 12],
+            }
+        }).encoded(), vec![0x06, 0x10, 0x02, 0x03, 0x00, 0x0E, 0x08, 0x01, 192, 168, 200, 12, 0xC3, 0xB4]);
+
+        let description_response = vec![0x06, 0x10, 0x02, 0x04, 0x00, 0x40,
+                                        0x08, 0x02, 0x02, 0x02, 0x04, 0x02, 0x09, 0x01,
+                                        0x06, 0x06, 0x04, 0x02, 0x09, 0x01,
+                                        0x10, 0x03, 192, 168, 42, 10, 255, 255, 255, 0, 192, 168, 42, 1, 0x03, 0x01,
+                                        0x08, 0x05, 0x11, 0x00, 0x11, 0xF1, 0x11, 0xF2,
+                                        0x0C, 0x07, 0x00, 0xFE, 0x11, 0xF1, 0x00, 0x07, 0x11, 0xF2, 0x00, 0x04,
+                                        0x08, 0x08, 0x01, 0x00, 0x00, 0xFE, 0x09, 0x1A];
+        let response = DescriptionResponse{
+            dibs: vec![
+                Dib::SupportedServiceFamilies(ServiceFamilies{
+                    families: vec![
+                        ServiceFamily{family: ServiceFamilyType::Core, version: 2},
+                        ServiceFamily{family: ServiceFamilyType::Tunnelling, version: 2},
+                        ServiceFamily{family: ServiceFamilyType::Security, version: 1},
+                    ]
+                }),
+                Dib::SecuredServiceFamilies(ServiceFamilies{
+                    families: vec![
+                        ServiceFamily{family: ServiceFamilyType::Tunnelling, version: 2},
+                        ServiceFamily{family: ServiceFamilyType::Security, version: 1},
+                    ]
+                }),
+                Dib::IpConfig(IpConfig{
+                    ip_address: [192, 168, 42, 10],
+                    subnet_mask: [255, 255, 255, 0],
+                    default_gateway: [192, 168, 42, 1],
+                    capabilities: 0x03,
+                    assignment_method: 0x01,
+                }),
+                Dib::KnxAddresses(KnxAddresses{
+                    individual_address: IndividualAddress::new(1, 1, 0),
+                    additional_addresses: vec![IndividualAddress::new(1, 1, 241), IndividualAddress::new(1, 1, 242)],
+                }),
+                Dib::TunnellingInfo(TunnellingInfo{
+                    max_apdu_length: 254,
+                    slots: vec![
+                        TunnellingSlot{address: IndividualAddress::new(1, 1, 241), usable: true, authorised: true, free: true},
+                        TunnellingSlot{address: IndividualAddress::new(1, 1, 242), usable: true, authorised: false, free: false},
+                    ]
+                }),
+                Dib::ExtendedDeviceInfo(ExtendedDeviceInformation{
+                    medium_status: 0x01,
+                    max_local_apdu_length: 254,
+                    device_descriptor: 0x091A,
+                }),
+            ]
+        };
+        assert_eq!(Service::<()>::decoded(&description_response), Ok(Service::DescriptionResponse(response)));
+        match Service::<()>::decoded(&description_response) {
+            Ok(service) => assert_eq!(service.encoded(), description_response),
+            Err(e) => panic!("{}", e)
+        }
+        // unknown DIBs are skipped, unknown service families are kept
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x02, 0x04, 0x00, 0x10, 0x04, 0x0B, 0x00, 0x00, 0x06, 0x02, 0x02, 0x02, 0x0A, 0x01]),
+                   Ok(Service::DescriptionResponse(DescriptionResponse{dibs: vec![
+                       Dib::SupportedServiceFamilies(ServiceFamilies{
+                           families: vec![
+                               ServiceFamily{family: ServiceFamilyType::Core, version: 2},
+                               ServiceFamily{family: ServiceFamilyType::Unknown(0x0A), version: 1},
+                           ]
+                       }),
+                   ]})));
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x02, 0x04, 0x00, 0x0A, 0x06, 0x02, 0x02, 0x02]).unwrap_err().to_string(),
+                   "unexpected size detected");
+
+        // manufacturer data which does not fit into the DIB length is rejected and left out of the response
+        let oversized = Dib::ManufacturerData(ManufacturerData{manufacturer_id: 0x00FA, data: vec![0x55; 252]});
+        let mut buf = vec![];
+        assert_eq!(oversized.encode(&mut buf), Err(KnxNetIpError::InvalidSize));
+        assert!(buf.is_empty());
+        let largest = Dib::ManufacturerData(ManufacturerData{manufacturer_id: 0x00FA, data: vec![0x55; 251]});
+        largest.encode(&mut buf).unwrap();
+        assert_eq!(buf[0..4], [0xFF, 0xFE, 0x00, 0xFA]);
+        let response = Service::<()>::DescriptionResponse(DescriptionResponse{dibs: vec![oversized, Dib::SupportedServiceFamilies(ServiceFamilies{
+            families: vec![ServiceFamily{family: ServiceFamilyType::Core, version: 2}]
+        })]});
+        assert_eq!(response.encoded(), vec![0x06, 0x10, 0x02, 0x04, 0x00, 0x0A, 0x04, 0x02, 0x02, 0x02]);
+    }
+
+    #[test]
+    fn t_service_search_extended() {
+        let search_request = vec![0x06, 0x10, 0x02, 0x0B, 0x00, 0x22, 0x08, 0x01, 192, 168, 200, 12, 0xC3, 0xB4,
+                                  0x02, 0x81,
+                                  0x08, 0x82, 0x00, 0x01, 0x31, 0x3B, 0x0F, 0x22,
+                                  0x04, 0x83, 0x04, 0x02,
+                                  0x06, 0x84, 0x01, 0x02, 0x07, 0x00];
+        let request = SearchRequestExtended{
+            discovery: HPAI{
+                protocol: Protocol::Udp4Protocol,
+                port: 50100,
+                address: [192, 168, 200, 12],
+            },
+            parameters: vec![
+                Srp::SelectByProgrammingMode,
+                Srp::SelectByMacAddress([0x00, 0x01, 0x31, 0x3B, 0x0F, 0x22]),
+                Srp::SelectByService(ServiceFamily{family: ServiceFamilyType::Tunnelling, version: 2}),
+                Srp::RequestDibs(vec![DibType::DeviceInfo, DibType::SupportedServiceFamilies, DibType::TunnellingInfo]),
+            ]
+        };
+        assert_eq!(Service::<()>::decoded(&search_request), Ok(Service::SearchRequestExtended(request)));
+        match Service::<()>::decoded(&search_request) {
+            Ok(service) => assert_eq!(service.encoded(), search_request),
+            Err(e) => panic!("{}", e)
+        }
+        assert_eq!(Service::<()>::decoded(&vec![0x06, 0x10, 0x02, 0x0B, 0x00, 0x10, 0x08, 0x01, 192, 168, 200, 12, 0xC3, 0xB4, 0x02, 0x85]).unwrap_err().to_string(),
+                   "unknown search request parameter type 0x5");
+
+        let search_response = vec![0x06, 0x10, 0x02, 0x0C, 0x00, 0x16, 0x08, 0x01, 192, 168, 42, 10, 0x0E, 0x57,
+                                   0x08, 0x07, 0x00, 0xFE, 0x11, 0xF1, 0x00, 0x05];
+        assert_eq!(Service::<()>::decoded(&search_response), Ok(Service::SearchResponseExtended(SearchResponse{
+            control: HPAI{
+                protocol: Protocol::Udp4Protocol,
+                port: 3671,
+                address: [192, 168, 42, 10],
+            },
+            dibs: vec![Dib::TunnellingInfo(TunnellingInfo{
+                max_apdu_length: 254,
+                slots: vec![TunnellingSlot{address: IndividualAddress::new(1, 1, 241), usable: true, authorised: false, free: true}],
+            })],
+        })));
+    }
+
+    #[test]
+    fn t_service_routing_flow_control() {
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x05, 0x32, 0x00, 0x0C, 0x06, 0x01, 0x00, 0x64, 0x00, 0x00]),
+                   Ok(Service::RoutingBusy(RoutingBusy{device_state: 0x01, wait_time: 100, control: 0})));
+        assert_eq!(Service::<()>::RoutingBusy(RoutingBusy{device_state: 0x01, wait_time: 100, control: 0}).encoded(),
+                   vec![0x06, 0x10, 0x05, 0x32, 0x00, 0x0C, 0x06, 0x01, 0x00, 0x64, 0x00, 0x00]);
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x05, 0x31, 0x00, 0x0A, 0x04, 0x00, 0x01, 0x02]),
+                   Ok(Service::RoutingLostMessage(RoutingLostMessage{device_state: 0, lost_messages: 258})));
+        assert_eq!(Service::<()>::RoutingLostMessage(RoutingLostMessage{device_state: 0, lost_messages: 258}).encoded(),
+                   vec![0x06, 0x10, 0x05, 0x31, 0x00, 0x0A, 0x04, 0x00, 0x01, 0x02]);
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x05, 0x32, 0x00, 0x0A, 0x06, 0x01, 0x00, 0x64]).unwrap_err().to_string(),
+                   "datagram was too short (len 4)");
+    }
+
+    #[test]
+    fn t_service_device_management() {
+        let connect = ConnectRequest{
+            control: HPAI::new(Protocol::Udp4Protocol, [192, 168, 1, 10], 3671),
+            data: HPAI::new(Protocol::Udp4Protocol, [192, 168, 1, 10], 3671),
+            connection_type: ConnectionReqType::DeviceMgmtConnection,
+        };
+        let data = Service::<()>::ConnectRequest(connect).encoded();
+        assert_eq!(&data[22..], &[0x02, 0x03]);
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x12, 0x15, 0x00, 0x08, 0x01, 0xC0, 0xA8, 0x01, 0x01, 0x0E, 0x57, 0x02, 0x03]),
+                   Ok(Service::ConnectResponse(ConnectResponse{
+                       channel: 0x15,
+                       status: StatusCode::NoError,
+                       data: HPAI::new(Protocol::Udp4Protocol, [192, 168, 1, 1], 3671),
+                       connection_type: ConnectionRespType::DeviceMgmtConnection,
+                   })));
+
+        let req = Service::<()>::DeviceConfigurationRequest(DeviceConfigurationRequest{
+            channel: 0x15,
+            seq: 3,
+            data: Message::MPropReadReq(Property{object_type: 11, object_instance: 1, property_id: 76, count: 1, start_index: 1, data: vec![]}),
+        });
+        let data = vec![0x06, 0x10, 0x03, 0x10, 0x00, 0x11, 0x04, 0x15, 0x03, 0x00, 0xFC, 0x00, 0x0B, 0x01, 0x4C, 0x10, 0x01];
+        assert_eq!(req.encoded(), data);
+        assert_eq!(Service::<()>::decoded(&data), Ok(req));
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x03, 0x11, 0x00, 0x0A, 0x04, 0x15, 0x03, 0x00]),
+                   Ok(Service::DeviceConfigurationAck(DeviceConfigurationAck{channel: 0x15, seq: 3, status: StatusCode::NoError})));
+    }
+
+    #[test]
+    fn t_service_secure() {
+        let req = Service::<()>::SessionRequest(SessionRequest{
+            control: HPAI::new(Protocol::Tcp4Protocol, [0, 0, 0, 0], 0),
+            public_key: [0xAA; 32],
+        });
+        let data = req.encoded();
+        assert_eq!(&data[0..14], &[0x06, 0x10, 0x09, 0x51, 0x00, 0x2E, 0x08, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(Service::<()>::decoded(&data), Ok(req));
+
+        let res = Service::<()>::SessionResponse(SessionResponse{session_id: 1, public_key: [0xBB; 32], mac: [0xCC; 16]});
+        let data = res.encoded();
+        assert_eq!(&data[0..8], &[0x06, 0x10, 0x09, 0x52, 0x00, 0x38, 0x00, 0x01]);
+        assert_eq!(Service::<()>::decoded(&data), Ok(res));
+
+        let auth = Service::<()>::SessionAuthenticate(SessionAuthenticate{user_id: 2, mac: [0xCC; 16]});
+        let data = auth.encoded();
+        assert_eq!(&data[0..8], &[0x06, 0x10, 0x09, 0x53, 0x00, 0x18, 0x00, 0x02]);
+        assert_eq!(Service::<()>::decoded(&data), Ok(auth));
+
+        let data = vec![0x06, 0x10, 0x09, 0x54, 0x00, 0x08, 0x01, 0x00];
+        assert_eq!(Service::<()>::decoded(&data), Ok(Service::SessionStatus(SessionStatus{status: SessionStatusCode::AuthenticationFailed})));
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x09, 0x54, 0x00, 0x08, 0x09, 0x00]), Err(KnxNetIpError::UnknownStatus(0x09)));
+
+        let wrapper = Service::<()>::SecureWrapper(SecureWrapper{
+            session_id: 1,
+            sequence: 0x0102030405,
+            serial_number: [0x00, 0xFA, 0x12, 0x34, 0x56, 0x78],
+            message_tag: 0,
+            data: vec![0x11; 8],
+            mac: [0xCC; 16],
+        });
+        let data = wrapper.encoded();
+        assert_eq!(&data[0..22], &[0x06, 0x10, 0x09, 0x50, 0x00, 0x2E, 0x00, 0x01, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05,
+            0x00, 0xFA, 0x12, 0x34, 0x56, 0x78, 0x00, 0x00]);
+        assert_eq!(Service::<()>::decoded(&data), Ok(wrapper));
+        let mut short = data[0..30].to_vec();
+        short[5] = 30;
+        assert_eq!(Service::<()>::decoded(&short), Err(KnxNetIpError::MessageTooShort(24)));
+
+        let notify = Service::<()>::TimerNotify(TimerNotify{
+            timer: 0x0102030405,
+            serial_number: [0x00, 0xFA, 0x12, 0x34, 0x56, 0x78],
+            message_tag: 0xABCD,
+            mac: [0xCC; 16],
+        });
+        let data = notify.encoded();
+        assert_eq!(&data[0..20], &[0x06, 0x10, 0x09, 0x55, 0x00, 0x24, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05,
+            0x00, 0xFA, 0x12, 0x34, 0x56, 0x78, 0xAB, 0xCD]);
+        assert_eq!(Service::<()>::decoded(&data), Ok(notify));
+    }
+
+    #[test]
+    fn t_service_tunnelling_feature() {
+        let get = Service::<()>::TunnellingFeatureGet(TunnellingFeature{channel: 0x15, seq: 2, feature: 0x06, return_code: 0, data: vec![]});
+        let data = [0x06, 0x10, 0x04, 0x22, 0x00, 0x0C, 0x04, 0x15, 0x02, 0x00, 0x06, 0x00];
+        assert_eq!(get.encoded(), data);
+        assert_eq!(Service::<()>::decoded(&data), Ok(get));
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x04, 0x23, 0x00, 0x0E, 0x04, 0x15, 0x02, 0x00, 0x06, 0x00, 0x11, 0x0A]),
+                   Ok(Service::TunnellingFeatureResponse(TunnellingFeature{channel: 0x15, seq: 2, feature: 0x06, return_code: 0, data: vec![0x11, 0x0A]})));
+        assert_eq!(Service::<()>::TunnellingFeatureSet(TunnellingFeature{channel: 0x15, seq: 3, feature: 0x08, return_code: 0, data: vec![0x01]}).encoded(),
+                   [0x06, 0x10, 0x04, 0x24, 0x00, 0x0D, 0x04, 0x15, 0x03, 0x00, 0x08, 0x00, 0x01]);
+        assert_eq!(Service::<()>::decoded(&[0x06, 0x10, 0x04, 0x25, 0x00, 0x0A, 0x04, 0x15, 0x03, 0x00]), Err(KnxNetIpError::MessageTooShort(4)));
+    }
+
+    #[test]
+    fn t_service_connect_extended_cri() {
+        let data = [0x06, 0x10, 0x02, 0x05, 0x00, 0x1C, 0x08, 0x01, 192, 168, 200, 12, 0xC3, 0xB4, 0x08, 0x01, 192, 168, 200, 20, 0xC3, 0xB4,
+            0x06, 0x04, 0x02, 0x00, 0x11, 0x14];
+        let req = Service::<()>::ConnectRequest(ConnectRequest{
+            control: HPAI::new(Protocol::Udp4Protocol, [192, 168, 200, 12], 50100),
+            data: HPAI::new(Protocol::Udp4Protocol, [192, 168, 200, 20], 50100),
+            connection_type: ConnectionReqType::TunnelConnection {
+                layer: TunnelingLayer::TunnelLinkLayer,
+                address: Some(IndividualAddress::new(1, 1, 20)),
+            },
+        });
+        assert_eq!(req.encoded(), data);
+        assert_eq!(Service::<()>::decoded(&data), Ok(req));
+        let mut truncated = data[0..26].to_vec();
+        truncated[5] = 0x1A;
+        truncated[22] = 0x06;
+        assert_eq!(Service::<()>::decoded(&truncated), Err(KnxNetIpError::InvalidSize));
+    }
+
+    #[test]
+    fn t_stream_reassembler() {
+        let mut stream = StreamReassembler::new();
+        assert_eq!(stream.next_frame(), Ok(None));
+        // two frames split at arbitrary positions
+        stream.push(&[0x06, 0x10, 0x04, 0x21, 0x00]);
+        assert_eq!(stream.next_frame(), Ok(None));
+        stream.push(&[0x0A, 0x04, 0x15, 0x00, 0x00, 0x06, 0x10, 0x02]);
+        assert_eq!(stream.next_frame(), Ok(Some(vec![0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00])));
+        assert_eq!(stream.next_frame(), Ok(None));
+        assert_eq!(stream.pending(), 3);
+        stream.push(&[0x0A, 0x00, 0x08, 0x15, 0x00]);
+        assert_eq!(stream.next_frame(), Ok(Some(vec![0x06, 0x10, 0x02, 0x0A, 0x00, 0x08, 0x15, 0x00])));
+        assert_eq!(stream.pending(), 0);
+
+        stream.push(&[0x06, 0x20, 0x02, 0x0A, 0x00, 0x08]);
+        assert_eq!(stream.next_frame(), Err(KnxNetIpError::UnknownVersion(0x20)));
+        stream.clear();
+        stream.push(&[0x06, 0x10, 0x02, 0x0A, 0x00, 0x02]);
+        assert!(stream.next_frame().is_err());
+    }
 }
@@ -0,0 +1,92 @@
+use byteorder::{BigEndian, ByteOrder};
+use crate::cemi;
+use crate::cemi::Message;
+use crate::dpt::DPT;
+use crate::knxnet::KnxNetIpError;
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct RoutingIndication<D: DPT+Default> {
+    pub(crate) data: cemi::Message<D>,
+}
+
+impl<D:DPT+Default> RoutingIndication<D> {
+    pub(crate) fn payload_length(&self) -> u16 {
+        self.data.length()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+        self.data.encode(buf);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 2 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.data = Message::decode(buf)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct RoutingLostMessage {
+    pub(crate) device_state: u8,
+    pub(crate) lost_messages: u16,
+}
+
+impl RoutingLostMessage {
+    pub(crate) fn payload_length(&self) -> u16 {
+        4
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+        buf.push(0x04); // structure length
+        buf.push(self.device_state);
+        buf.extend(self.lost_messages.to_be_bytes());
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 4 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        if buf[0] != 0x04 {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.device_state = buf[1];
+        self.lost_messages = BigEndian::read_u16(&buf[2..4]);
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct RoutingBusy {
+    pub(crate) device_state: u8,
+    /// time in ms the sender asks to pause sending
+    pub(crate) wait_time: u16,
+    pub(crate) control: u16,
+}
+
+impl RoutingBusy {
+    pub(crate) fn payload_length(&self) -> u16 {
+        6
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+        buf.push(0x06); // structure length
+        buf.push(self.device_state);
+        buf.extend(self.wait_time.to_be_bytes());
+        buf.extend(self.control.to_be_bytes());
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 6 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        if buf[0] != 0x06 {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.device_state = buf[1];
+        self.wait_time = BigEndian::read_u16(&buf[2..4]);
+        self.control = BigEndian::read_u16(&buf[4..6]);
+        Ok(())
+    }
+}
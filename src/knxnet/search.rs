@@ -0,0 +1,85 @@
+use crate::knxnet::dib::Dib;
+use crate::knxnet::hpai::HPAI;
+use crate::knxnet::KnxNetIpError;
+use crate::knxnet::srp::Srp;
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct SearchRequest {
+    pub(crate) discovery: HPAI,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct SearchRequestExtended {
+    pub(crate) discovery: HPAI,
+    pub(crate) parameters: Vec<Srp>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct SearchResponse {
+    pub(crate) control: HPAI,
+    pub(crate) dibs: Vec<Dib>,
+}
+
+impl SearchRequest {
+    pub(crate) fn payload_length(&self) -> u16 {
+        HPAI::length()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+        self.discovery.encode(buf);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < HPAI::length() as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.discovery = HPAI::decode(&buf[0..8])?;
+        Ok(())
+    }
+}
+
+impl SearchRequestExtended {
+    pub(crate) fn payload_length(&self) -> u16 {
+        HPAI::length() + self.parameters.iter().map(|p| p.length()).sum::<u16>()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+        self.discovery.encode(buf);
+        self.parameters.iter().for_each(|p| p.encode(buf));
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < HPAI::length() as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.discovery = HPAI::decode(&buf[0..8])?;
+        self.parameters.clear();
+        let mut offset = 8;
+        while offset < buf.len() {
+            self.parameters.push(Srp::decode(&buf[offset..])?);
+            offset += buf[offset] as usize;
+        }
+        Ok(())
+    }
+}
+
+impl SearchResponse {
+    pub(crate) fn payload_length(&self) -> u16 {
+        HPAI::length() + self.dibs.iter().filter(|d| d.check().is_ok()).map(|d| d.length()).sum::<u16>()
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>){
+        self.control.encode(buf);
+        // oversized DIBs are left out
+        self.dibs.iter().for_each(|d| { let _ = d.encode(buf); });
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < HPAI::length() as usize + 2 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.control = HPAI::decode(&buf[0..8])?;
+        self.dibs = Dib::decode_all(&buf[8..])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use byteorder::{BigEndian, ByteOrder};
+use strum_macros::FromRepr;
+use crate::knxnet::hpai::HPAI;
+use crate::knxnet::KnxNetIpError;
+
+// Secure services of KNX IP Secure (See 3/8/9)
+
+pub(crate) const MAC_LENGTH: usize = 16;
+pub(crate) const PUBLIC_KEY_LENGTH: usize = 32;
+
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum SessionStatusCode {
+    #[default]
+    AuthenticationSuccess = 0x00,
+    AuthenticationFailed = 0x01,
+    Unauthenticated = 0x02,
+    Timeout = 0x03,
+    KeepAlive = 0x04,
+    Close = 0x05,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct SessionRequest {
+    pub(crate) control: HPAI,
+    pub(crate) public_key: [u8; PUBLIC_KEY_LENGTH],
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct SessionResponse {
+    pub(crate) session_id: u16,
+    pub(crate) public_key: [u8; PUBLIC_KEY_LENGTH],
+    pub(crate) mac: [u8; MAC_LENGTH],
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct SessionAuthenticate {
+    pub(crate) user_id: u8,
+    pub(crate) mac: [u8; MAC_LENGTH],
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct SessionStatus {
+    pub(crate) status: SessionStatusCode,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct SecureWrapper {
+    pub(crate) session_id: u16,
+    // 48 bit sequence number for unicast, timer value for multicast
+    pub(crate) sequence: u64,
+    pub(crate) serial_number: [u8; 6],
+    pub(crate) message_tag: u16,
+    // encrypted KNXnet/IP frame
+    pub(crate) data: Vec<u8>,
+    pub(crate) mac: [u8; MAC_LENGTH],
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct TimerNotify {
+    // 48 bit multicast timer of the sender
+    pub(crate) timer: u64,
+    pub(crate) serial_number: [u8; 6],
+    pub(crate) message_tag: u16,
+    pub(crate) mac: [u8; MAC_LENGTH],
+}
+
+impl SessionRequest {
+    pub(crate) fn payload_length(&self) -> u16 {
+        HPAI::length() + PUBLIC_KEY_LENGTH as u16
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        self.control.encode(buf);
+        buf.extend_from_slice(&self.public_key);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < self.payload_length() as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.control = HPAI::decode(&buf[0..8])?;
+        self.public_key.copy_from_slice(&buf[8..40]);
+        Ok(())
+    }
+}
+
+impl SessionResponse {
+    pub(crate) fn payload_length(&self) -> u16 {
+        (2 + PUBLIC_KEY_LENGTH + MAC_LENGTH) as u16
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.session_id.to_be_bytes());
+        buf.extend_from_slice(&self.public_key);
+        buf.extend_from_slice(&self.mac);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < self.payload_length() as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.session_id = BigEndian::read_u16(&buf[0..2]);
+        self.public_key.copy_from_slice(&buf[2..34]);
+        self.mac.copy_from_slice(&buf[34..50]);
+        Ok(())
+    }
+}
+
+impl SessionAuthenticate {
+    pub(crate) fn payload_length(&self) -> u16 {
+        (2 + MAC_LENGTH) as u16
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(0x00); // reserved
+        buf.push(self.user_id);
+        buf.extend_from_slice(&self.mac);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < self.payload_length() as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.user_id = buf[1];
+        self.mac.copy_from_slice(&buf[2..18]);
+        Ok(())
+    }
+}
+
+impl SessionStatus {
+    pub(crate) fn payload_length(&self) -> u16 {
+        2
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.status as u8);
+        buf.push(0x00); // reserved
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 2 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.status = match SessionStatusCode::from_repr(buf[0]) {
+            Some(status) => status,
+            None => return Err(KnxNetIpError::UnknownStatus(buf[0]))
+        };
+        Ok(())
+    }
+}
+
+impl SecureWrapper {
+    pub(crate) fn payload_length(&self) -> u16 {
+        (16 + self.data.len() + MAC_LENGTH) as u16
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.session_id.to_be_bytes());
+        buf.extend_from_slice(&self.sequence.to_be_bytes()[2..]);
+        buf.extend_from_slice(&self.serial_number);
+        buf.extend(self.message_tag.to_be_bytes());
+        buf.extend_from_slice(&self.data);
+        buf.extend_from_slice(&self.mac);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        // the wrapped frame contains at least a KNXnet/IP header
+        if buf.len() < 16 + 6 + MAC_LENGTH {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.session_id = BigEndian::read_u16(&buf[0..2]);
+        self.sequence = BigEndian::read_u48(&buf[2..8]);
+        self.serial_number.copy_from_slice(&buf[8..14]);
+        self.message_tag = BigEndian::read_u16(&buf[14..16]);
+        self.data = buf[16..buf.len() - MAC_LENGTH].to_vec();
+        self.mac.copy_from_slice(&buf[buf.len() - MAC_LENGTH..]);
+        Ok(())
+    }
+}
+
+impl TimerNotify {
+    pub(crate) fn payload_length(&self) -> u16 {
+        (14 + MAC_LENGTH) as u16
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.timer.to_be_bytes()[2..]);
+        buf.extend_from_slice(&self.serial_number);
+        buf.extend(self.message_tag.to_be_bytes());
+        buf.extend_from_slice(&self.mac);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < self.payload_length() as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.timer = BigEndian::read_u48(&buf[0..6]);
+        self.serial_number.copy_from_slice(&buf[6..12]);
+        self.message_tag = BigEndian::read_u16(&buf[12..14]);
+        self.mac.copy_from_slice(&buf[14..30]);
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use strum_macros::FromRepr;
+use crate::knxnet::dib::{DibType, ServiceFamily, ServiceFamilyType};
+use crate::knxnet::KnxNetIpError;
+
+// Search Request Parameters of an extended search request (See 3/8/2 7.6.3)
+
+const MANDATORY_FLAG: u8 = 0x80;
+
+#[derive(FromRepr, Debug, Clone, PartialEq)]
+#[repr(u8)]
+pub enum Srp {
+    /// Only servers which are in programming mode shall respond
+    SelectByProgrammingMode = 0x01,
+    /// Only the server with the given MAC address shall respond
+    SelectByMacAddress([u8; 6]) = 0x02,
+    /// Only servers supporting the service family in at least the given version shall respond
+    SelectByService(ServiceFamily) = 0x03,
+    /// Servers shall add the given DIBs to their response
+    RequestDibs(Vec<DibType>) = 0x04,
+}
+
+impl Srp {
+    pub(crate) fn length(&self) -> u16 {
+        match self {
+            Self::SelectByProgrammingMode => 2,
+            Self::SelectByMacAddress(_) => 8,
+            Self::SelectByService(_) => 4,
+            // padded to an even length
+            Self::RequestDibs(dibs) => 2 + ((dibs.len() as u16 + 1) & !1),
+        }
+    }
+
+    fn identifier(&self) -> u8 {
+        // SAFETY: Because `Self` is marked `repr(u8)`, its layout is a `repr(C)` `union`
+        // between `repr(C)` structs, each of which has the `u8` discriminant as its first
+        // field, so we can read the discriminant without offsetting the pointer.
+        unsafe { *<*const _>::from(self).cast::<u8>() }
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.length() as u8);
+        // all parameters need to be understood by the server, else it must not respond
+        buf.push(MANDATORY_FLAG | self.identifier());
+        match self {
+            Self::SelectByProgrammingMode => {}
+            Self::SelectByMacAddress(mac) => buf.extend_from_slice(mac),
+            Self::SelectByService(service) => {
+                buf.push(service.family.to_u8());
+                buf.push(service.version);
+            }
+            Self::RequestDibs(dibs) => {
+                dibs.iter().for_each(|d| buf.push(*d as u8));
+                if dibs.len() & 1 != 0 {
+                    buf.push(0x00);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn decode(buf: &[u8]) -> Result<Srp, KnxNetIpError> {
+        if buf.len() < 2 || buf[0] < 2 || buf.len() < buf[0] as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        let payload = &buf[2..buf[0] as usize];
+        let mut srp = match Srp::from_repr(buf[1] & !MANDATORY_FLAG) {
+            Some(srp) => srp,
+            None => return Err(KnxNetIpError::UnknownSrp(buf[1] & !MANDATORY_FLAG))
+        };
+        match srp {
+            Self::SelectByProgrammingMode => {}
+            Self::SelectByMacAddress(ref mut mac) => {
+                if payload.len() != 6 {
+                    return Err(KnxNetIpError::InvalidSize)
+                }
+                mac.copy_from_slice(payload);
+            }
+            Self::SelectByService(ref mut service) => {
+                if payload.len() != 2 {
+                    return Err(KnxNetIpError::InvalidSize)
+                }
+                service.family = ServiceFamilyType::from_u8(payload[0]);
+                service.version = payload[1];
+            }
+            Self::RequestDibs(ref mut dibs) => {
+                // unknown DIB types (including the padding) are skipped
+                *dibs = payload.iter().filter_map(|d| DibType::from_repr(*d)).collect();
+            }
+        }
+        Ok(srp)
+    }
+}
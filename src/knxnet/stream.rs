@@ -0,0 +1,56 @@
+use byteorder::{BigEndian, ByteOrder};
+use crate::knxnet::{HEADER_LENGTH, KNXNET_VERSION, KnxNetIpError};
+
+// Over TCP frames are only delimited by the total length of the KNXnet/IP header
+
+/// Splits the byte stream of a KNXnet/IP TCP connection into frames.
+///
+/// Received data is appended with `push` and complete frames are taken with `next_frame`, which can then be passed to
+/// `handle_inbound_message` of a connection.
+#[derive(Debug, Default)]
+pub struct StreamReassembler {
+    buf: Vec<u8>,
+}
+
+impl StreamReassembler {
+    pub fn new() -> StreamReassembler {
+        StreamReassembler::default()
+    }
+
+    /// Append data read from the stream
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Take the next complete frame. An error means the stream lost synchronization and needs to be closed.
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, KnxNetIpError> {
+        if self.buf.len() < HEADER_LENGTH as usize {
+            return Ok(None)
+        }
+        if self.buf[0] != HEADER_LENGTH {
+            return Err(KnxNetIpError::UnknownHeaderSize(self.buf[0]))
+        }
+        if self.buf[1] != KNXNET_VERSION {
+            return Err(KnxNetIpError::UnknownVersion(self.buf[1]))
+        }
+        let total_size = BigEndian::read_u16(&self.buf[4..6]) as usize;
+        if total_size < HEADER_LENGTH as usize {
+            return Err(KnxNetIpError::InvalidHeaderSize(total_size as u16, HEADER_LENGTH as usize))
+        }
+        if self.buf.len() < total_size {
+            return Ok(None)
+        }
+        let rest = self.buf.split_off(total_size);
+        Ok(Some(std::mem::replace(&mut self.buf, rest)))
+    }
+
+    /// Number of buffered bytes which do not form a complete frame yet
+    pub fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Drop buffered data, e.g. after the stream was closed
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+}
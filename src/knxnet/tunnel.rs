@@ -20,7 +20,7 @@ pub(crate) struct TunnelAck{
 
 impl<D:DPT+Default> TunnelRequest<D> {
     pub(crate) fn payload_length(&self)->u16 {
-        return (4 + self.data.length()) as u16;
+        return 4 + self.data.length();
     }
 
     pub(crate) fn encode(&self, buf: &mut Vec<u8>){
@@ -67,4 +67,42 @@ impl TunnelAck {
         };
         return Ok(());
     }
-}
\ No newline at end of file
+}
+/// Payload of the tunnelling feature services (get, response, set and info)
+#[derive(Debug, PartialEq, Default)]
+pub(crate) struct TunnellingFeature {
+    pub(crate) channel: u8,
+    pub(crate) seq: u8,
+    pub(crate) feature: u8,
+    // only used by responses, reserved otherwise
+    pub(crate) return_code: u8,
+    pub(crate) data: Vec<u8>,
+}
+
+impl TunnellingFeature {
+    pub(crate) fn payload_length(&self) -> u16 {
+        (6 + self.data.len()) as u16
+    }
+
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(0x4);
+        buf.push(self.channel);
+        buf.push(self.seq);
+        buf.push(0x00); // reserved
+        buf.push(self.feature);
+        buf.push(self.return_code);
+        buf.extend_from_slice(&self.data);
+    }
+
+    pub(crate) fn decode(&mut self, buf: &[u8]) -> Result<(), KnxNetIpError> {
+        if buf.len() < 6 {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        self.channel = buf[1];
+        self.seq = buf[2];
+        self.feature = buf[4];
+        self.return_code = buf[5];
+        self.data = buf[6..].to_vec();
+        Ok(())
+    }
+}
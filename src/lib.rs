@@ -3,7 +3,25 @@
 
 pub mod group_event;
 pub mod tunnel_connection;
-mod knxnet;
+pub mod tunnelling_server;
+pub mod discovery;
+pub mod routing_connection;
+pub mod failover_connection;
+pub mod device_management_connection;
+pub mod property_event;
+pub mod feature_event;
+pub mod busmonitor_event;
+pub mod raw_event;
+pub mod delivery_event;
+pub mod knxnet;
+pub mod tp1;
+pub mod ft12;
+pub mod emi;
+pub mod usb;
+#[cfg(feature = "secure")]
+pub mod secure;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod cemi;
 pub mod address;
 pub mod dpt;
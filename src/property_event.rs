@@ -0,0 +1,144 @@
+use strum_macros::FromRepr;
+use crate::cemi::Message;
+use crate::dpt::DPT;
+
+/// Interface object type of the device object
+pub const OBJECT_TYPE_DEVICE: u16 = 0;
+/// Interface object type of the cEMI server object
+pub const OBJECT_TYPE_CEMI_SERVER: u16 = 8;
+/// Interface object type of the KNXnet/IP parameter object
+pub const OBJECT_TYPE_KNXNET_IP_PARAMETER: u16 = 11;
+/// Interface object type of the security object
+pub const OBJECT_TYPE_SECURITY: u16 = 17;
+/// Communication mode of the cEMI server object, e.g. data link layer (0x00), busmonitor (0x01) or raw (0x02)
+pub const PID_COMM_MODE: u8 = 52;
+pub const PID_PROJECT_INSTALLATION_ID: u8 = 51;
+pub const PID_KNX_INDIVIDUAL_ADDRESS: u8 = 52;
+pub const PID_ADDITIONAL_INDIVIDUAL_ADDRESSES: u8 = 53;
+pub const PID_CURRENT_IP_ADDRESS: u8 = 57;
+pub const PID_CURRENT_SUBNET_MASK: u8 = 58;
+pub const PID_CURRENT_DEFAULT_GATEWAY: u8 = 59;
+pub const PID_IP_ADDRESS: u8 = 60;
+pub const PID_SUBNET_MASK: u8 = 61;
+pub const PID_DEFAULT_GATEWAY: u8 = 62;
+pub const PID_MAC_ADDRESS: u8 = 64;
+pub const PID_FRIENDLY_NAME: u8 = 76;
+/// Function property of the security object switching the security mode on or off
+pub const PID_SECURITY_MODE: u8 = 51;
+
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq)]
+#[repr(u8)]
+pub enum PropertyEventType {
+    /// Confirmation of a property read
+    ReadResponse,
+    /// Confirmation of a property write
+    WriteResponse,
+    /// Property value changed on the server
+    Info,
+    /// Confirmation of a function property command or state read, `data` starts with the return code
+    FunctionResponse,
+    /// The server was reset, all other fields are empty
+    Reset,
+    /// The server rejected the request in its acknowledgement, returned by `get_failed_request`
+    Rejected,
+    /// The server did not confirm the request in time, returned by `get_failed_request`
+    Timeout,
+}
+
+/// Error codes of negative property confirmations (See 3/6/3 4.1.7.3.7.2)
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Default)]
+#[repr(u8)]
+pub enum PropertyError {
+    #[default]
+    Unspecified = 0x00,
+    OutOfRange = 0x01,
+    OutOfMaxRange = 0x02,
+    OutOfMinRange = 0x03,
+    Memory = 0x04,
+    ReadOnly = 0x05,
+    IllegalCommand = 0x06,
+    VoidDp = 0x07,
+    TypeConflict = 0x08,
+    PropIndexRangeError = 0x09,
+    ValueNotWriteableNow = 0x0A,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyEvent {
+    pub event_type: PropertyEventType,
+    pub object_type: u16,
+    pub object_instance: u8,
+    pub property_id: u8,
+    pub start_index: u16,
+    pub count: u8,
+    pub data: Vec<u8>,
+    /// Set if the server rejected the request, `data` is empty in that case
+    pub error: Option<PropertyError>,
+}
+
+impl PropertyEvent {
+    pub(crate) fn from_message<D: DPT+Default>(msg: Message<D>) -> Option<PropertyEvent> {
+        let (event_type, property) = match msg {
+            Message::MPropReadCon(p) => (PropertyEventType::ReadResponse, p),
+            Message::MPropWriteCon(p) => (PropertyEventType::WriteResponse, p),
+            Message::MPropInfoInd(p) => (PropertyEventType::Info, p),
+            Message::MFuncPropCon(p) => {
+                return Some(PropertyEvent{
+                    event_type: PropertyEventType::FunctionResponse,
+                    object_type: p.object_type,
+                    object_instance: p.object_instance,
+                    property_id: p.property_id,
+                    start_index: 0,
+                    count: 0,
+                    // a negative confirmation has no return code
+                    error: if p.data.is_empty() { Some(PropertyError::Unspecified) } else { None },
+                    data: p.data,
+                })
+            }
+            Message::MResetInd => {
+                return Some(PropertyEvent{
+                    event_type: PropertyEventType::Reset,
+                    object_type: 0,
+                    object_instance: 0,
+                    property_id: 0,
+                    start_index: 0,
+                    count: 0,
+                    data: vec![],
+                    error: None,
+                })
+            }
+            _ => return None
+        };
+        let error = property.error().map(|e| PropertyError::from_repr(e).unwrap_or_default());
+        Some(PropertyEvent{
+            event_type,
+            object_type: property.object_type,
+            object_instance: property.object_instance,
+            property_id: property.property_id,
+            start_index: property.start_index,
+            count: property.count,
+            data: if error.is_some() { vec![] } else { property.data },
+            error,
+        })
+    }
+
+    // identifies a request which failed without a confirmation
+    pub(crate) fn from_request<D: DPT+Default>(event_type: PropertyEventType, msg: Message<D>) -> Option<PropertyEvent> {
+        let (object_type, object_instance, property_id, start_index, count) = match msg {
+            Message::MPropReadReq(p) | Message::MPropWriteReq(p) => (p.object_type, p.object_instance, p.property_id, p.start_index, p.count),
+            Message::MFuncPropCommandReq(p) | Message::MFuncPropStateReadReq(p) => (p.object_type, p.object_instance, p.property_id, 0, 0),
+            Message::MResetReq => (0, 0, 0, 0, 0),
+            _ => return None
+        };
+        Some(PropertyEvent{
+            event_type,
+            object_type,
+            object_instance,
+            property_id,
+            start_index,
+            count,
+            data: vec![],
+            error: None,
+        })
+    }
+}
@@ -0,0 +1,15 @@
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RawEventType {
+    /// Frame received from the bus
+    Indication,
+    /// Confirmation of a frame sent with `send_raw`
+    Confirmation,
+}
+
+/// Frame received in raw mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawEvent {
+    pub event_type: RawEventType,
+    /// Raw TP1 frame bytes, unchecked
+    pub frame: Vec<u8>,
+}
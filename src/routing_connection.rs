@@ -0,0 +1,319 @@
+//runtime facing functions:
+
+// get data to be transmitted next (to the KNX multicast address)
+// get next time event
+// handle next time event
+// handle received data -> returns a group event or none
+// send data
+
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::ops::Add;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::address::IndividualAddress;
+use crate::cemi::l_data::{Acknowledge, LData, SystemBroadcast};
+use crate::cemi::Message;
+use crate::dpt::DPT;
+use crate::group_event::GroupEvent;
+use crate::knxnet::routing::{RoutingBusy, RoutingIndication};
+use crate::knxnet::{KnxNetIpError, Service};
+
+// routing has no connection, so there is nothing to be done regularly
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+// flow control timings (See 3/8/5 2.3.5)
+const BUSY_SEPARATION: Duration = Duration::from_millis(10);
+const BUSY_RANDOM_WAIT: Duration = Duration::from_millis(50);
+const BUSY_SLOW_DURATION: Duration = Duration::from_millis(100);
+const BUSY_DECREMENT_INTERVAL: Duration = Duration::from_millis(5);
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RoutingConnectionConfig {
+    max_indications_per_second: usize,
+}
+
+impl Default for RoutingConnectionConfig {
+    fn default() -> RoutingConnectionConfig {
+        RoutingConnectionConfig{
+            max_indications_per_second: 50,
+        }
+    }
+}
+
+/// Connectionless KNXnet/IP routing over the KNX multicast address 224.0.23.12:3671
+#[derive(Debug)]
+pub struct RoutingConnection {
+    address: IndividualAddress,
+    out_queue: VecDeque<Vec<u8>>,
+    current: Vec<u8>,
+    sent: VecDeque<Instant>,
+    busy_counter: u32,
+    last_busy: Option<Instant>,
+    paused_until: Instant,
+    next_busy_decrement: Instant,
+    lost_messages: u64,
+    random_state: u64,
+    config: RoutingConnectionConfig,
+}
+
+impl RoutingConnection {
+    /// Create a routing connection sending with the given individual address as source
+    pub fn new(address: IndividualAddress, config: RoutingConnectionConfig) -> RoutingConnection {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        RoutingConnection{
+            address,
+            out_queue: VecDeque::new(),
+            current: vec![],
+            sent: VecDeque::new(),
+            busy_counter: 0,
+            last_busy: None,
+            paused_until: Instant::now(),
+            next_busy_decrement: Instant::now(),
+            lost_messages: 0,
+            // xorshift must not be seeded with 0
+            random_state: (seed ^ ((address.to_u16() as u64) << 32)) | 1,
+            config,
+        }
+    }
+
+    /// Send a group event, returns `InvalidSize` if its APDU does not fit into an extended frame
+    pub fn send<T: DPT+Default>(&mut self, ev: GroupEvent<T>) -> Result<(), KnxNetIpError> {
+        self.send_l_data(ev.into_l_data())
+    }
+
+    pub(crate) fn send_l_data<T: DPT+Default>(&mut self, mut l_data: LData<T>) -> Result<(), KnxNetIpError> {
+        if !l_data.valid_length() {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        l_data.source = self.address.to_u16();
+        // there is no link layer acknowledge on the IP backbone
+        l_data.acknowledge = Acknowledge::NoAcknowledge;
+        let system_broadcast = l_data.system_broadcast;
+        let data = RoutingIndication{
+            data: Message::<T>::LDataInd(vec![], l_data),
+        };
+        // system broadcast frames must only be forwarded to system broadcast aware devices (See 3/8/5 2.6)
+        let service = match system_broadcast {
+            SystemBroadcast::SystemBroadcast => Service::RoutingSystemBroadcast(data),
+            SystemBroadcast::Broadcast => Service::RoutingIndication(data),
+        };
+        self.out_queue.push_back(service.encoded());
+        Ok(())
+    }
+
+    /// Removes the frames not yet sent, e.g. to send them through another connection
+    pub(crate) fn take_pending_l_data(&mut self) -> Vec<LData<Vec<u8>>> {
+        self.out_queue.drain(..).filter_map(|data| match Service::<Vec<u8>>::decoded(&data) {
+            Ok(Service::RoutingIndication(ind)) | Ok(Service::RoutingSystemBroadcast(ind)) => match ind.data {
+                Message::LDataInd(_, l_data) => Some(l_data),
+                _ => None
+            },
+            _ => None
+        }).collect()
+    }
+
+    /// Data which needs to be sent to `KNX_MULTICAST_ADDRESS`:`KNX_PORT`
+    pub fn get_outbound_data(&mut self) -> Option<&[u8]> {
+        let now = Instant::now();
+        if self.out_queue.is_empty() || now < self.paused_until {
+            return None
+        }
+        while self.sent.front().is_some_and(|t| *t + RATE_WINDOW <= now) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.config.max_indications_per_second {
+            return None
+        }
+        self.sent.push_back(now);
+        self.current = self.out_queue.pop_front()?;
+        Some(&self.current)
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        let mut next = Instant::now().add(IDLE_INTERVAL);
+        if self.busy_counter > 0 {
+            next = min(next, self.next_busy_decrement);
+        }
+        if !self.out_queue.is_empty() {
+            next = min(next, self.paused_until);
+            if self.sent.len() >= self.config.max_indications_per_second {
+                next = min(next, max(self.paused_until, self.sent[0] + RATE_WINDOW));
+            }
+        }
+        next
+    }
+
+    pub fn handle_time_events(&mut self) {
+        let now = Instant::now();
+        while self.busy_counter > 0 && self.next_busy_decrement <= now {
+            self.busy_counter -= 1;
+            self.next_busy_decrement += BUSY_DECREMENT_INTERVAL;
+        }
+    }
+
+    /// Whether sending is paused because a router signalled an overflow
+    pub fn busy(&self) -> bool {
+        Instant::now() < self.paused_until
+    }
+
+    /// Number of messages routers reported to be lost since creation
+    pub fn lost_messages(&self) -> u64 {
+        self.lost_messages
+    }
+
+    pub fn handle_inbound_message(&mut self, data: &[u8]) -> Option<GroupEvent<Vec<u8>>> {
+        match Service::<Vec<u8>>::decoded(data) {
+            Ok(Service::RoutingIndication(ind)) | Ok(Service::RoutingSystemBroadcast(ind)) => match ind.data {
+                // multicast loopback delivers our own frames as well
+                Message::LDataInd(_, d) if d.source != self.address.to_u16() => GroupEvent::from_l_data(d),
+                _ => None
+            },
+            Ok(Service::RoutingBusy(busy)) => {
+                self.handle_routing_busy(busy);
+                None
+            }
+            Ok(Service::RoutingLostMessage(lost)) => {
+                self.lost_messages += lost.lost_messages as u64;
+                None
+            }
+            _ => None
+        }
+    }
+
+    fn handle_routing_busy(&mut self, busy: RoutingBusy) {
+        // a control field other than 0 addresses only specific devices
+        if busy.control != 0 {
+            return
+        }
+        let now = Instant::now();
+        // busy frames within a short time are most likely caused by the same overflow
+        let separated = match self.last_busy {
+            Some(t) => now.duration_since(t) > BUSY_SEPARATION,
+            None => true
+        };
+        if separated {
+            self.busy_counter += 1;
+        }
+        self.last_busy = Some(now);
+        let random_wait = BUSY_RANDOM_WAIT.mul_f64(self.random() * self.busy_counter as f64);
+        self.paused_until = max(self.paused_until, now + Duration::from_millis(busy.wait_time as u64) + random_wait);
+        self.next_busy_decrement = now + BUSY_SLOW_DURATION * self.busy_counter;
+    }
+
+    // uniform random value in [0, 1)
+    fn random(&mut self) -> f64 {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        (self.random_state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::address::IndividualAddress;
+    use crate::cemi::apdu::Apdu;
+    use crate::cemi::l_data::{AddressType, LData, SystemBroadcast};
+    use crate::group_event::{GroupEvent, GroupEventType};
+    use crate::knxnet::KnxNetIpError;
+    use crate::routing_connection::{RoutingConnection, RoutingConnectionConfig};
+
+    #[test]
+    fn t_routing_send() {
+        let mut routing = RoutingConnection::new(IndividualAddress::new(1, 1, 250), RoutingConnectionConfig::default());
+        assert_eq!(routing.get_outbound_data(), None);
+        routing.send(GroupEvent{
+            address: 10,
+            event_type: GroupEventType::GroupValueWrite,
+            data: vec![0x03, 0xD4],
+        }).unwrap();
+        assert_eq!(routing.get_outbound_data(), Some(&[0x06, 0x10, 0x05, 0x30, 0x00, 0x13, 0x29, 0x00, 0xBC, 0xE0, 0x11, 0xFA, 0x00, 0x0A, 0x03, 0x00, 0x80, 0x03, 0xD4][..]));
+        assert_eq!(routing.get_outbound_data(), None);
+        // APDUs longer than an extended frame allows are rejected
+        assert_eq!(routing.send(GroupEvent{
+            address: 10,
+            event_type: GroupEventType::GroupValueWrite,
+            data: vec![0; 300],
+        }), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(routing.get_outbound_data(), None);
+    }
+
+    #[test]
+    fn t_routing_receive() {
+        let mut routing = RoutingConnection::new(IndividualAddress::new(1, 1, 250), RoutingConnectionConfig::default());
+        let ev = routing.handle_inbound_message(&[0x06, 0x10, 0x05, 0x30, 0x00, 0x13, 0x29, 0x00, 0xBC, 0xE0, 0x11, 0x01, 0x00, 0x0A, 0x03, 0x00, 0x80, 0x03, 0xD4]).unwrap();
+        assert_eq!(ev.address, 10);
+        assert_eq!(ev.event_type, GroupEventType::GroupValueWrite);
+        assert_eq!(ev.data, vec![0x03, 0xD4]);
+        // own frames are ignored
+        assert!(routing.handle_inbound_message(&[0x06, 0x10, 0x05, 0x30, 0x00, 0x13, 0x29, 0x00, 0xBC, 0xE0, 0x11, 0xFA, 0x00, 0x0A, 0x03, 0x00, 0x80, 0x03, 0xD4]).is_none());
+    }
+
+    #[test]
+    fn t_routing_system_broadcast() {
+        let mut routing = RoutingConnection::new(IndividualAddress::new(1, 1, 250), RoutingConnectionConfig::default());
+        routing.send_l_data(LData::<()>{
+            system_broadcast: SystemBroadcast::SystemBroadcast,
+            destination_address_type: AddressType::Group,
+            destination: 0,
+            data: Apdu::GroupValueRead,
+            ..LData::default()
+        }).unwrap();
+        assert_eq!(routing.get_outbound_data(), Some(&[0x06, 0x10, 0x05, 0x33, 0x00, 0x11, 0x29, 0x00, 0xAC, 0xE0, 0x11, 0xFA, 0x00, 0x00, 0x01, 0x00, 0x00][..]));
+        // system broadcasts are received as well
+        let ev = routing.handle_inbound_message(&[0x06, 0x10, 0x05, 0x33, 0x00, 0x11, 0x29, 0x00, 0xAC, 0xE0, 0x11, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00]).unwrap();
+        assert_eq!(ev.event_type, GroupEventType::GroupValueRead);
+    }
+
+    #[test]
+    fn t_routing_flow_control() {
+        let mut routing = RoutingConnection::new(IndividualAddress::new(1, 1, 250), RoutingConnectionConfig::default());
+        routing.send(GroupEvent{
+            address: 10,
+            event_type: GroupEventType::GroupValueRead,
+            data: vec![],
+        }).unwrap();
+        // routing busy with 100ms wait time
+        let before = Instant::now();
+        routing.handle_inbound_message(&[0x06, 0x10, 0x05, 0x32, 0x00, 0x0C, 0x06, 0x01, 0x00, 0x64, 0x00, 0x00]);
+        let after = Instant::now();
+        assert!(routing.busy());
+        assert_eq!(routing.get_outbound_data(), None);
+        // the wait time is extended by a random time of up to 50ms
+        assert!(routing.paused_until >= before + Duration::from_millis(100));
+        assert!(routing.paused_until < after + Duration::from_millis(150));
+        // the busy counter is decremented after 100ms, before sending resumes
+        assert_eq!(routing.busy_counter, 1);
+        assert_eq!(routing.get_next_time_event(), routing.next_busy_decrement);
+        assert!(routing.next_busy_decrement <= routing.paused_until);
+        // a second busy frame of the same overflow only extends the wait time
+        routing.handle_inbound_message(&[0x06, 0x10, 0x05, 0x32, 0x00, 0x0C, 0x06, 0x01, 0x00, 0x64, 0x00, 0x00]);
+        assert_eq!(routing.busy_counter, 1);
+
+        std::thread::sleep(routing.paused_until - Instant::now());
+        routing.handle_time_events();
+        assert!(!routing.busy());
+        assert!(routing.get_outbound_data().is_some());
+
+        routing.handle_inbound_message(&[0x06, 0x10, 0x05, 0x31, 0x00, 0x0A, 0x04, 0x00, 0x00, 0x03]);
+        routing.handle_inbound_message(&[0x06, 0x10, 0x05, 0x31, 0x00, 0x0A, 0x04, 0x00, 0x00, 0x02]);
+        assert_eq!(routing.lost_messages(), 5);
+    }
+
+    #[test]
+    fn t_routing_rate_limit() {
+        let mut routing = RoutingConnection::new(IndividualAddress::new(1, 1, 250), RoutingConnectionConfig::default());
+        for _ in 0..51 {
+            routing.send(GroupEvent{
+                address: 10,
+                event_type: GroupEventType::GroupValueRead,
+                data: vec![],
+            }).unwrap();
+        }
+        for _ in 0..50 {
+            assert!(routing.get_outbound_data().is_some());
+        }
+        assert_eq!(routing.get_outbound_data(), None);
+    }
+}
@@ -0,0 +1,187 @@
+use aes::Aes128;
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use pbkdf2::pbkdf2_hmac;
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+use crate::knxnet::secure::{SecureWrapper, TimerNotify, MAC_LENGTH};
+use crate::knxnet::{KnxNetIpError, Service};
+
+// KNX IP Secure uses AES-128 in CCM mode with a counter block format of its own (See 3/8/9 and AN159)
+
+const USER_PASSWORD_SALT: &[u8] = b"user-password.1.secure.ip.knx.org";
+const DEVICE_AUTHENTICATION_SALT: &[u8] = b"device-authentication-code.1.secure.ip.knx.org";
+const PASSWORD_ITERATIONS: u32 = 65536;
+
+/// Counter block used to encrypt the MACs of the session handshake
+pub(crate) const COUNTER_0_HANDSHAKE: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0x00];
+
+/// CBC-MAC over the length prefixed additional data and the payload, starting with `block_0`
+pub(crate) fn cbc_mac(key: &[u8; 16], block_0: &[u8; 16], additional_data: &[u8], payload: &[u8]) -> [u8; MAC_LENGTH] {
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+    let mut data = Vec::with_capacity(18 + additional_data.len() + payload.len() + 15);
+    data.extend_from_slice(block_0);
+    data.extend((additional_data.len() as u16).to_be_bytes());
+    data.extend_from_slice(additional_data);
+    data.extend_from_slice(payload);
+    data.resize(data.len().div_ceil(16) * 16, 0);
+
+    let mut mac = [0u8; MAC_LENGTH];
+    for block in data.chunks(16) {
+        mac.iter_mut().zip(block).for_each(|(m, b)| *m ^= b);
+        let mut out = GenericArray::from(mac);
+        cipher.encrypt_block(&mut out);
+        mac = out.into();
+    }
+    mac
+}
+
+/// Encrypts or decrypts the MAC with `counter_0` and the payload with the succeeding counters
+pub(crate) fn ctr(key: &[u8; 16], counter_0: &[u8; 16], mac: &[u8; MAC_LENGTH], payload: &[u8]) -> ([u8; MAC_LENGTH], Vec<u8>) {
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+    let counter = u128::from_be_bytes(*counter_0);
+    let key_stream = |i: u128| {
+        let mut block = GenericArray::from(counter.wrapping_add(i).to_be_bytes());
+        cipher.encrypt_block(&mut block);
+        block
+    };
+    let mut mac_out = *mac;
+    mac_out.iter_mut().zip(key_stream(0)).for_each(|(m, k)| *m ^= k);
+    let mut data = Vec::with_capacity(payload.len());
+    for (i, block) in payload.chunks(16).enumerate() {
+        data.extend(block.iter().zip(key_stream(i as u128 + 1)).map(|(d, k)| d ^ k));
+    }
+    (mac_out, data)
+}
+
+/// Counter block of secure wrapper frames
+pub(crate) fn counter_0(sequence: u64, serial_number: &[u8; 6], message_tag: u16) -> [u8; 16] {
+    let mut counter = [0u8; 16];
+    counter[0..6].copy_from_slice(&sequence.to_be_bytes()[2..]);
+    counter[6..12].copy_from_slice(serial_number);
+    counter[12..14].copy_from_slice(&message_tag.to_be_bytes());
+    counter[14] = 0xFF;
+    counter
+}
+
+/// First CBC-MAC block of secure wrapper frames
+pub(crate) fn block_0(sequence: u64, serial_number: &[u8; 6], message_tag: u16, payload_length: usize) -> [u8; 16] {
+    let mut block = counter_0(sequence, serial_number, message_tag);
+    block[14..16].copy_from_slice(&(payload_length as u16).to_be_bytes());
+    block
+}
+
+/// Encrypts the frame carried as data of the wrapper and returns the encoded wrapper
+pub(crate) fn encrypt_wrapper(key: &[u8; 16], wrapper: SecureWrapper) -> Vec<u8> {
+    let mut service = Service::<()>::SecureWrapper(wrapper);
+    // the header only depends on the length, which does not change by the encryption
+    let header = service.header();
+    if let Service::SecureWrapper(ref mut w) = service {
+        let mut additional_data = header.to_vec();
+        additional_data.extend(w.session_id.to_be_bytes());
+        let mac = cbc_mac(key, &block_0(w.sequence, &w.serial_number, w.message_tag, w.data.len()), &additional_data, &w.data);
+        (w.mac, w.data) = ctr(key, &counter_0(w.sequence, &w.serial_number, w.message_tag), &mac, &w.data);
+    }
+    service.encoded()
+}
+
+/// Decrypts the frame of a received wrapper, `None` if it was not authenticated with the key
+pub(crate) fn decrypt_wrapper(key: &[u8; 16], header: &[u8], wrapper: &SecureWrapper) -> Option<Vec<u8>> {
+    let (mac, data) = ctr(key, &counter_0(wrapper.sequence, &wrapper.serial_number, wrapper.message_tag), &wrapper.mac, &wrapper.data);
+    let mut additional_data = header.to_vec();
+    additional_data.extend(wrapper.session_id.to_be_bytes());
+    if cbc_mac(key, &block_0(wrapper.sequence, &wrapper.serial_number, wrapper.message_tag, data.len()), &additional_data, &data) != mac {
+        return None
+    }
+    Some(data)
+}
+
+/// Adds the MAC to a timer notify and returns the encoded frame
+pub(crate) fn authenticate_timer_notify(key: &[u8; 16], notify: TimerNotify) -> Vec<u8> {
+    let mut service = Service::<()>::TimerNotify(notify);
+    let header = service.header();
+    if let Service::TimerNotify(ref mut n) = service {
+        n.mac = timer_notify_mac(key, &header, n);
+    }
+    service.encoded()
+}
+
+pub(crate) fn verify_timer_notify(key: &[u8; 16], header: &[u8], notify: &TimerNotify) -> bool {
+    timer_notify_mac(key, header, notify) == notify.mac
+}
+
+// timer notifications have no payload, only the header is authenticated
+fn timer_notify_mac(key: &[u8; 16], header: &[u8], notify: &TimerNotify) -> [u8; MAC_LENGTH] {
+    let mac = cbc_mac(key, &block_0(notify.timer, &notify.serial_number, notify.message_tag, 0), header, &[]);
+    ctr(key, &counter_0(notify.timer, &notify.serial_number, notify.message_tag), &mac, &[]).0
+}
+
+pub(crate) fn user_password_hash(password: &str) -> [u8; 16] {
+    password_hash(password, USER_PASSWORD_SALT)
+}
+
+pub(crate) fn device_authentication_code(password: &str) -> [u8; 16] {
+    password_hash(password, DEVICE_AUTHENTICATION_SALT)
+}
+
+fn password_hash(password: &str, salt: &[u8]) -> [u8; 16] {
+    // passwords are encoded as ISO-8859-1
+    let password: Vec<u8> = password.chars().map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' }).collect();
+    let mut key = [0u8; 16];
+    pbkdf2_hmac::<Sha256>(&password, salt, PASSWORD_ITERATIONS, &mut key);
+    key
+}
+
+pub(crate) fn random_bytes<const N: usize>() -> Result<[u8; N], KnxNetIpError> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|_| KnxNetIpError::NoRandomSource)?;
+    Ok(bytes)
+}
+
+pub(crate) fn generate_secret() -> Result<StaticSecret, KnxNetIpError> {
+    Ok(StaticSecret::from(random_bytes::<32>()?))
+}
+
+/// Session key derived from the ECDH shared secret
+pub(crate) fn session_key(secret: &StaticSecret, peer_public_key: &[u8; 32]) -> [u8; 16] {
+    let shared = secret.diffie_hellman(&PublicKey::from(*peer_public_key));
+    let mut key = [0u8; 16];
+    key.copy_from_slice(&Sha256::digest(shared.as_bytes())[..16]);
+    key
+}
+
+pub(crate) fn xor(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.iter_mut().zip(a.iter().zip(b)).for_each(|(o, (a, b))| *o = a ^ b);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use x25519_dalek::{PublicKey, StaticSecret};
+    use crate::secure::crypto::{block_0, cbc_mac, counter_0, ctr, device_authentication_code, session_key, user_password_hash};
+
+    #[test]
+    fn t_ccm() {
+        let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let payload: Vec<u8> = (0..40).collect();
+        let serial_number = [0x00, 0xFA, 0x12, 0x34, 0x56, 0x78];
+        assert_eq!(cbc_mac(&key, &block_0(1, &serial_number, 0, payload.len()), &[0x06, 0x10, 0x09, 0x50, 0x00, 0x01, 0x02], &payload),
+                   [0x74, 0xF2, 0x41, 0xB5, 0x24, 0x29, 0x5D, 0x80, 0x72, 0x62, 0xB9, 0x44, 0xAA, 0x60, 0x36, 0xB9]);
+        let (mac, data) = ctr(&key, &counter_0(1, &serial_number, 0), &[0; 16], &payload);
+        assert_eq!(mac, [0x6F, 0x87, 0x35, 0xFC, 0x95, 0x59, 0xFD, 0x04, 0xD9, 0xBB, 0xCA, 0x9E, 0x01, 0x42, 0x17, 0x60]);
+        assert_eq!(data, vec![0xD5, 0x1F, 0x54, 0xF8, 0xAC, 0x1E, 0x16, 0xF5, 0x73, 0x1C, 0x6A, 0x45, 0x58, 0xFF, 0xE0, 0x79,
+                              0x5D, 0xB4, 0x09, 0x1E, 0x36, 0x77, 0x2D, 0x4D, 0x29, 0x2A, 0xF5, 0xEC, 0xF5, 0x6F, 0x4F, 0xFE,
+                              0xCA, 0xF2, 0x80, 0xC7, 0xF0, 0x95, 0x0C, 0x74]);
+        // decryption is the same operation
+        assert_eq!(ctr(&key, &counter_0(1, &serial_number, 0), &mac, &data), ([0; 16], payload));
+    }
+
+    #[test]
+    fn t_keys() {
+        assert_eq!(user_password_hash("trustme"), [0xD8, 0x01, 0x61, 0x46, 0x0A, 0xE8, 0x51, 0x61, 0x47, 0x7F, 0x7D, 0x92, 0xCF, 0x64, 0x87, 0x63]);
+        assert_eq!(device_authentication_code("trustme"), [0xE1, 0x58, 0xE4, 0x01, 0x20, 0x47, 0xBD, 0x6C, 0xC4, 0x1A, 0xAF, 0xBC, 0x5C, 0x04, 0xC1, 0xFC]);
+        let server = PublicKey::from(&StaticSecret::from([2; 32])).to_bytes();
+        assert_eq!(session_key(&StaticSecret::from([1; 32]), &server), [0x6D, 0xEF, 0xCA, 0xE7, 0x08, 0xA9, 0x59, 0x5A, 0xE5, 0x8D, 0x71, 0x6A, 0xC6, 0x50, 0xDF, 0x5A]);
+    }
+}
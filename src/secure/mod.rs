@@ -0,0 +1,5 @@
+// KNX IP Secure (See 3/8/9)
+
+pub(crate) mod crypto;
+pub mod session;
+pub mod routing;
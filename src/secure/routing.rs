@@ -0,0 +1,394 @@
+//runtime facing functions:
+
+// get data to be transmitted next (to the KNX multicast address)
+// get next time event
+// handle next time event
+// handle received data -> returns the decrypted KNXnet/IP frame or none
+// send a KNXnet/IP frame of the routing connection running on top
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+use crate::knxnet::secure::{SecureWrapper, TimerNotify, MAC_LENGTH};
+use crate::knxnet::{KnxNetIpError, Service};
+use crate::secure::crypto;
+
+// timer synchronisation delays
+const KEEPER_PERIODIC_NOTIFY: Duration = Duration::from_secs(10);
+const FOLLOWER_PERIODIC_NOTIFY: Duration = Duration::from_secs(30);
+const UPDATE_NOTIFY_DELAY: Duration = Duration::from_millis(100);
+const TIMER_MASK: u64 = (1 << 48) - 1;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SecureRoutingConfig {
+    serial_number: [u8; 6],
+}
+
+impl SecureRoutingConfig {
+    /// Config with a random serial number, fails if no random source is available
+    pub fn new() -> Result<SecureRoutingConfig, KnxNetIpError> {
+        // the serial number is part of the nonce, so every device on the backbone needs its own
+        let random: [u8; 4] = crypto::random_bytes()?;
+        Ok(SecureRoutingConfig{
+            serial_number: [0x00, 0xFA, random[0], random[1], random[2], random[3]],
+        })
+    }
+
+    /// KNX serial number of the device, which needs to be unique on the backbone
+    pub fn with_serial_number(mut self, serial_number: [u8; 6]) -> SecureRoutingConfig {
+        self.serial_number = serial_number;
+        self
+    }
+}
+
+/// Secure multicast routing on a backbone secured with a backbone key. A `RoutingConnection` runs on top of it.
+///
+/// Frames are authenticated with the multicast timer of the sender, which is kept in sync with the other devices
+/// using TIMER_NOTIFY frames. Frames older than the latency tolerance are discarded as replays.
+pub struct SecureRouting {
+    backbone_key: [u8; 16],
+    latency_tolerance: u64,
+    sync_latency_tolerance: u64,
+    // the multicast timer is the time since `epoch` in milliseconds plus the offset gained by synchronisation
+    epoch: Instant,
+    timer_offset: u64,
+    synchronized: bool,
+    timekeeper: bool,
+    out_queue: VecDeque<Vec<u8>>,
+    // frames sent before the timer was synchronized
+    pending: VecDeque<Vec<u8>>,
+    current: Vec<u8>,
+    sync_deadline: Instant,
+    next_periodic_notify: Instant,
+    // serial number and message tag of an outdated frame which is answered with the own timer
+    update_notify: Option<(Instant, [u8; 6], u16)>,
+    random_state: u64,
+    config: SecureRoutingConfig,
+}
+
+impl SecureRouting {
+    /// Create secure routing with the backbone key and the latency tolerance of the backbone from the ETS project,
+    /// fails if no random source is available
+    pub fn new(backbone_key: [u8; 16], latency_tolerance: Duration, config: SecureRoutingConfig) -> Result<SecureRouting, KnxNetIpError> {
+        let now = Instant::now();
+        // delays and message tags only need to differ between devices, so they are derived from a single seed
+        let seed = u64::from_be_bytes(crypto::random_bytes()?);
+        let latency_tolerance = latency_tolerance.as_millis() as u64;
+        let mut routing = SecureRouting{
+            backbone_key,
+            latency_tolerance,
+            sync_latency_tolerance: latency_tolerance / 10,
+            epoch: now,
+            timer_offset: 0,
+            synchronized: false,
+            timekeeper: false,
+            out_queue: VecDeque::new(),
+            pending: VecDeque::new(),
+            current: vec![],
+            // devices with a newer timer answer the initial notification with their timer
+            sync_deadline: now.add(Duration::from_millis(latency_tolerance)),
+            next_periodic_notify: now,
+            update_notify: None,
+            // xorshift must not be seeded with 0
+            random_state: seed | 1,
+            config,
+        };
+        let message_tag = routing.random() as u16;
+        routing.send_timer_notify(config.serial_number, message_tag);
+        Ok(routing)
+    }
+
+    /// Send a KNXnet/IP frame encrypted with the backbone key. Frames are held back until the timer is synchronized.
+    pub fn send(&mut self, frame: &[u8]) {
+        if self.synchronized {
+            let wrapped = self.wrap(frame);
+            self.out_queue.push_back(wrapped);
+        } else {
+            self.pending.push_back(frame.to_vec());
+        }
+    }
+
+    pub fn synchronized(&self) -> bool {
+        self.synchronized
+    }
+
+    /// No device with a newer timer is known, so this one sends the periodic timer notifications
+    pub fn timekeeper(&self) -> bool {
+        self.timekeeper
+    }
+
+    /// Current value of the multicast timer in milliseconds
+    pub fn timer_value(&self) -> u64 {
+        (self.timer_offset + self.epoch.elapsed().as_millis() as u64) & TIMER_MASK
+    }
+
+    /// Data which needs to be sent to the KNX multicast address
+    pub fn get_outbound_data(&mut self) -> Option<&[u8]> {
+        self.current = self.out_queue.pop_front()?;
+        Some(&self.current)
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        let next = if self.synchronized { self.next_periodic_notify } else { self.sync_deadline };
+        match self.update_notify {
+            Some((at, _, _)) => next.min(at),
+            None => next,
+        }
+    }
+
+    pub fn handle_time_events(&mut self) {
+        let now = Instant::now();
+        if !self.synchronized && self.sync_deadline <= now {
+            // nobody answered with a newer timer, so the own timer is the reference
+            self.timekeeper = true;
+            self.set_synchronized(now);
+        }
+        if self.synchronized && self.next_periodic_notify <= now {
+            let message_tag = self.random() as u16;
+            self.send_timer_notify(self.config.serial_number, message_tag);
+            self.schedule_periodic_notify(now);
+        }
+        if let Some((at, serial_number, message_tag)) = self.update_notify {
+            if at <= now {
+                self.update_notify = None;
+                self.send_timer_notify(serial_number, message_tag);
+            }
+        }
+    }
+
+    /// Handle data received on the KNX multicast address, returns the decrypted frame for the routing connection
+    pub fn handle_inbound_message(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        match Service::<()>::decoded(data) {
+            // secure routing frames are not part of a session
+            Ok(Service::SecureWrapper(wrapper)) if wrapper.session_id == 0 => {
+                let frame = crypto::decrypt_wrapper(&self.backbone_key, &data[0..6], &wrapper)?;
+                self.check_timer(wrapper.sequence, wrapper.serial_number, wrapper.message_tag).then_some(frame)
+            }
+            Ok(Service::TimerNotify(notify)) => {
+                if crypto::verify_timer_notify(&self.backbone_key, &data[0..6], &notify) {
+                    self.check_timer(notify.timer, notify.serial_number, notify.message_tag);
+                }
+                None
+            }
+            // unsecured frames are not accepted on a secured backbone
+            _ => None
+        }
+    }
+
+    // compares the timer of an authenticated frame with the own one, returns if the frame is recent enough
+    fn check_timer(&mut self, timer: u64, serial_number: [u8; 6], message_tag: u16) -> bool {
+        let now = Instant::now();
+        let local = self.timer_value();
+        if local.saturating_sub(timer) >= self.latency_tolerance {
+            // answer with the own timer, unless another device does so first
+            if self.update_notify.is_none() {
+                let delay = if self.timekeeper {
+                    UPDATE_NOTIFY_DELAY
+                } else {
+                    UPDATE_NOTIFY_DELAY + self.random_delay(self.sync_latency_tolerance)
+                };
+                self.update_notify = Some((now + delay, serial_number, message_tag));
+            }
+            return false
+        }
+        if self.update_notify.is_some_and(|(_, s, t)| s == serial_number && t == message_tag) {
+            self.update_notify = None;
+        }
+        if timer > local {
+            self.timer_offset += timer - local;
+            self.timekeeper = false;
+            if self.synchronized {
+                self.schedule_periodic_notify(now);
+            } else {
+                self.set_synchronized(now);
+            }
+        } else if local - timer < self.sync_latency_tolerance && !self.timekeeper {
+            // another device keeps the time, so no periodic notification is needed
+            self.schedule_periodic_notify(now);
+        }
+        true
+    }
+
+    fn set_synchronized(&mut self, now: Instant) {
+        self.synchronized = true;
+        self.schedule_periodic_notify(now);
+        while let Some(frame) = self.pending.pop_front() {
+            let wrapped = self.wrap(&frame);
+            self.out_queue.push_back(wrapped);
+        }
+    }
+
+    fn schedule_periodic_notify(&mut self, now: Instant) {
+        self.next_periodic_notify = if self.timekeeper {
+            now + KEEPER_PERIODIC_NOTIFY
+        } else {
+            // followers only step in when the time keeper is gone, not all at the same time
+            now + FOLLOWER_PERIODIC_NOTIFY + self.random_delay(3 * self.latency_tolerance)
+        };
+    }
+
+    fn send_timer_notify(&mut self, serial_number: [u8; 6], message_tag: u16) {
+        let notify = TimerNotify{
+            timer: self.timer_value(),
+            serial_number,
+            message_tag,
+            mac: [0; MAC_LENGTH],
+        };
+        self.out_queue.push_back(crypto::authenticate_timer_notify(&self.backbone_key, notify));
+    }
+
+    fn wrap(&self, frame: &[u8]) -> Vec<u8> {
+        let wrapper = SecureWrapper{
+            session_id: 0,
+            sequence: self.timer_value(),
+            serial_number: self.config.serial_number,
+            message_tag: 0,
+            data: frame.to_vec(),
+            mac: [0; MAC_LENGTH],
+        };
+        crypto::encrypt_wrapper(&self.backbone_key, wrapper)
+    }
+
+    // uniform random delay up to `max_millis`
+    fn random_delay(&mut self, max_millis: u64) -> Duration {
+        let random = self.random() as u16 as u64;
+        Duration::from_millis(max_millis * random / (u16::MAX as u64 + 1))
+    }
+
+    fn random(&mut self) -> u64 {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        self.random_state
+    }
+}
+
+impl fmt::Debug for SecureRouting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecureRouting")
+            .field("timer", &self.timer_value())
+            .field("synchronized", &self.synchronized)
+            .field("timekeeper", &self.timekeeper)
+            .field("latency_tolerance", &self.latency_tolerance)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::knxnet::secure::{SecureWrapper, TimerNotify, MAC_LENGTH};
+    use crate::knxnet::Service;
+    use crate::secure::crypto;
+    use crate::secure::routing::{SecureRouting, SecureRoutingConfig};
+
+    const BACKBONE_KEY: [u8; 16] = [0x44; 16];
+    const PEER_SERIAL_NUMBER: [u8; 6] = [0x00, 0xFA, 0x01, 0x02, 0x03, 0x04];
+    const ROUTING_INDICATION: [u8; 19] = [0x06, 0x10, 0x05, 0x30, 0x00, 0x13, 0x29, 0x00, 0xBC, 0xE0, 0x11, 0x01, 0x00, 0x0A, 0x03, 0x00, 0x80, 0x03, 0xD4];
+
+    fn peer_notify(key: &[u8; 16], timer: u64, message_tag: u16) -> Vec<u8> {
+        crypto::authenticate_timer_notify(key, TimerNotify{timer, serial_number: PEER_SERIAL_NUMBER, message_tag, mac: [0; MAC_LENGTH]})
+    }
+
+    fn peer_wrap(timer: u64, frame: &[u8]) -> Vec<u8> {
+        crypto::encrypt_wrapper(&BACKBONE_KEY, SecureWrapper{
+            session_id: 0,
+            sequence: timer,
+            serial_number: PEER_SERIAL_NUMBER,
+            message_tag: 0,
+            data: frame.to_vec(),
+            mac: [0; MAC_LENGTH],
+        })
+    }
+
+    fn timer_notify(data: &[u8]) -> TimerNotify {
+        match Service::<()>::decoded(data) {
+            Ok(Service::TimerNotify(notify)) => {
+                assert!(crypto::verify_timer_notify(&BACKBONE_KEY, &data[0..6], &notify));
+                notify
+            }
+            _ => panic!("timer notify expected")
+        }
+    }
+
+    fn synchronized_routing() -> SecureRouting {
+        let mut routing = SecureRouting::new(BACKBONE_KEY, Duration::from_millis(1000), SecureRoutingConfig::new().unwrap()).unwrap();
+        routing.get_outbound_data();
+        routing.handle_inbound_message(&peer_notify(&BACKBONE_KEY, 1_000_000, 0x1234));
+        routing
+    }
+
+    #[test]
+    fn t_secure_routing_synchronization() {
+        let mut routing = SecureRouting::new(BACKBONE_KEY, Duration::from_millis(1000), SecureRoutingConfig::new().unwrap()).unwrap();
+        let notify = timer_notify(routing.get_outbound_data().unwrap());
+        assert!(notify.timer < 1000);
+        assert_eq!(&notify.serial_number[0..2], &[0x00, 0xFA]);
+        // a serial number of the ETS project is used instead of the random one
+        let config = SecureRoutingConfig::new().unwrap().with_serial_number([0x00, 0xFA, 0x12, 0x34, 0x56, 0x78]);
+        let mut other = SecureRouting::new(BACKBONE_KEY, Duration::from_millis(1000), config).unwrap();
+        assert_eq!(timer_notify(other.get_outbound_data().unwrap()).serial_number, [0x00, 0xFA, 0x12, 0x34, 0x56, 0x78]);
+        // frames are held back until the timer is synchronized
+        routing.send(&ROUTING_INDICATION);
+        assert_eq!(routing.get_outbound_data(), None);
+
+        // notifications with another key are ignored
+        routing.handle_inbound_message(&peer_notify(&[0x55; 16], 1_000_000, 0x1234));
+        assert!(!routing.synchronized());
+
+        routing.handle_inbound_message(&peer_notify(&BACKBONE_KEY, 1_000_000, 0x1234));
+        assert!(routing.synchronized());
+        assert!(!routing.timekeeper());
+        assert!(routing.timer_value() >= 1_000_000);
+        let data = routing.get_outbound_data().unwrap().to_vec();
+        match Service::<()>::decoded(&data) {
+            Ok(Service::SecureWrapper(wrapper)) => {
+                assert_eq!(wrapper.session_id, 0);
+                assert!(wrapper.sequence >= 1_000_000);
+                assert_eq!(crypto::decrypt_wrapper(&BACKBONE_KEY, &data[0..6], &wrapper), Some(ROUTING_INDICATION.to_vec()));
+            }
+            _ => panic!("secure wrapper expected")
+        }
+        assert_eq!(routing.get_outbound_data(), None);
+    }
+
+    #[test]
+    fn t_secure_routing_timekeeper() {
+        let mut routing = SecureRouting::new(BACKBONE_KEY, Duration::from_millis(10), SecureRoutingConfig::new().unwrap()).unwrap();
+        routing.get_outbound_data();
+        routing.send(&ROUTING_INDICATION);
+        std::thread::sleep(routing.get_next_time_event().saturating_duration_since(Instant::now()));
+        routing.handle_time_events();
+        // nobody answered, so the own timer is used
+        assert!(routing.synchronized());
+        assert!(routing.timekeeper());
+        assert!(routing.get_outbound_data().is_some());
+        assert!(routing.get_next_time_event() > Instant::now() + Duration::from_secs(9));
+    }
+
+    #[test]
+    fn t_secure_routing_latency_tolerance() {
+        let mut routing = synchronized_routing();
+        assert_eq!(routing.handle_inbound_message(&peer_wrap(999_500, &ROUTING_INDICATION)), Some(ROUTING_INDICATION.to_vec()));
+        assert_eq!(routing.handle_inbound_message(&ROUTING_INDICATION), None);
+
+        // outdated frames are discarded and answered with the own timer
+        assert_eq!(routing.handle_inbound_message(&peer_wrap(990_000, &ROUTING_INDICATION)), None);
+        assert_eq!(routing.get_outbound_data(), None);
+        assert!(routing.get_next_time_event() <= Instant::now() + Duration::from_millis(200));
+        std::thread::sleep(routing.get_next_time_event().saturating_duration_since(Instant::now()));
+        routing.handle_time_events();
+        let notify = timer_notify(routing.get_outbound_data().unwrap());
+        assert!(notify.timer >= 1_000_000);
+        assert_eq!(notify.serial_number, PEER_SERIAL_NUMBER);
+        assert_eq!(notify.message_tag, 0);
+
+        // no answer is sent when another device answered first
+        routing.handle_inbound_message(&peer_notify(&BACKBONE_KEY, 980_000, 0x4321));
+        routing.handle_inbound_message(&peer_notify(&BACKBONE_KEY, routing.timer_value(), 0x4321));
+        std::thread::sleep(Duration::from_millis(250));
+        routing.handle_time_events();
+        assert_eq!(routing.get_outbound_data(), None);
+    }
+}
@@ -0,0 +1,521 @@
+//runtime facing functions:
+
+// get data to be transmitted next (session handshake or wrapped frames)
+// get next time event
+// handle next time event
+// handle received data -> returns the decrypted KNXnet/IP frame or none
+// send a KNXnet/IP frame of a connection running on top of the session
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+use strum_macros::FromRepr;
+use x25519_dalek::{PublicKey, StaticSecret};
+use crate::knxnet::hpai::{HPAI, Protocol};
+use crate::knxnet::secure::{SecureWrapper, SessionAuthenticate, SessionRequest, SessionResponse, SessionStatus, SessionStatusCode, MAC_LENGTH};
+use crate::knxnet::{KnxNetIpError, Service};
+use crate::secure::crypto;
+
+/// Keys of a secure tunnelling user. User 1 is reserved for management access.
+#[derive(Clone, PartialEq)]
+pub struct SecureSessionCredentials {
+    user_id: u8,
+    user_key: [u8; 16],
+    device_authentication_code: Option<[u8; 16]>,
+}
+
+impl SecureSessionCredentials {
+    /// Derive the keys from the passwords of the ETS project. This takes some time, so the credentials should be reused.
+    /// Without device authentication password the identity of the server is not verified.
+    pub fn from_passwords(user_id: u8, user_password: &str, device_authentication_password: Option<&str>) -> SecureSessionCredentials {
+        SecureSessionCredentials{
+            user_id,
+            user_key: crypto::user_password_hash(user_password),
+            device_authentication_code: device_authentication_password.map(crypto::device_authentication_code),
+        }
+    }
+
+    /// Use already derived keys, e.g. from a keyring
+    pub fn from_keys(user_id: u8, user_key: [u8; 16], device_authentication_code: Option<[u8; 16]>) -> SecureSessionCredentials {
+        SecureSessionCredentials{
+            user_id,
+            user_key,
+            device_authentication_code,
+        }
+    }
+}
+
+impl fmt::Debug for SecureSessionCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecureSessionCredentials").field("user_id", &self.user_id).finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SecureSessionConfig {
+    handshake_timeout: Duration,
+    keepalive_interval: Duration,
+    serial_number: [u8; 6],
+}
+
+impl Default for SecureSessionConfig {
+    fn default() -> SecureSessionConfig {
+        // servers close sessions after 60s without frames
+        SecureSessionConfig{
+            handshake_timeout: Duration::from_secs(10),
+            keepalive_interval: Duration::from_secs(30),
+            serial_number: [0; 6],
+        }
+    }
+}
+
+impl SecureSessionConfig {
+    /// Time the server has to answer the session request and authenticate the session
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> SecureSessionConfig {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Interval of the keep alive status frames, which needs to be below the 60s servers wait before closing a session
+    pub fn with_keepalive_interval(mut self, interval: Duration) -> SecureSessionConfig {
+        self.keepalive_interval = interval;
+        self
+    }
+
+    /// KNX serial number of the client, sent in every secure wrapper
+    pub fn with_serial_number(mut self, serial_number: [u8; 6]) -> SecureSessionConfig {
+        self.serial_number = serial_number;
+        self
+    }
+}
+
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Default)]
+enum SecureSessionState {
+    #[default]
+    Connecting,
+    Authenticating,
+    Authenticated,
+    Closed,
+}
+
+/// Secure session to a KNX IP Secure server, unicast connections like a `TunnelConnection` run on top of it.
+///
+/// Frames of the connection are passed to `send` and all data received from the server to `handle_inbound_message`,
+/// which returns the decrypted frames for the connection.
+pub struct SecureSession {
+    state: SecureSessionState,
+    status: Option<SessionStatusCode>,
+    credentials: SecureSessionCredentials,
+    secret: StaticSecret,
+    public_key: [u8; 32],
+    session_id: u16,
+    session_key: [u8; 16],
+    outbound_sequence: u64,
+    inbound_sequence: u64,
+    out_queue: VecDeque<Vec<u8>>,
+    // frames sent before the session was authenticated
+    pending: VecDeque<Vec<u8>>,
+    current: Vec<u8>,
+    next_timeout: Instant,
+    next_keepalive: Instant,
+    config: SecureSessionConfig,
+}
+
+impl SecureSession {
+    /// Create a session over UDP which expects responses on the given local address and port,
+    /// fails if no random source is available for the key pair of the session
+    pub fn new(ipv4: [u8;4], port: u16, credentials: SecureSessionCredentials, config: SecureSessionConfig) -> Result<SecureSession, KnxNetIpError> {
+        SecureSession::new_with_control(HPAI::new(Protocol::Udp4Protocol, ipv4, port), credentials, config)
+    }
+
+    /// Create a session over an already established TCP stream
+    pub fn new_tcp(credentials: SecureSessionCredentials, config: SecureSessionConfig) -> Result<SecureSession, KnxNetIpError> {
+        SecureSession::new_with_control(HPAI::new(Protocol::Tcp4Protocol, [0, 0, 0, 0], 0), credentials, config)
+    }
+
+    fn new_with_control(control: HPAI, credentials: SecureSessionCredentials, config: SecureSessionConfig) -> Result<SecureSession, KnxNetIpError> {
+        let secret = crypto::generate_secret()?;
+        let public_key = PublicKey::from(&secret).to_bytes();
+        let req: Service<()> = Service::SessionRequest(SessionRequest{
+            control,
+            public_key,
+        });
+        Ok(SecureSession{
+            state: SecureSessionState::Connecting,
+            status: None,
+            credentials,
+            secret,
+            public_key,
+            session_id: 0,
+            session_key: [0; 16],
+            outbound_sequence: 0,
+            inbound_sequence: 0,
+            out_queue: VecDeque::from(vec![req.encoded()]),
+            pending: VecDeque::new(),
+            current: vec![],
+            next_timeout: Instant::now().add(config.handshake_timeout),
+            next_keepalive: Instant::now().add(config.keepalive_interval),
+            config,
+        })
+    }
+
+    /// Send a KNXnet/IP frame encrypted within the session. Frames are held back until the session is authenticated.
+    pub fn send(&mut self, frame: &[u8]) {
+        match self.state {
+            SecureSessionState::Authenticated => {
+                let wrapped = self.wrap(frame);
+                self.out_queue.push_back(wrapped);
+            }
+            SecureSessionState::Connecting | SecureSessionState::Authenticating => self.pending.push_back(frame.to_vec()),
+            SecureSessionState::Closed => {}
+        }
+    }
+
+    /// Close the session, frames of connections running on top of it can't be sent anymore
+    pub fn close(&mut self) {
+        if self.state == SecureSessionState::Authenticated {
+            self.send_status(SessionStatusCode::Close);
+        }
+        self.state = SecureSessionState::Closed;
+        self.status = Some(SessionStatusCode::Close);
+        self.pending.clear();
+    }
+
+    pub fn authenticated(&self) -> bool {
+        self.state == SecureSessionState::Authenticated
+    }
+
+    /// Session was closed by either side or could not be established, a new session is needed
+    pub fn closed(&self) -> bool {
+        self.state == SecureSessionState::Closed
+    }
+
+    /// Last status reported by the server or the reason the session was closed
+    pub fn status(&self) -> Option<SessionStatusCode> {
+        self.status
+    }
+
+    /// Data which needs to be sent to the control endpoint of the server
+    pub fn get_outbound_data(&mut self) -> Option<&[u8]> {
+        self.current = self.out_queue.pop_front()?;
+        Some(&self.current)
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        match self.state {
+            SecureSessionState::Connecting | SecureSessionState::Authenticating => self.next_timeout,
+            SecureSessionState::Authenticated => self.next_keepalive,
+            SecureSessionState::Closed => Instant::now().add(self.config.keepalive_interval),
+        }
+    }
+
+    pub fn handle_time_events(&mut self) {
+        let now = Instant::now();
+        match self.state {
+            SecureSessionState::Connecting | SecureSessionState::Authenticating if self.next_timeout <= now => {
+                self.state = SecureSessionState::Closed;
+                self.status = Some(SessionStatusCode::Timeout);
+                self.pending.clear();
+            }
+            SecureSessionState::Authenticated if self.next_keepalive <= now => {
+                self.send_status(SessionStatusCode::KeepAlive);
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle data received from the server, returns the decrypted frame of the connection running on top of the session
+    pub fn handle_inbound_message(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        match Service::<()>::decoded(data) {
+            Ok(Service::SessionResponse(resp)) if self.state == SecureSessionState::Connecting => {
+                self.handle_session_response(resp, &data[0..6]);
+                None
+            }
+            // without a session key the server can only reject the session request unencrypted
+            Ok(Service::SessionStatus(status)) if self.state == SecureSessionState::Connecting && Self::rejects_session(status.status) => {
+                self.handle_session_status(status.status);
+                None
+            }
+            Ok(Service::SecureWrapper(wrapper)) => {
+                let frame = self.unwrap(&wrapper, &data[0..6])?;
+                if let Ok(Service::<Vec<u8>>::SessionStatus(status)) = Service::decoded(&frame) {
+                    self.handle_session_status(status.status);
+                    return None
+                }
+                if self.state != SecureSessionState::Authenticated {
+                    return None
+                }
+                Some(frame)
+            }
+            _ => None
+        }
+    }
+
+    fn handle_session_response(&mut self, resp: SessionResponse, header: &[u8]) {
+        let public_keys = crypto::xor(&self.public_key, &resp.public_key);
+        // the response is authenticated with the device authentication code, if it is known
+        if let Some(code) = self.credentials.device_authentication_code {
+            let mut additional_data = header.to_vec();
+            additional_data.extend(resp.session_id.to_be_bytes());
+            additional_data.extend_from_slice(&public_keys);
+            let (mac, _) = crypto::ctr(&code, &crypto::COUNTER_0_HANDSHAKE, &resp.mac, &[]);
+            if crypto::cbc_mac(&code, &[0; 16], &additional_data, &[]) != mac {
+                return
+            }
+        }
+        self.session_id = resp.session_id;
+        self.session_key = crypto::session_key(&self.secret, &resp.public_key);
+
+        let user_id = self.credentials.user_id;
+        let user_key = self.credentials.user_key;
+        let mut additional_data = Service::<()>::SessionAuthenticate(SessionAuthenticate{user_id, mac: [0; MAC_LENGTH]}).header().to_vec();
+        additional_data.extend([0x00, user_id]);
+        additional_data.extend_from_slice(&public_keys);
+        let mac = crypto::cbc_mac(&user_key, &[0; 16], &additional_data, &[]);
+        let (mac, _) = crypto::ctr(&user_key, &crypto::COUNTER_0_HANDSHAKE, &mac, &[]);
+        let auth: Service<()> = Service::SessionAuthenticate(SessionAuthenticate{user_id, mac});
+        let wrapped = self.wrap(&auth.encoded());
+        self.out_queue.push_back(wrapped);
+        self.state = SecureSessionState::Authenticating;
+        self.next_timeout = Instant::now().add(self.config.handshake_timeout);
+    }
+
+    fn rejects_session(status: SessionStatusCode) -> bool {
+        matches!(status, SessionStatusCode::Unauthenticated | SessionStatusCode::Timeout | SessionStatusCode::Close)
+    }
+
+    fn handle_session_status(&mut self, status: SessionStatusCode) {
+        self.status = Some(status);
+        match status {
+            SessionStatusCode::AuthenticationSuccess if self.state == SecureSessionState::Authenticating => {
+                self.state = SecureSessionState::Authenticated;
+                self.next_keepalive = Instant::now().add(self.config.keepalive_interval);
+                while let Some(frame) = self.pending.pop_front() {
+                    let wrapped = self.wrap(&frame);
+                    self.out_queue.push_back(wrapped);
+                }
+            }
+            SessionStatusCode::AuthenticationSuccess | SessionStatusCode::KeepAlive => {}
+            _ => {
+                self.state = SecureSessionState::Closed;
+                self.pending.clear();
+            }
+        }
+    }
+
+    fn send_status(&mut self, status: SessionStatusCode) {
+        let frame = Service::<()>::SessionStatus(SessionStatus{status}).encoded();
+        let wrapped = self.wrap(&frame);
+        self.out_queue.push_back(wrapped);
+    }
+
+    fn wrap(&mut self, frame: &[u8]) -> Vec<u8> {
+        let wrapper = SecureWrapper{
+            session_id: self.session_id,
+            sequence: self.outbound_sequence,
+            serial_number: self.config.serial_number,
+            message_tag: 0,
+            data: frame.to_vec(),
+            mac: [0; MAC_LENGTH],
+        };
+        self.outbound_sequence += 1;
+        // every frame sent resets the session timeout of the server
+        self.next_keepalive = Instant::now().add(self.config.keepalive_interval);
+        crypto::encrypt_wrapper(&self.session_key, wrapper)
+    }
+
+    fn unwrap(&mut self, wrapper: &SecureWrapper, header: &[u8]) -> Option<Vec<u8>> {
+        if self.state == SecureSessionState::Connecting || wrapper.session_id != self.session_id {
+            return None
+        }
+        // replayed frames are discarded
+        if wrapper.sequence < self.inbound_sequence {
+            return None
+        }
+        let frame = crypto::decrypt_wrapper(&self.session_key, header, wrapper)?;
+        self.inbound_sequence = wrapper.sequence + 1;
+        Some(frame)
+    }
+}
+
+impl fmt::Debug for SecureSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecureSession")
+            .field("state", &self.state)
+            .field("status", &self.status)
+            .field("session_id", &self.session_id)
+            .field("outbound_sequence", &self.outbound_sequence)
+            .field("inbound_sequence", &self.inbound_sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use x25519_dalek::{PublicKey, StaticSecret};
+    use crate::knxnet::hpai::{HPAI, Protocol};
+    use crate::knxnet::secure::{SecureWrapper, SessionResponse, SessionStatus, SessionStatusCode, MAC_LENGTH};
+    use crate::knxnet::Service;
+    use crate::secure::crypto;
+    use crate::secure::session::{SecureSession, SecureSessionConfig, SecureSessionCredentials};
+
+    const USER_KEY: [u8; 16] = [0x11; 16];
+    const DEVICE_CODE: [u8; 16] = [0x22; 16];
+    const CONNECTIONSTATE_REQUEST: [u8; 16] = [0x06, 0x10, 0x02, 0x07, 0x00, 0x10, 0x15, 0x00, 0x08, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+    const CONNECTIONSTATE_RESPONSE: [u8; 8] = [0x06, 0x10, 0x02, 0x08, 0x00, 0x08, 0x15, 0x00];
+
+    fn session_response(session_id: u16, server: &StaticSecret, client_public_key: &[u8; 32], code: &[u8; 16]) -> Vec<u8> {
+        let public_key = PublicKey::from(server).to_bytes();
+        let mut additional_data = Service::<()>::SessionResponse(SessionResponse::default()).header().to_vec();
+        additional_data.extend(session_id.to_be_bytes());
+        additional_data.extend(crypto::xor(client_public_key, &public_key));
+        let (mac, _) = crypto::ctr(code, &crypto::COUNTER_0_HANDSHAKE, &crypto::cbc_mac(code, &[0; 16], &additional_data, &[]), &[]);
+        Service::<()>::SessionResponse(SessionResponse{session_id, public_key, mac}).encoded()
+    }
+
+    fn server_wrap(key: &[u8; 16], sequence: u64, frame: &[u8]) -> Vec<u8> {
+        crypto::encrypt_wrapper(key, SecureWrapper{
+            session_id: 1,
+            sequence,
+            serial_number: [0x00, 0xFA, 0x01, 0x02, 0x03, 0x04],
+            message_tag: 0,
+            data: frame.to_vec(),
+            mac: [0; MAC_LENGTH],
+        })
+    }
+
+    fn server_unwrap(key: &[u8; 16], data: &[u8]) -> Vec<u8> {
+        match Service::<()>::decoded(data) {
+            Ok(Service::SecureWrapper(wrapper)) => crypto::decrypt_wrapper(key, &data[0..6], &wrapper).unwrap(),
+            _ => panic!("not a secure wrapper")
+        }
+    }
+
+    #[test]
+    fn t_secure_session() {
+        let mut session = SecureSession::new_tcp(SecureSessionCredentials::from_keys(2, USER_KEY, Some(DEVICE_CODE)), SecureSessionConfig::default()).unwrap();
+        // frames are held back until the session is authenticated
+        session.send(&CONNECTIONSTATE_REQUEST);
+        let client_public_key = match Service::<()>::decoded(session.get_outbound_data().unwrap()) {
+            Ok(Service::SessionRequest(req)) => {
+                assert_eq!(req.control, HPAI::new(Protocol::Tcp4Protocol, [0, 0, 0, 0], 0));
+                req.public_key
+            }
+            _ => panic!("session request expected")
+        };
+        assert_eq!(session.get_outbound_data(), None);
+
+        // responses of a server with another device authentication code are ignored
+        let server = StaticSecret::from([2; 32]);
+        session.handle_inbound_message(&session_response(1, &server, &client_public_key, &[0x33; 16]));
+        assert_eq!(session.get_outbound_data(), None);
+
+        session.handle_inbound_message(&session_response(1, &server, &client_public_key, &DEVICE_CODE));
+        let key = crypto::session_key(&server, &client_public_key);
+        let auth = server_unwrap(&key, session.get_outbound_data().unwrap());
+        match Service::<()>::decoded(&auth) {
+            Ok(Service::SessionAuthenticate(a)) => {
+                assert_eq!(a.user_id, 2);
+                let mut additional_data = auth[0..6].to_vec();
+                additional_data.extend([0x00, 0x02]);
+                additional_data.extend(crypto::xor(&client_public_key, &PublicKey::from(&server).to_bytes()));
+                let (mac, _) = crypto::ctr(&USER_KEY, &crypto::COUNTER_0_HANDSHAKE, &a.mac, &[]);
+                assert_eq!(mac, crypto::cbc_mac(&USER_KEY, &[0; 16], &additional_data, &[]));
+            }
+            _ => panic!("session authenticate expected")
+        }
+        assert!(!session.authenticated());
+
+        let status = Service::<()>::SessionStatus(SessionStatus{status: SessionStatusCode::AuthenticationSuccess}).encoded();
+        assert_eq!(session.handle_inbound_message(&server_wrap(&key, 0, &status)), None);
+        assert!(session.authenticated());
+        assert_eq!(server_unwrap(&key, session.get_outbound_data().unwrap()), CONNECTIONSTATE_REQUEST.to_vec());
+
+        // frames of the server are decrypted once, replayed frames are discarded
+        let wrapped = server_wrap(&key, 1, &CONNECTIONSTATE_RESPONSE);
+        assert_eq!(session.handle_inbound_message(&wrapped), Some(CONNECTIONSTATE_RESPONSE.to_vec()));
+        assert_eq!(session.handle_inbound_message(&wrapped), None);
+        // as well as frames with a wrong MAC
+        let mut forged = server_wrap(&key, 2, &CONNECTIONSTATE_RESPONSE);
+        forged[30] ^= 0x01;
+        assert_eq!(session.handle_inbound_message(&forged), None);
+
+        session.close();
+        let close = Service::<()>::SessionStatus(SessionStatus{status: SessionStatusCode::Close}).encoded();
+        assert_eq!(server_unwrap(&key, session.get_outbound_data().unwrap()), close);
+        assert!(session.closed());
+        session.send(&CONNECTIONSTATE_REQUEST);
+        assert_eq!(session.get_outbound_data(), None);
+    }
+
+    #[test]
+    fn t_secure_session_authentication_failed() {
+        let mut session = SecureSession::new_tcp(SecureSessionCredentials::from_keys(2, USER_KEY, None), SecureSessionConfig::default()).unwrap();
+        let client_public_key = match Service::<()>::decoded(session.get_outbound_data().unwrap()) {
+            Ok(Service::SessionRequest(req)) => req.public_key,
+            _ => panic!("session request expected")
+        };
+        // without device authentication code the server is not verified
+        let server = StaticSecret::from([2; 32]);
+        session.handle_inbound_message(&session_response(1, &server, &client_public_key, &[0x33; 16]));
+        assert!(session.get_outbound_data().is_some());
+        let key = crypto::session_key(&server, &client_public_key);
+        let status = Service::<()>::SessionStatus(SessionStatus{status: SessionStatusCode::AuthenticationFailed}).encoded();
+        session.handle_inbound_message(&server_wrap(&key, 0, &status));
+        assert!(session.closed());
+        assert_eq!(session.status(), Some(SessionStatusCode::AuthenticationFailed));
+    }
+
+    #[test]
+    fn t_secure_session_unencrypted_status() {
+        let success = Service::<()>::SessionStatus(SessionStatus{status: SessionStatusCode::AuthenticationSuccess}).encoded();
+        let close = Service::<()>::SessionStatus(SessionStatus{status: SessionStatusCode::Close}).encoded();
+        let mut session = SecureSession::new_tcp(SecureSessionCredentials::from_keys(2, USER_KEY, None), SecureSessionConfig::default()).unwrap();
+        let client_public_key = match Service::<()>::decoded(session.get_outbound_data().unwrap()) {
+            Ok(Service::SessionRequest(req)) => req.public_key,
+            _ => panic!("session request expected")
+        };
+        // only a rejection of the session request is accepted unencrypted
+        session.handle_inbound_message(&success);
+        assert_eq!(session.status(), None);
+        assert!(!session.closed());
+
+        // once the session key exists every status needs to be encrypted
+        let server = StaticSecret::from([2; 32]);
+        session.handle_inbound_message(&session_response(1, &server, &client_public_key, &[0x33; 16]));
+        session.handle_inbound_message(&close);
+        assert_eq!(session.status(), None);
+        assert!(!session.closed());
+
+        let mut session = SecureSession::new_tcp(SecureSessionCredentials::from_keys(2, USER_KEY, None), SecureSessionConfig::default()).unwrap();
+        session.handle_inbound_message(&close);
+        assert!(session.closed());
+        assert_eq!(session.status(), Some(SessionStatusCode::Close));
+    }
+
+    #[test]
+    fn t_secure_session_config() {
+        let config = SecureSessionConfig::default()
+            .with_serial_number([0x00, 0xFA, 0x12, 0x34, 0x56, 0x78])
+            .with_handshake_timeout(Duration::ZERO);
+        let mut session = SecureSession::new_tcp(SecureSessionCredentials::from_keys(2, USER_KEY, None), config).unwrap();
+        let client_public_key = match Service::<()>::decoded(session.get_outbound_data().unwrap()) {
+            Ok(Service::SessionRequest(req)) => req.public_key,
+            _ => panic!("session request expected")
+        };
+        let server = StaticSecret::from([2; 32]);
+        session.handle_inbound_message(&session_response(1, &server, &client_public_key, &[0x33; 16]));
+        match Service::<()>::decoded(session.get_outbound_data().unwrap()) {
+            Ok(Service::SecureWrapper(wrapper)) => assert_eq!(wrapper.serial_number, [0x00, 0xFA, 0x12, 0x34, 0x56, 0x78]),
+            _ => panic!("secure wrapper expected")
+        }
+
+        // the server does not authenticate the session in time
+        std::thread::sleep(Duration::from_millis(1));
+        session.handle_time_events();
+        assert!(session.closed());
+        assert_eq!(session.status(), Some(SessionStatusCode::Timeout));
+    }
+}
@@ -0,0 +1,397 @@
+//! Emulated KNX IP gateway for integration tests of applications using this crate.
+//!
+//! The gateway speaks KNXnet/IP tunnelling over UDP on localhost and connects its clients to a virtual bus of
+//! scriptable devices. Faults can be injected to test how applications cope with lost or duplicated datagrams.
+
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::address::IndividualAddress;
+use crate::group_event::{GroupEvent, GroupEventType};
+use crate::tunnelling_server::{TunnellingServer, TunnellingServerConfig};
+
+// the gateway thread wakes up regularly to send data of the test and to notice it was stopped
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type WriteHandler = Box<dyn FnMut(&[u8]) -> Vec<GroupEvent<Vec<u8>>> + Send>;
+
+/// Device on the virtual bus answering reads of its group address with its current value
+pub struct VirtualDevice {
+    address: u16,
+    value: Vec<u8>,
+    on_write: Option<WriteHandler>,
+}
+
+impl VirtualDevice {
+    pub fn new(address: u16, value: Vec<u8>) -> VirtualDevice {
+        VirtualDevice{address, value, on_write: None}
+    }
+
+    /// Script the reaction on writes to the group address, e.g. a status feedback on another group address.
+    /// The returned group events are sent to the bus after the value was updated.
+    pub fn with_write_handler<F>(mut self, handler: F) -> VirtualDevice
+        where F: FnMut(&[u8]) -> Vec<GroupEvent<Vec<u8>>> + Send + 'static {
+        self.on_write = Some(Box::new(handler));
+        self
+    }
+}
+
+/// Faults the gateway can be told to inject
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Fault {
+    /// The next datagrams received from clients are lost
+    DropInbound(usize),
+    /// The next datagrams sent to clients are lost
+    DropOutbound(usize),
+    /// The next datagrams sent to clients are sent twice
+    DuplicateOutbound(usize),
+    /// Datagrams are sent to clients after the given delay, in the order they were sent by the gateway
+    Delay(Duration),
+    /// The gateway neither receives nor sends anything, as if it was switched off
+    Unresponsive,
+}
+
+struct Emulation {
+    server: TunnellingServer,
+    devices: Vec<VirtualDevice>,
+    writes: Vec<(u16, Vec<u8>)>,
+    drop_inbound: usize,
+    drop_outbound: usize,
+    duplicate_outbound: usize,
+    delay: Duration,
+    unresponsive: bool,
+    // datagrams for clients together with the time they are due to be sent
+    outbound: VecDeque<(Instant, Vec<u8>, SocketAddrV4)>,
+}
+
+impl Emulation {
+    fn handle_datagram(&mut self, data: &[u8], source: SocketAddrV4) {
+        if self.unresponsive {
+            return
+        }
+        if self.drop_inbound > 0 {
+            self.drop_inbound -= 1;
+            return
+        }
+        if let Some(ev) = self.server.handle_inbound_message(data, source) {
+            if ev.event_type == GroupEventType::GroupValueWrite {
+                self.writes.push((ev.address, ev.data.clone()));
+            }
+            // the server already forwarded the frame to the other clients
+            self.handle_bus_event(ev, false);
+        }
+    }
+
+    // lets the devices react on a frame of the bus, `indicate` tells whether clients still need to receive it
+    fn handle_bus_event(&mut self, ev: GroupEvent<Vec<u8>>, indicate: bool) {
+        let mut events = vec![(ev, indicate)];
+        while let Some((ev, indicate)) = events.pop() {
+            for device in self.devices.iter_mut().filter(|d| d.address == ev.address) {
+                match ev.event_type {
+                    GroupEventType::GroupValueRead => events.push((GroupEvent{
+                        address: device.address,
+                        event_type: GroupEventType::GroupValueResponse,
+                        data: device.value.clone(),
+                    }, true)),
+                    GroupEventType::GroupValueWrite => {
+                        device.value = ev.data.clone();
+                        if let Some(handler) = device.on_write.as_mut() {
+                            events.extend(handler(&ev.data).into_iter().map(|ev| (ev, true)));
+                        }
+                    }
+                    GroupEventType::GroupValueResponse => {}
+                }
+            }
+            if indicate {
+                self.server.send(ev);
+            }
+        }
+    }
+
+    // queues the outbound data of the server with the faults applied
+    fn queue_outbound(&mut self) {
+        let due = Instant::now() + self.delay;
+        while let Some((data, target)) = self.server.get_outbound_data() {
+            if self.unresponsive {
+                continue
+            }
+            if self.drop_outbound > 0 {
+                self.drop_outbound -= 1;
+                continue
+            }
+            self.outbound.push_back((due, data.to_vec(), target));
+            if self.duplicate_outbound > 0 {
+                self.duplicate_outbound -= 1;
+                self.outbound.push_back((due, data.to_vec(), target));
+            }
+        }
+    }
+
+    // datagrams due to be sent, they are sent after the lock was released so delays do not block the gateway
+    fn take_due(&mut self) -> Vec<(Vec<u8>, SocketAddrV4)> {
+        let now = Instant::now();
+        let count = self.outbound.iter().take_while(|(at, _, _)| *at <= now).count();
+        self.outbound.drain(..count).map(|(_, data, target)| (data, target)).collect()
+    }
+
+    fn get_next_time_event(&self) -> Instant {
+        let next = self.server.get_next_time_event();
+        match self.outbound.front() {
+            Some((at, _, _)) => min(next, *at),
+            None => next,
+        }
+    }
+}
+
+/// KNX IP gateway running on a thread of its own until it is dropped
+pub struct EmulatedGateway {
+    address: SocketAddrV4,
+    emulation: Arc<Mutex<Emulation>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EmulatedGateway {
+    /// Start a gateway on a free UDP port of localhost
+    pub fn start(config: TunnellingServerConfig) -> io::Result<EmulatedGateway> {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let SocketAddr::V4(address) = socket.local_addr()? else {
+            unreachable!("socket is bound to an IPv4 address")
+        };
+        let emulation = Arc::new(Mutex::new(Emulation{
+            server: TunnellingServer::new(address.ip().octets(), address.port(), config),
+            devices: vec![],
+            writes: vec![],
+            drop_inbound: 0,
+            drop_outbound: 0,
+            duplicate_outbound: 0,
+            delay: Duration::ZERO,
+            unresponsive: false,
+            outbound: VecDeque::new(),
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let emulation = emulation.clone();
+            let stop = stop.clone();
+            thread::spawn(move || run(socket, emulation, stop))
+        };
+        Ok(EmulatedGateway{address, emulation, stop, thread: Some(thread)})
+    }
+
+    /// Control and data endpoint of the gateway
+    pub fn address(&self) -> SocketAddrV4 {
+        self.address
+    }
+
+    pub fn add_device(&self, device: VirtualDevice) {
+        self.lock().devices.push(device);
+    }
+
+    /// Current value of the first device with the group address
+    pub fn value(&self, address: u16) -> Option<Vec<u8>> {
+        self.lock().devices.iter().find(|d| d.address == address).map(|d| d.value.clone())
+    }
+
+    /// Group addresses and values of all group value writes sent by clients so far
+    pub fn writes(&self) -> Vec<(u16, Vec<u8>)> {
+        self.lock().writes.clone()
+    }
+
+    /// Send a group event to the virtual bus, as if another device sent it
+    pub fn send(&self, ev: GroupEvent<Vec<u8>>) {
+        self.lock().handle_bus_event(ev, true);
+    }
+
+    /// Individual addresses of the currently connected tunnels
+    pub fn connected_addresses(&self) -> Vec<IndividualAddress> {
+        self.lock().server.connected_addresses()
+    }
+
+    pub fn inject_fault(&self, fault: Fault) {
+        let mut emulation = self.lock();
+        match fault {
+            Fault::DropInbound(count) => emulation.drop_inbound += count,
+            Fault::DropOutbound(count) => emulation.drop_outbound += count,
+            Fault::DuplicateOutbound(count) => emulation.duplicate_outbound += count,
+            Fault::Delay(delay) => emulation.delay = delay,
+            Fault::Unresponsive => emulation.unresponsive = true,
+        }
+    }
+
+    pub fn clear_faults(&self) {
+        let mut emulation = self.lock();
+        emulation.drop_inbound = 0;
+        emulation.drop_outbound = 0;
+        emulation.duplicate_outbound = 0;
+        emulation.delay = Duration::ZERO;
+        emulation.unresponsive = false;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Emulation> {
+        // a panicking write handler must not take down the whole test
+        self.emulation.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for EmulatedGateway {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(socket: UdpSocket, emulation: Arc<Mutex<Emulation>>, stop: Arc<AtomicBool>) {
+    let mut buf = [0u8; 1 << 16];
+    while !stop.load(Ordering::Relaxed) {
+        let (due, timeout) = {
+            let mut emulation = emulation.lock().unwrap_or_else(|e| e.into_inner());
+            emulation.queue_outbound();
+            (emulation.take_due(), emulation.get_next_time_event().saturating_duration_since(Instant::now()))
+        };
+        for (data, target) in due {
+            let _ = socket.send_to(&data, target);
+        }
+        // a zero timeout is rejected by the socket
+        let _ = socket.set_read_timeout(Some(timeout.clamp(Duration::from_millis(1), POLL_INTERVAL)));
+        let received = socket.recv_from(&mut buf);
+        let mut emulation = emulation.lock().unwrap_or_else(|e| e.into_inner());
+        if let Ok((len, SocketAddr::V4(source))) = received {
+            emulation.handle_datagram(&buf[..len], source);
+        }
+        emulation.server.handle_time_events();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+    use std::time::{Duration, Instant};
+    use crate::group_event::{GroupEvent, GroupEventType};
+    use crate::testing::{EmulatedGateway, Fault, VirtualDevice};
+    use crate::tunnel_connection::{Endpoint, TunnelConnection, TunnelConnectionConfig};
+    use crate::tunnelling_server::TunnellingServerConfig;
+
+    struct Client {
+        tunnel: TunnelConnection,
+        socket: UdpSocket,
+        gateway: SocketAddr,
+        events: Vec<GroupEvent<Vec<u8>>>,
+    }
+
+    impl Client {
+        fn connect(gateway: &EmulatedGateway) -> Client {
+            let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            socket.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+            let port = socket.local_addr().unwrap().port();
+            let mut client = Client{
+                tunnel: TunnelConnection::new([127, 0, 0, 1], port, TunnelConnectionConfig::default()),
+                socket,
+                gateway: SocketAddr::V4(gateway.address()),
+                events: vec![],
+            };
+            assert!(client.poll_until(|c| c.tunnel.connected()));
+            client
+        }
+
+        fn poll_until(&mut self, condition: impl Fn(&Client) -> bool) -> bool {
+            self.poll(Duration::from_secs(5), condition)
+        }
+
+        fn poll(&mut self, timeout: Duration, condition: impl Fn(&Client) -> bool) -> bool {
+            let deadline = Instant::now() + timeout;
+            let mut buf = [0u8; 1024];
+            while Instant::now() < deadline {
+                while let Some((data, endpoint)) = self.tunnel.get_outbound_data() {
+                    let target = match endpoint {
+                        Endpoint::Control => self.gateway,
+                        Endpoint::Data(address) => SocketAddr::V4(address),
+                    };
+                    self.socket.send_to(data, target).unwrap();
+                }
+                if let Ok(len) = self.socket.recv(&mut buf) {
+                    self.events.extend(self.tunnel.handle_inbound_message(&buf[..len]));
+                }
+                self.tunnel.handle_time_events();
+                if condition(self) {
+                    return true
+                }
+            }
+            false
+        }
+    }
+
+    #[test]
+    fn t_emulated_gateway() {
+        let gateway = EmulatedGateway::start(TunnellingServerConfig::default()).unwrap();
+        // switch with its status on the next group address
+        gateway.add_device(VirtualDevice::new(10, vec![0x01])
+            .with_write_handler(|value| vec![GroupEvent{address: 11, event_type: GroupEventType::GroupValueWrite, data: value.to_vec()}]));
+        let mut client = Client::connect(&gateway);
+        assert_eq!(gateway.connected_addresses().len(), 1);
+
+        client.tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert!(client.poll_until(|c| c.events.iter().any(|ev| ev.event_type == GroupEventType::GroupValueResponse)));
+        assert_eq!(client.events[0].data, vec![0x01]);
+
+        client.tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: vec![0x00]});
+        assert!(client.poll_until(|c| c.events.iter().any(|ev| ev.address == 11)));
+        assert_eq!(client.events[1].data, vec![0x00]);
+        assert_eq!(gateway.writes(), vec![(10, vec![0x00])]);
+        assert_eq!(gateway.value(10), Some(vec![0x00]));
+
+        gateway.send(GroupEvent{address: 12, event_type: GroupEventType::GroupValueWrite, data: vec![0x12, 0x34]});
+        assert!(client.poll_until(|c| c.events.iter().any(|ev| ev.address == 12)));
+    }
+
+    #[test]
+    fn t_emulated_gateway_faults() {
+        let gateway = EmulatedGateway::start(TunnellingServerConfig::default()).unwrap();
+        let mut client = Client::connect(&gateway);
+
+        // the request is lost, so the client repeats it
+        gateway.inject_fault(Fault::DropInbound(1));
+        client.tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: vec![0x01]});
+        assert!(client.poll_until(|_| gateway.writes().len() == 1));
+
+        // the ack is lost, so the client repeats the request which must not be processed twice
+        gateway.inject_fault(Fault::DropOutbound(1));
+        client.tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: vec![0x00]});
+        client.poll(Duration::from_millis(1500), |_| false);
+        assert_eq!(gateway.writes(), vec![(10, vec![0x01]), (10, vec![0x00])]);
+
+        gateway.inject_fault(Fault::Unresponsive);
+        gateway.send(GroupEvent{address: 12, event_type: GroupEventType::GroupValueWrite, data: vec![0x01]});
+        assert!(!client.poll(Duration::from_millis(100), |c| !c.events.is_empty()));
+        gateway.clear_faults();
+        gateway.send(GroupEvent{address: 12, event_type: GroupEventType::GroupValueWrite, data: vec![0x02]});
+        // the lost indication is repeated by the gateway
+        assert!(client.poll_until(|c| c.events.len() == 2));
+        assert_eq!(client.events.iter().map(|ev| ev.data.clone()).collect::<Vec<_>>(), vec![vec![0x01], vec![0x02]]);
+    }
+
+    #[test]
+    fn t_emulated_gateway_delay() {
+        let gateway = EmulatedGateway::start(TunnellingServerConfig::default()).unwrap();
+        gateway.add_device(VirtualDevice::new(10, vec![0x01]));
+        let mut client = Client::connect(&gateway);
+
+        gateway.inject_fault(Fault::Delay(Duration::from_millis(300)));
+        let sent = Instant::now();
+        client.tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        client.poll(Duration::from_millis(50), |_| false);
+        // the ack and the response are delayed, but the gateway is still responsive
+        let called = Instant::now();
+        assert_eq!(gateway.value(10), Some(vec![0x01]));
+        assert!(called.elapsed() < Duration::from_millis(100));
+        assert!(client.poll_until(|c| !c.events.is_empty()));
+        assert!(sent.elapsed() >= Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,197 @@
+// TP1 data link layer frames as transmitted on the twisted pair bus (See 3/2/2 2.2)
+
+use strum_macros::FromRepr;
+use crate::address::IndividualAddress;
+use crate::cemi::l_data::{Acknowledge, LData};
+use crate::dpt::DPT;
+use crate::group_event::GroupEvent;
+use crate::knxnet::KnxNetIpError;
+
+// control field, frame type and poll flag
+const STANDARD_FRAME: u8 = 0x80;
+const POLL_FRAME: u8 = 0x40;
+
+/// Group event together with the individual address of its sender
+pub type SourcedGroupEvent = (IndividualAddress, GroupEvent<Vec<u8>>);
+
+/// Short acknowledgement frame of the receivers, sent right after a data frame
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Acknowledgement {
+    Ack = 0xCC,
+    Nak = 0x0C,
+    Busy = 0xC0,
+    /// Some receivers answered with NAK, others with BUSY
+    NakBusy = 0x00,
+}
+
+impl Acknowledgement {
+    pub fn encode(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn decode(frame: &[u8]) -> Result<Acknowledgement, KnxNetIpError> {
+        if frame.len() != 1 {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        Acknowledgement::from_repr(frame[0]).ok_or(KnxNetIpError::Unknown)
+    }
+}
+
+/// Checksum of a frame, the inverted XOR of all its octets
+pub fn checksum(frame: &[u8]) -> u8 {
+    !frame.iter().fold(0, |cs, b| cs ^ b)
+}
+
+/// Encodes a group event sent from `source` as TP1 frame including the checksum, e.g. for `send_raw`.
+/// Events with an APDU longer than extended frames allow return `InvalidSize`.
+pub fn encode_group_event<T: DPT+Default>(ev: GroupEvent<T>, source: IndividualAddress) -> Result<Vec<u8>, KnxNetIpError> {
+    let mut l_data = ev.into_l_data();
+    if !l_data.valid_length() {
+        return Err(KnxNetIpError::InvalidSize)
+    }
+    l_data.source = source.to_u16();
+    let mut buf = vec![];
+    encode(&l_data, &mut buf);
+    Ok(buf)
+}
+
+/// Decodes a TP1 data frame, e.g. of a busmonitor or raw event, into its source address and group event.
+/// Valid frames not carrying group communication return `None`.
+pub fn decode_group_event(frame: &[u8]) -> Result<Option<SourcedGroupEvent>, KnxNetIpError> {
+    let l_data = decode(frame)?;
+    let source = IndividualAddress::from_u16(l_data.source);
+    Ok(GroupEvent::from_l_data(l_data).map(|ev| (source, ev)))
+}
+
+pub(crate) fn encode<D: DPT+Default>(l_data: &LData<D>, buf: &mut Vec<u8>) {
+    let start = buf.len();
+    encode_frame(l_data, buf);
+    let cs = checksum(&buf[start..]);
+    buf.push(cs);
+}
+
+pub(crate) fn decode(frame: &[u8]) -> Result<LData<Vec<u8>>, KnxNetIpError> {
+    if frame.len() < 8 {
+        return Err(KnxNetIpError::MessageTooShort(frame.len()))
+    }
+    if checksum(frame) != 0 {
+        return Err(KnxNetIpError::InvalidChecksum(frame[frame.len() - 1]))
+    }
+    decode_frame(&frame[..frame.len() - 1])
+}
+
+/// Encodes the frame without checksum, which is the layout used by EMI1 and EMI2 as well
+pub(crate) fn encode_frame<D: DPT+Default>(l_data: &LData<D>, buf: &mut Vec<u8>) {
+    // extended frames share the layout of cEMI frames, standard ones merge the length into the hop count octet
+    let mut cemi = vec![];
+    l_data.encode(&mut cemi);
+    // acknowledge request and confirmation flags do not exist on the bus
+    buf.push(cemi[0] & 0xFC);
+    if cemi[0] & STANDARD_FRAME != 0 {
+        buf.extend_from_slice(&cemi[2..6]);
+        buf.push((cemi[1] & 0xF0) | (cemi[6] & 0x0F));
+    } else {
+        buf.extend_from_slice(&cemi[1..7]);
+    }
+    buf.extend_from_slice(&cemi[7..]);
+}
+
+/// Decodes a frame without checksum
+pub(crate) fn decode_frame(frame: &[u8]) -> Result<LData<Vec<u8>>, KnxNetIpError> {
+    if frame.len() < 7 {
+        return Err(KnxNetIpError::MessageTooShort(frame.len()))
+    }
+    if frame[0] & POLL_FRAME != 0 {
+        return Err(KnxNetIpError::NotImplemented)
+    }
+    // all frames on the bus are acknowledged by their receivers
+    let ctrl = frame[0] | Acknowledge::Acknowledge as u8;
+    let cemi = if frame[0] & STANDARD_FRAME != 0 {
+        if frame.len() != 7 + (frame[5] & 0x0F) as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        let mut cemi = vec![ctrl, frame[5] & 0xF0];
+        cemi.extend_from_slice(&frame[1..5]);
+        cemi.push(frame[5] & 0x0F);
+        cemi.extend_from_slice(&frame[6..]);
+        cemi
+    } else {
+        if frame.len() < 8 {
+            return Err(KnxNetIpError::MessageTooShort(frame.len()))
+        }
+        if frame.len() != 8 + frame[6] as usize {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        let mut cemi = vec![ctrl];
+        cemi.extend_from_slice(&frame[1..]);
+        cemi
+    };
+    let mut l_data = LData::<Vec<u8>>::default();
+    l_data.decode(&cemi)?;
+    Ok(l_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address::IndividualAddress;
+    use crate::cemi::l_data::{FrameType, LData, Priority, Repetition};
+    use crate::group_event::{GroupEvent, GroupEventType};
+    use crate::knxnet::KnxNetIpError;
+    use crate::tp1::{checksum, decode, decode_group_event, encode, encode_group_event, Acknowledgement};
+
+    #[test]
+    fn t_tp1_standard_frame() {
+        let frame = encode_group_event(GroupEvent{address: 0x0A01, event_type: GroupEventType::GroupValueWrite, data: true},
+            IndividualAddress::new(1, 1, 10)).unwrap();
+        assert_eq!(frame, vec![0xBC, 0x11, 0x0A, 0x0A, 0x01, 0xE1, 0x00, 0x81, 0x33]);
+        assert_eq!(checksum(&frame), 0);
+
+        let (source, ev) = decode_group_event(&frame).unwrap().unwrap();
+        assert_eq!(source, IndividualAddress::new(1, 1, 10));
+        assert_eq!((ev.address, ev.event_type, ev.data), (0x0A01, GroupEventType::GroupValueWrite, vec![0x01]));
+
+        // repeated frame of system priority, raw data is always encoded in long form
+        let l_data = decode(&[0x90, 0x11, 0x0A, 0x0A, 0x01, 0xE2, 0x00, 0x80, 0x01, 0x1C]).unwrap();
+        assert_eq!((l_data.repetition, l_data.priority), (Repetition::Repeat, Priority::System));
+        let mut buf = vec![];
+        encode(&l_data, &mut buf);
+        assert_eq!(buf, vec![0x90, 0x11, 0x0A, 0x0A, 0x01, 0xE2, 0x00, 0x80, 0x01, 0x1C]);
+    }
+
+    #[test]
+    fn t_tp1_extended_frame() {
+        let l_data = LData::<Vec<u8>>{source: 0x110A, destination: 0x0A01, data: crate::cemi::apdu::Apdu::GroupValueWrite(vec![0x41; 20]), ..LData::default()};
+        let mut frame = vec![];
+        encode(&l_data, &mut frame);
+        assert_eq!(&frame[..9], &[0x3C, 0xE0, 0x11, 0x0A, 0x0A, 0x01, 0x15, 0x00, 0x80]);
+        assert_eq!(frame.len(), 30);
+        assert_eq!(checksum(&frame), 0);
+        let decoded = decode(&frame).unwrap();
+        assert_eq!(decoded.frame_type, FrameType::Extended);
+        assert_eq!(decoded.data, l_data.data);
+
+        let ev = GroupEvent{address: 0x0A01, event_type: GroupEventType::GroupValueWrite, data: vec![0x41; 300]};
+        assert_eq!(encode_group_event(ev, IndividualAddress::new(1, 1, 10)), Err(KnxNetIpError::InvalidSize));
+    }
+
+    #[test]
+    fn t_tp1_invalid_frames() {
+        assert_eq!(decode(&[0xBC, 0x11, 0x0A, 0x0A, 0x01, 0xE1, 0x00, 0x81, 0x34]).err(), Some(KnxNetIpError::InvalidChecksum(0x34)));
+        assert_eq!(decode(&[0xBC, 0x11, 0x0A, 0x0A, 0x01, 0xE1, 0x00]).err(), Some(KnxNetIpError::MessageTooShort(7)));
+        // length nibble does not match the frame
+        assert_eq!(decode(&[0xBC, 0x11, 0x0A, 0x0A, 0x01, 0xE2, 0x00, 0x81, 0x30]).err(), Some(KnxNetIpError::InvalidSize));
+        // individually addressed frames are no group events
+        assert!(decode_group_event(&[0xB0, 0x11, 0x0A, 0x11, 0x01, 0x60, 0x80, 0xA4]).unwrap().is_none());
+    }
+
+    #[test]
+    fn t_tp1_acknowledgement() {
+        assert_eq!(Acknowledgement::decode(&[0xCC]), Ok(Acknowledgement::Ack));
+        assert_eq!(Acknowledgement::decode(&[0x0C]), Ok(Acknowledgement::Nak));
+        assert_eq!(Acknowledgement::decode(&[0xC0]), Ok(Acknowledgement::Busy));
+        assert_eq!(Acknowledgement::Busy.encode(), 0xC0);
+        assert!(Acknowledgement::decode(&[0x55]).is_err());
+        assert!(Acknowledgement::decode(&[0xCC, 0xCC]).is_err());
+    }
+}
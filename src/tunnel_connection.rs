@@ -5,27 +5,32 @@
 // get next time event
 // handle next time event
 // handle received data -> returns a cemi or none
-// send data with future<Result<>>
+// send data -> returns a token, the delivery result is reported with it
 
 
 
 use std::cmp::{min, PartialEq};
 use std::collections::VecDeque;
+use std::net::SocketAddrV4;
 use std::ops::Add;
 use std::time::{Duration, Instant};
 use strum_macros::FromRepr;
-use crate::cemi::apdu::Apdu;
 use crate::dpt::DPT;
-use crate::cemi::l_data::LData;
+use crate::address::IndividualAddress;
+use crate::busmonitor_event::BusmonitorEvent;
+use crate::cemi::l_data::{Confirmation, LData, MAX_STANDARD_APDU_LENGTH};
 use crate::cemi::Message;
-use crate::group_event::{GroupEvent, GroupEventType};
+use crate::delivery_event::{DeliveryEvent, DeliveryResult, SendToken};
+use crate::feature_event::{FeatureEvent, FeatureEventType, InterfaceFeature};
+use crate::group_event::GroupEvent;
+use crate::raw_event::{RawEvent, RawEventType};
 use crate::knxnet;
 use crate::knxnet::connectionstate::ConnectionstateRequest;
-use crate::knxnet::{cri, KnxNetIpError, Service};
+use crate::knxnet::{cri, crd, KnxNetIpError, Service};
 use crate::knxnet::disconnect::{DisconnectRequest, DisconnectResponse};
 use crate::knxnet::hpai::{HPAI, Protocol};
 use crate::knxnet::status::StatusCode;
-use crate::knxnet::tunnel::TunnelAck;
+use crate::knxnet::tunnel::{TunnelAck, TunnelRequest, TunnellingFeature};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct TunnelConnectionConfig {
@@ -33,6 +38,23 @@ pub struct TunnelConnectionConfig {
     response_timeout: Duration,
     heartbeat_response_timeout: Duration,
     heartbeat_interval: Duration,
+    individual_address: Option<IndividualAddress>,
+    mode: TunnelMode,
+    max_apdu_length: u16,
+    confirmation_timeout: Duration,
+}
+
+/// Layer the tunnel connects to on the server
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum TunnelMode {
+    /// Group communication through the data link layer
+    #[default]
+    LinkLayer,
+    /// Passive monitoring of all frames on the bus, including acknowledgements and corrupted frames.
+    /// Nothing can be sent to the bus in this mode.
+    Busmonitor,
+    /// Raw frames are sent and received verbatim with `send_raw` and `get_raw_event`, group events are not available
+    Raw,
 }
 
 impl Default for TunnelConnectionConfig {
@@ -42,15 +64,81 @@ impl Default for TunnelConnectionConfig {
             heartbeat_interval: Duration::from_secs(60),
             response_timeout: Duration::from_millis(1500),
             heartbeat_response_timeout: Duration::from_secs(10),
+            individual_address: None,
+            mode: TunnelMode::LinkLayer,
+            // standard frames only, until the server reports a longer max APDU length
+            max_apdu_length: MAX_STANDARD_APDU_LENGTH,
+            // See 3/8/4 2.6
+            confirmation_timeout: Duration::from_secs(3),
         }
     }
 }
 
+impl TunnelConnectionConfig {
+    /// Request a specific individual address for the tunnel, e.g. the one reserved in the ETS project.
+    /// Otherwise the server assigns any of its free tunnel addresses.
+    pub fn with_individual_address(mut self, address: IndividualAddress) -> TunnelConnectionConfig {
+        self.individual_address = Some(address);
+        self
+    }
+
+    pub fn with_mode(mut self, mode: TunnelMode) -> TunnelConnectionConfig {
+        self.mode = mode;
+        self
+    }
+
+    /// Max APDU length of the server, up to 254 for servers supporting extended frames.
+    /// It is updated by responses to `get_feature(InterfaceFeature::MaxApduLength)`.
+    pub fn with_max_apdu_length(mut self, length: u16) -> TunnelConnectionConfig {
+        self.max_apdu_length = length;
+        self
+    }
+
+    /// Time the server has to answer a request, connect requests are repeated after it elapsed
+    pub fn with_response_timeout(mut self, timeout: Duration) -> TunnelConnectionConfig {
+        self.response_timeout = timeout;
+        self
+    }
+
+    /// Time the server has to confirm a frame after acknowledging it, before `DeliveryResult::NotConfirmed` is reported
+    pub fn with_confirmation_timeout(mut self, timeout: Duration) -> TunnelConnectionConfig {
+        self.confirmation_timeout = timeout;
+        self
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Default)]
 struct OutMessage {
     data: Vec<u8>,
     need_ack: bool,
+    // over TCP tunnelling requests are not acknowledged, they only have to be sent in order
+    complete_on_send: bool,
+    // tunnelling requests and acks go to the data endpoint, everything else to the control endpoint
+    data_endpoint: bool,
     retried: u8,
+    // group events sent by the application report their delivery result
+    token: Option<SendToken>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AwaitingConfirmation {
+    token: SendToken,
+    l_data: LData<Vec<u8>>,
+    due: Instant,
+}
+
+// delivery results which are never collected must not pile up
+const MAX_DELIVERY_EVENTS: usize = 1024;
+// neither must frames dropped while disconnected which are never taken over by another connection
+const MAX_DROPPED_L_DATA: usize = 1024;
+
+/// Endpoint of the server outbound data needs to be sent to. Over TCP everything is sent on the stream.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Control endpoint the tunnel was established with
+    Control,
+    /// Data endpoint assigned by the server in its connect response
+    Data(SocketAddrV4),
 }
 
 #[derive(FromRepr, Debug, Copy, Clone, PartialEq, Default)]
@@ -72,8 +160,19 @@ pub struct TunnelConnection {
     inbound_seq: u8,
     out_queue: VecDeque<OutMessage>,
     ack_queue: VecDeque<OutMessage>,
-    current_ack: Vec<u8>,
+    current: Vec<u8>,
     message_pending: bool,
+    feature_events: VecDeque<FeatureEvent>,
+    bus_connected: Option<bool>,
+    individual_address: Option<IndividualAddress>,
+    server_data_endpoint: Option<SocketAddrV4>,
+    busmonitor_events: VecDeque<BusmonitorEvent>,
+    raw_events: VecDeque<RawEvent>,
+    dropped_l_data: Vec<LData<Vec<u8>>>,
+    max_apdu_length: u16,
+    next_token: u32,
+    awaiting_confirmation: VecDeque<AwaitingConfirmation>,
+    delivery_events: VecDeque<DeliveryEvent>,
     next_resent: Instant,
     next_timeout: Instant,
     next_heartbeat: Instant,
@@ -83,9 +182,10 @@ pub struct TunnelConnection {
 impl TunnelConnection {
     /// Create an TunnelConnReq
     pub fn new(ipv4: [u8;4], port: u16, config: TunnelConnectionConfig) -> TunnelConnection {
-        let host_info = HPAI::new(Protocol::Udp4Protocol, ipv4, port);
-
+        TunnelConnection::new_with_host_info(HPAI::new(Protocol::Udp4Protocol, ipv4, port), config)
+    }
 
+    fn new_with_host_info(host_info: HPAI, config: TunnelConnectionConfig) -> TunnelConnection {
         let mut con = TunnelConnection{
             state: TunnelConnectionState::Disconnected,
             awaiting_heartbeat_response: false,
@@ -100,43 +200,223 @@ impl TunnelConnection {
             ack_queue: VecDeque::from(vec![]),
             host_info,
             message_pending: true,
-            current_ack: vec![],
+            current: vec![],
+            feature_events: VecDeque::new(),
+            bus_connected: None,
+            individual_address: None,
+            server_data_endpoint: None,
+            busmonitor_events: VecDeque::new(),
+            raw_events: VecDeque::new(),
+            dropped_l_data: vec![],
+            max_apdu_length: config.max_apdu_length,
+            next_token: 0,
+            awaiting_confirmation: VecDeque::new(),
+            delivery_events: VecDeque::new(),
         };
         con.send_connect_request();
         con
     }
 
-    pub fn send<T: DPT+Default>(&mut self, ev: GroupEvent<T>) ->() {
-        let msg = match ev.event_type {
-            GroupEventType::GroupValueRead => Message::<T>::LDataReq(vec![], LData::<T>{data: Apdu::GroupValueRead, destination: ev.address , ..LData::<T>::default()}),
-            GroupEventType::GroupValueWrite => Message::<T>::LDataReq(vec![], LData::<T>{data: Apdu::GroupValueWrite(ev.data), destination: ev.address , ..LData::<T>::default()}),
-            GroupEventType::GroupValueResponse => Message::<T>::LDataReq(vec![], LData::<T>{data: Apdu::GroupValueResponse(ev.data), destination: ev.address , ..LData::<T>::default()}),
-        };
+    /// Create a tunnel connection for servers behind a NAT or port forwarding, which send their responses to the
+    /// address and port the requests came from instead of the local endpoint
+    pub fn new_nat(config: TunnelConnectionConfig) -> TunnelConnection {
+        TunnelConnection::new_with_host_info(HPAI::new(Protocol::Udp4Protocol, [0, 0, 0, 0], 0), config)
+    }
+
+    /// Create a tunnel connection over an already established TCP stream to the server.
+    /// Inbound data of the stream needs to be split into frames using `knxnet::stream::StreamReassembler`.
+    pub fn new_tcp(config: TunnelConnectionConfig) -> TunnelConnection {
+        // the server answers on the same stream, so the route back endpoint is used
+        TunnelConnection::new_with_host_info(HPAI::new(Protocol::Tcp4Protocol, [0, 0, 0, 0], 0), config)
+    }
+
+    /// Send a group event to the bus, which is only possible in link layer mode. Events with an APDU longer than
+    /// `max_apdu_length` are dropped, longer ones than standard frames allow are sent in extended frames.
+    /// The returned token identifies the event in its delivery result, see `get_delivery_event`.
+    pub fn send<T: DPT+Default>(&mut self, ev: GroupEvent<T>) -> SendToken {
+        let token = SendToken(self.next_token);
+        self.next_token = self.next_token.wrapping_add(1);
+        self.send_l_data(ev.into_l_data(), Some(token));
+        token
+    }
+
+    pub(crate) fn send_l_data<T: DPT+Default>(&mut self, l_data: LData<T>, token: Option<SendToken>) {
+        // frames the server cannot handle are dropped
+        if self.config.mode != TunnelMode::LinkLayer || !l_data.valid_length() || l_data.data.length() > self.max_apdu_length {
+            self.report_delivery(token, DeliveryResult::Dropped);
+            return
+        }
+        self.send_message(Message::<T>::LDataReq(vec![], l_data), token);
+    }
+
+    /// Removes the frames not yet acknowledged by the server, including the ones dropped since the connection was lost,
+    /// e.g. to send them through another connection
+    pub(crate) fn take_pending_l_data(&mut self) -> Vec<LData<Vec<u8>>> {
+        let mut pending = std::mem::take(&mut self.dropped_l_data);
+        let queued = self.remove_l_data_requests();
+        if !queued.is_empty() && !self.out_queue.is_empty() {
+            // the first message might have been removed, so the new first one is due to be sent
+            self.message_pending = true;
+            self.next_timeout = Instant::now().add(self.config.response_timeout);
+        }
+        pending.extend(queued);
+        pending
+    }
+
+    fn remove_l_data_requests(&mut self) -> Vec<LData<Vec<u8>>> {
+        let mut removed = vec![];
+        let mut tokens = vec![];
+        self.out_queue.retain(|msg| match Service::<Vec<u8>>::decoded(&msg.data) {
+            Ok(Service::TunnelRequest(TunnelRequest{data: Message::LDataReq(_, l_data), ..})) => {
+                removed.push(l_data);
+                tokens.extend(msg.token);
+                false
+            }
+            _ => true
+        });
+        tokens.into_iter().for_each(|token| self.report_delivery(Some(token), DeliveryResult::Dropped));
+        removed
+    }
+
+    /// Send a raw TP1 frame to the bus as it is, which is only possible in raw mode
+    pub fn send_raw(&mut self, frame: Vec<u8>) {
+        if self.config.mode != TunnelMode::Raw {
+            return
+        }
+        self.send_message(Message::<()>::LRawReq(vec![], frame), None);
+    }
+
+    fn send_message<T: DPT+Default>(&mut self, msg: Message<T>, token: Option<SendToken>) {
         let req = Service::TunnelRequest(knxnet::tunnel::TunnelRequest{
             channel: self.channel,
             seq: self.outbound_seq,
             data: msg,
         });
-        self.outbound_seq += 1;
-        self.push_out_message(OutMessage{data: req.encoded(), need_ack: true, retried:0});
+        self.outbound_seq = self.outbound_seq.wrapping_add(1);
+        self.push_out_message(OutMessage{data: req.encoded(), need_ack: true, complete_on_send: self.tcp(), data_endpoint: true, retried:0, token});
     }
 
-    pub fn get_outbound_data(&mut self) -> Option<&[u8]> {
+    /// Request the value of an interface feature of the server, the response is returned by `get_feature_event`
+    pub fn get_feature(&mut self, feature: InterfaceFeature) {
+        self.send_feature(Service::TunnellingFeatureGet, feature, vec![]);
+    }
+
+    /// Set an interface feature of the server, the response is returned by `get_feature_event`
+    pub fn set_feature(&mut self, feature: InterfaceFeature, data: Vec<u8>) {
+        self.send_feature(Service::TunnellingFeatureSet, feature, data);
+    }
+
+    fn send_feature(&mut self, service: fn(TunnellingFeature) -> Service<()>, feature: InterfaceFeature, data: Vec<u8>) {
+        let req = service(TunnellingFeature{
+            channel: self.channel,
+            seq: self.outbound_seq,
+            feature: feature as u8,
+            return_code: 0,
+            data,
+        });
+        self.outbound_seq = self.outbound_seq.wrapping_add(1);
+        self.push_out_message(OutMessage{data: req.encoded(), need_ack: true, complete_on_send: self.tcp(), data_endpoint: true, retried:0, token: None});
+    }
+
+    /// Next feature response or info received from the server
+    pub fn get_feature_event(&mut self) -> Option<FeatureEvent> {
+        self.feature_events.pop_front()
+    }
+
+    /// Next frame received in busmonitor mode
+    pub fn get_busmonitor_event(&mut self) -> Option<BusmonitorEvent> {
+        self.busmonitor_events.pop_front()
+    }
+
+    /// Next frame or confirmation received in raw mode
+    pub fn get_raw_event(&mut self) -> Option<RawEvent> {
+        self.raw_events.pop_front()
+    }
+
+    /// Next delivery result of a group event passed to `send`
+    pub fn get_delivery_event(&mut self) -> Option<DeliveryEvent> {
+        self.delivery_events.pop_front()
+    }
+
+    /// Max APDU length of the server as configured or reported by feature responses
+    pub fn max_apdu_length(&self) -> u16 {
+        self.max_apdu_length
+    }
+
+    /// Whether the server is connected to the KNX bus, as far as reported by feature responses or infos
+    pub fn bus_connected(&self) -> Option<bool> {
+        self.bus_connected
+    }
+
+    /// Has to be called when the TCP stream was closed. All pending messages are dropped and
+    /// the connection is re-established once `handle_stream_opened` is called for a new stream.
+    pub fn handle_stream_closed(&mut self) {
+        self.individual_address = None;
+        self.remove_l_data_requests();
+        self.out_queue.clear();
+        self.ack_queue.clear();
+        self.fail_awaiting_confirmation();
+        self.message_pending = false;
+        self.awaiting_heartbeat_response = false;
+        self.state = TunnelConnectionState::Disconnected;
+    }
+
+    /// Has to be called when a new TCP stream to the server was established after the previous one was closed
+    pub fn handle_stream_opened(&mut self) {
+        if self.state == TunnelConnectionState::Disconnected {
+            self.send_connect_request();
+        }
+    }
+
+    fn tcp(&self) -> bool {
+        self.host_info.protocol() == Protocol::Tcp4Protocol
+    }
+
+    // NAT and TCP connections let the server answer to the address the requests came from
+    fn route_back(&self) -> bool {
+        self.host_info.address() == [0, 0, 0, 0]
+    }
+
+    /// Data which needs to be sent to the returned endpoint of the server
+    pub fn get_outbound_data(&mut self) -> Option<(&[u8], Endpoint)> {
         if !self.ack_queue.is_empty(){
-            self.current_ack = self.ack_queue.pop_front().unwrap().data;
-            return Some(&self.current_ack)
+            let msg = self.ack_queue.pop_front().unwrap();
+            let endpoint = self.endpoint(&msg);
+            self.current = msg.data;
+            return Some((&self.current, endpoint))
+        }
+        if self.message_pending && !self.out_queue.is_empty() && self.out_queue[0].complete_on_send {
+            let endpoint = self.endpoint(&self.out_queue[0]);
+            self.current = std::mem::take(&mut self.out_queue[0].data);
+            // over TCP the frame is considered acknowledged once it is sent
+            if let Some(token) = self.out_queue[0].token {
+                let data = std::mem::take(&mut self.current);
+                self.await_confirmation(token, &data);
+                self.current = data;
+            }
+            self.remove_first_message();
+            return Some((&self.current, endpoint))
         }
         if self.message_pending && !self.out_queue.is_empty() {
             self.message_pending = false;
             self.next_resent = Instant::now().add(self.config.resent_interval);
             self.out_queue[0].retried += 1;
             //println!("Data {:?} to be send {}", &self.out_queue[0].data, self.out_queue[0].retried);
-            return Some(&self.out_queue[0].data)
+            let endpoint = self.endpoint(&self.out_queue[0]);
+            return Some((&self.out_queue[0].data, endpoint))
         }
 
         return None
     }
 
+    fn endpoint(&self, msg: &OutMessage) -> Endpoint {
+        match self.server_data_endpoint {
+            Some(address) if msg.data_endpoint => Endpoint::Data(address),
+            // servers answering with a route back endpoint expect data on the control endpoint
+            _ => Endpoint::Control,
+        }
+    }
+
     fn remove_first_message(&mut self){
         self.out_queue.pop_front();
         if !self.out_queue.is_empty(){
@@ -154,20 +434,31 @@ impl TunnelConnection {
     }
 
     pub fn get_next_time_event(&self) -> Instant{
-        return min(self.next_heartbeat, min(self.next_resent, self.next_timeout))
+        let next = min(self.next_heartbeat, min(self.next_resent, self.next_timeout));
+        match self.awaiting_confirmation.front() {
+            Some(awaiting) => min(next, awaiting.due),
+            None => next,
+        }
     }
 
     pub fn connected(&self) -> bool {return self.state == TunnelConnectionState::Connected}
 
+    /// Individual address the server assigned to the tunnel while connected
+    pub fn individual_address(&self) -> Option<IndividualAddress> {
+        self.individual_address
+    }
+
     pub fn handle_time_events(&mut self) -> () {
         if self.next_timeout < Instant::now() && !self.out_queue.is_empty() {
             match self.state {
                 TunnelConnectionState::Connecting => {
-                    // we might want to handle the connect Future here to return an error
-                    panic!("Failed to initialize tunnel connection")
+                    // the server did not answer, so the connection is requested again until it is reachable
+                    self.send_connect_request();
                 }
                 TunnelConnectionState::Disconnected | TunnelConnectionState::Connected => {
                     // outbound message timed out so skip sending it
+                    let token = self.out_queue[0].token;
+                    self.report_delivery(token, DeliveryResult::NotAcknowledged);
                     self.remove_first_message();
                 }
                 // in case we don't get a response for disconnection connection is probably already lost
@@ -188,7 +479,12 @@ impl TunnelConnection {
             }
             self.next_heartbeat += self.config.heartbeat_interval;
         }
-        if self.next_resent < Instant::now() && !self.out_queue.is_empty(){
+        while self.awaiting_confirmation.front().is_some_and(|awaiting| awaiting.due < Instant::now()) {
+            let token = self.awaiting_confirmation.pop_front().map(|awaiting| awaiting.token);
+            self.report_delivery(token, DeliveryResult::NotConfirmed);
+        }
+        // TCP takes care of retransmissions itself
+        if self.next_resent < Instant::now() && !self.out_queue.is_empty() && !self.tcp() {
             // set message back to due to send
             self.message_pending = true
         }
@@ -211,7 +507,10 @@ impl TunnelConnection {
                                 status: StatusCode::NoError,
                             }).encoded(),
                         need_ack: false,
+                        complete_on_send: false,
+                        data_endpoint: false,
                         retried: 0,
+                        token: None,
                     });
                     self.state = TunnelConnectionState::Disconnected;
                     self.send_connect_request();
@@ -231,6 +530,14 @@ impl TunnelConnection {
                     self.inbound_seq = 0;
                     self.outbound_seq = 0;
                     self.channel = connect.channel;
+                    if let crd::ConnectionRespType::TunnelConnection{address} = connect.connection_type {
+                        self.individual_address = Some(address);
+                    }
+                    // behind a NAT the data endpoint is the private address of the server, which is not reachable
+                    if !self.route_back() && connect.data.address() != [0, 0, 0, 0] && connect.data.port() != 0 {
+                        self.server_data_endpoint = Some(SocketAddrV4::new(connect.data.address().into(), connect.data.port()));
+                    }
+                    self.dropped_l_data.clear();
                     self.handle_outbount_send();
                     self.state = TunnelConnectionState::Connected;
                 }
@@ -249,65 +556,143 @@ impl TunnelConnection {
                 None
             }
             Service::TunnelAck(tack) => {
-                if tack.status == StatusCode::NoError {
+                if tack.status == StatusCode::NoError && self.acknowledges_first_message(&tack) {
+                    if let Some((Some(token), data)) = self.out_queue.front().map(|msg| (msg.token, msg.data.clone())) {
+                        self.await_confirmation(token, &data);
+                    }
                     self.handle_outbount_send()
                 }
                 None
             },
             Service::TunnelRequest(treq) => {
-                //only messages with the expected seq or one less should be accepted (and thereby acked). See 03/08/04 Tunneling 2.6
-                if !(self.inbound_seq == treq.seq || self.inbound_seq == treq.seq.wrapping_add(1)) || self.channel != treq.channel{
-                    println!("Discarding due to not matching seq {}, channel {}", self.inbound_seq, self.channel);
+                if !self.accept_inbound(treq.channel, treq.seq) {
                     return None
                 }
-                self.push_out_message(OutMessage{
-                    data: Service::<()>::TunnelAck(
-                        TunnelAck{
-                            seq: treq.seq,
-                            channel: treq.channel,
-                            status: StatusCode::NoError,
-                        }).encoded(),
-                    need_ack: false,
-                    retried: 0,
-                });
-                //println!("Outqueue size {}, pending {}", self.out_queue.len(), self.message_pending);
-                self.inbound_seq = treq.seq.wrapping_add(1);
-
                 match treq.data {
-                    Message::LDataInd(i, d) => {
-                        match d.data {
-                            Apdu::GroupValueRead => {
-                                Some(GroupEvent::<Vec<u8>> {
-                                    data: vec![],
-                                    address: d.destination,
-                                    event_type: GroupEventType::GroupValueRead,
-                                })
-                            }
-                            Apdu::GroupValueResponse(data) => {
-                                Some(GroupEvent::<Vec<u8>> {
-                                    data,
-                                    address: d.destination,
-                                    event_type: GroupEventType::GroupValueResponse,
-                                })
-                            }
-                            Apdu::GroupValueWrite(data) => {
-                                Some(GroupEvent::<Vec<u8>> {
-                                    data,
-                                    address: d.destination,
-                                    event_type: GroupEventType::GroupValueWrite,
-                                })
-                            }
-                            _ => None
-                        }
+                    Message::LDataInd(_, d) => GroupEvent::from_l_data(d),
+                    Message::LDataCon(_, d) => {
+                        self.handle_confirmation(d);
+                        None
+                    }
+                    Message::LBusmonInd(info, frame) => {
+                        self.busmonitor_events.push_back(BusmonitorEvent::from_busmon(info, frame));
+                        None
+                    }
+                    Message::LRawInd(_, frame) => {
+                        self.raw_events.push_back(RawEvent{event_type: RawEventType::Indication, frame});
+                        None
+                    }
+                    Message::LRawCon(_, frame) => {
+                        self.raw_events.push_back(RawEvent{event_type: RawEventType::Confirmation, frame});
+                        None
                     }
                     _ => None
                 }
             },
+            Service::TunnellingFeatureResponse(f) => {
+                self.handle_feature(FeatureEventType::Response, f);
+                None
+            }
+            Service::TunnellingFeatureInfo(f) => {
+                self.handle_feature(FeatureEventType::Info, f);
+                None
+            }
             _ => None,
         }
 
     }
 
+    // checks the sequence of requests sent by the server and acknowledges them
+    fn accept_inbound(&mut self, channel: u8, seq: u8) -> bool {
+        //only messages with the expected seq or one less should be accepted (and thereby acked). See 03/08/04 Tunneling 2.6
+        if !(self.inbound_seq == seq || self.inbound_seq == seq.wrapping_add(1)) || self.channel != channel{
+            println!("Discarding due to not matching seq {}, channel {}", self.inbound_seq, self.channel);
+            return false
+        }
+        if !self.tcp() {
+            self.push_out_message(OutMessage{
+                data: Service::<()>::TunnelAck(
+                    TunnelAck{
+                        seq,
+                        channel,
+                        status: StatusCode::NoError,
+                    }).encoded(),
+                need_ack: false,
+                complete_on_send: false,
+                data_endpoint: true,
+                retried: 0,
+                token: None,
+            });
+        }
+        //println!("Outqueue size {}, pending {}", self.out_queue.len(), self.message_pending);
+        self.inbound_seq = seq.wrapping_add(1);
+        true
+    }
+
+    // stray, late or duplicate acks must not complete the request currently waiting for its own ack
+    fn acknowledges_first_message(&self, tack: &TunnelAck) -> bool {
+        // tunnelling requests are the only ones sent to the data endpoint, their connection header follows the header
+        self.out_queue.front().is_some_and(|msg| msg.data_endpoint && msg.data.len() > 8
+            && msg.data[7] == tack.channel && msg.data[8] == tack.seq)
+    }
+
+    fn await_confirmation(&mut self, token: SendToken, data: &[u8]) {
+        if let Ok(Service::TunnelRequest(TunnelRequest{data: Message::LDataReq(_, l_data), ..})) = Service::<Vec<u8>>::decoded(data) {
+            self.awaiting_confirmation.push_back(AwaitingConfirmation{
+                token,
+                l_data,
+                due: Instant::now().add(self.config.confirmation_timeout),
+            });
+        }
+    }
+
+    // the confirmation repeats the frame, the server might have filled in the source address though
+    fn handle_confirmation(&mut self, l_data: LData<Vec<u8>>) {
+        let position = self.awaiting_confirmation.iter().position(|awaiting| {
+            awaiting.l_data.destination == l_data.destination
+                && awaiting.l_data.destination_address_type == l_data.destination_address_type
+                && awaiting.l_data.data == l_data.data
+        });
+        if let Some(awaiting) = position.and_then(|i| self.awaiting_confirmation.remove(i)) {
+            let result = match l_data.confirmation {
+                Confirmation::NoError => DeliveryResult::Confirmed,
+                Confirmation::Error => DeliveryResult::Failed,
+            };
+            self.report_delivery(Some(awaiting.token), result);
+        }
+    }
+
+    fn fail_awaiting_confirmation(&mut self) {
+        while let Some(awaiting) = self.awaiting_confirmation.pop_front() {
+            self.report_delivery(Some(awaiting.token), DeliveryResult::NotConfirmed);
+        }
+    }
+
+    fn report_delivery(&mut self, token: Option<SendToken>, result: DeliveryResult) {
+        let Some(token) = token else {
+            return
+        };
+        if self.delivery_events.len() >= MAX_DELIVERY_EVENTS {
+            self.delivery_events.pop_front();
+        }
+        self.delivery_events.push_back(DeliveryEvent{token, result});
+    }
+
+    fn handle_feature(&mut self, event_type: FeatureEventType, f: TunnellingFeature) {
+        if !self.accept_inbound(f.channel, f.seq) {
+            return
+        }
+        if let Some(event) = FeatureEvent::from_tunnelling_feature(event_type, f) {
+            if let Some(connected) = event.bus_connected() {
+                self.bus_connected = Some(connected);
+            }
+            if let Some(length) = event.max_apdu_length() {
+                self.max_apdu_length = length;
+            }
+            self.feature_events.push_back(event);
+        }
+    }
+
     fn push_out_message(&mut self, msg: OutMessage)  {
         if !msg.need_ack {
             return self.ack_queue.push_back(msg)
@@ -330,7 +715,10 @@ impl TunnelConnection {
         self.push_out_message(OutMessage {
             data: req.encoded(),
             need_ack: true,
+            complete_on_send: false,
+            data_endpoint: false,
             retried: 0,
+            token: None,
         });
     }
 
@@ -338,20 +726,39 @@ impl TunnelConnection {
         let tunnel_request: Service<()> = Service::ConnectRequest(crate::knxnet::connect::ConnectRequest{
             data: self.host_info,
             control: self.host_info,
-            connection_type: cri::ConnectionReqType::TunnelConnection {layer: cri::TunnelingLayer::TunnelLinkLayer}
+            connection_type: cri::ConnectionReqType::TunnelConnection {
+                layer: match self.config.mode {
+                    TunnelMode::LinkLayer => cri::TunnelingLayer::TunnelLinkLayer,
+                    TunnelMode::Busmonitor => cri::TunnelingLayer::TunnelBusmon,
+                    TunnelMode::Raw => cri::TunnelingLayer::TunnelRaw,
+                },
+                address: self.config.individual_address,
+            }
         });
 
 
         let buf = tunnel_request.encoded();
+        // kept until the connection is established again, so they can be taken over by another connection
+        let dropped = self.remove_l_data_requests();
+        self.dropped_l_data.extend(dropped);
+        let excess = self.dropped_l_data.len().saturating_sub(MAX_DROPPED_L_DATA);
+        self.dropped_l_data.drain(..excess);
         self.out_queue.clear();
         self.ack_queue.clear();
+        self.fail_awaiting_confirmation();
         self.inbound_seq = 0;
         self.outbound_seq = 0;
+        self.bus_connected = None;
+        self.individual_address = None;
+        self.server_data_endpoint = None;
         self.state = TunnelConnectionState::Connecting;
         self.push_out_message(OutMessage{
             data: buf,
             need_ack: true,
+            complete_on_send: false,
+            data_endpoint: false,
             retried: 0,
+            token: None,
         });
     }
 
@@ -367,7 +774,342 @@ impl TunnelConnection {
         self.push_out_message(OutMessage{
             data: buf,
             need_ack: true,
+            complete_on_send: false,
+            data_endpoint: false,
             retried: 0,
+            token: None,
         });
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::address::IndividualAddress;
+    use crate::busmonitor_event::BusmonitorEvent;
+    use crate::raw_event::{RawEvent, RawEventType};
+    use crate::delivery_event::{DeliveryEvent, DeliveryResult};
+    use crate::feature_event::{FeatureEvent, FeatureEventType, InterfaceFeature, ReturnCode};
+    use crate::group_event::{GroupEvent, GroupEventType};
+    use std::net::SocketAddrV4;
+    use std::time::Duration;
+    use crate::tunnel_connection::{Endpoint, TunnelConnection, TunnelConnectionConfig, TunnelMode};
+
+    #[test]
+    fn t_tunnel_tcp() {
+        let mut tunnel = TunnelConnection::new_tcp(TunnelConnectionConfig::default());
+        // route back HPAIs for control and data endpoint
+        assert_eq!(tunnel.get_outbound_data().map(|(data, _)| data), Some(&[0x06, 0x10, 0x02, 0x05, 0x00, 0x1A,
+            0x08, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x08, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x04, 0x04, 0x02, 0x00][..]));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x04, 0x11, 0x0A]);
+        assert!(tunnel.connected());
+
+        // tunnelling requests are neither acknowledged nor waiting for an ack
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        tunnel.send(GroupEvent{address: 11, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[8]), Some(0));
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[8]), Some(1));
+        let ev = tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x15, 0x00, 0x00,
+            0x29, 0x00, 0xBC, 0xE0, 0x11, 0x01, 0x00, 0x0A, 0x01, 0x00, 0x00]).unwrap();
+        assert_eq!(ev.address, 10);
+        assert_eq!(tunnel.get_outbound_data(), None);
+
+        tunnel.handle_stream_closed();
+        assert!(!tunnel.connected());
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        tunnel.handle_stream_opened();
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[3]), Some(0x05));
+    }
+
+    #[test]
+    fn t_tunnel_feature() {
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default());
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        assert!(tunnel.connected());
+        assert_eq!(tunnel.bus_connected(), None);
+
+        tunnel.get_feature(InterfaceFeature::BusConnectionStatus);
+        assert_eq!(tunnel.get_outbound_data().map(|(data, _)| data), Some(&[0x06, 0x10, 0x04, 0x22, 0x00, 0x0C, 0x04, 0x15, 0x00, 0x00, 0x03, 0x00][..]));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        // the response is a request of the server which has to be acknowledged
+        assert!(tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x23, 0x00, 0x0D, 0x04, 0x15, 0x00, 0x00, 0x03, 0x00, 0x01]).is_none());
+        assert_eq!(tunnel.get_outbound_data().map(|(data, _)| data), Some(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00][..]));
+        assert_eq!(tunnel.get_feature_event(), Some(FeatureEvent{
+            event_type: FeatureEventType::Response,
+            feature: InterfaceFeature::BusConnectionStatus,
+            return_code: ReturnCode::Success,
+            data: vec![0x01],
+        }));
+        assert_eq!(tunnel.bus_connected(), Some(true));
+
+        tunnel.set_feature(InterfaceFeature::InterfaceFeatureInfoServiceEnable, vec![0x01]);
+        assert_eq!(tunnel.get_outbound_data().map(|(data, _)| data), Some(&[0x06, 0x10, 0x04, 0x24, 0x00, 0x0D, 0x04, 0x15, 0x01, 0x00, 0x08, 0x00, 0x01][..]));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00]);
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x23, 0x00, 0x0D, 0x04, 0x15, 0x01, 0x00, 0x08, 0xFB, 0x01]);
+        tunnel.get_outbound_data();
+        let ev = tunnel.get_feature_event().unwrap();
+        assert_eq!(ev.return_code, ReturnCode::AccessReadOnly);
+        assert!(!ev.success());
+
+        // the KNX line went down
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x25, 0x00, 0x0D, 0x04, 0x15, 0x02, 0x00, 0x03, 0x00, 0x00]);
+        assert_eq!(tunnel.get_outbound_data().map(|(data, _)| data), Some(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x02, 0x00][..]));
+        assert_eq!(tunnel.get_feature_event().map(|ev| ev.event_type), Some(FeatureEventType::Info));
+        assert_eq!(tunnel.bus_connected(), Some(false));
+        assert_eq!(tunnel.get_feature_event(), None);
+    }
+
+    #[test]
+    fn t_tunnel_individual_address() {
+        let config = TunnelConnectionConfig::default().with_individual_address(IndividualAddress::new(1, 1, 20));
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, config);
+        // extended CRI with the requested address
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[22..].to_vec()), Some(vec![0x06, 0x04, 0x02, 0x00, 0x11, 0x14]));
+        assert_eq!(tunnel.individual_address(), None);
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x14]);
+        assert_eq!(tunnel.individual_address(), Some(IndividualAddress::new(1, 1, 20)));
+
+        // without a requested address the standard CRI is used
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default());
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[22..].to_vec()), Some(vec![0x04, 0x04, 0x02, 0x00]));
+    }
+
+    #[test]
+    fn t_tunnel_busmonitor() {
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default().with_mode(TunnelMode::Busmonitor));
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[22..].to_vec()), Some(vec![0x04, 0x04, 0x80, 0x00]));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        assert!(tunnel.connected());
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(tunnel.get_outbound_data(), None);
+
+        // group value write with status and timestamp, followed by its acknowledgement
+        assert!(tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x1C, 0x04, 0x15, 0x00, 0x00,
+            0x2B, 0x07, 0x03, 0x01, 0x02, 0x04, 0x02, 0x12, 0x34,
+            0xBC, 0x11, 0x01, 0x00, 0x0A, 0xE1, 0x00, 0x81, 0x39]).is_none());
+        assert_eq!(tunnel.get_outbound_data().map(|(data, _)| data), Some(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00][..]));
+        assert_eq!(tunnel.get_busmonitor_event(), Some(BusmonitorEvent{
+            frame: vec![0xBC, 0x11, 0x01, 0x00, 0x0A, 0xE1, 0x00, 0x81, 0x39],
+            sequence_number: 2,
+            timestamp: Some(0x1234),
+            ..BusmonitorEvent::default()
+        }));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x10, 0x04, 0x15, 0x01, 0x00,
+            0x2B, 0x03, 0x03, 0x01, 0xAB, 0xCC]);
+        let ev = tunnel.get_busmonitor_event().unwrap();
+        assert!(ev.acknowledgement());
+        assert!(ev.frame_error && ev.parity_error && ev.lost);
+        assert_eq!(ev.sequence_number, 3);
+        assert_eq!(tunnel.get_busmonitor_event(), None);
+    }
+
+    #[test]
+    fn t_tunnel_raw() {
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default().with_mode(TunnelMode::Raw));
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[22..].to_vec()), Some(vec![0x04, 0x04, 0x04, 0x00]));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(tunnel.get_outbound_data(), None);
+
+        // frame with a wrong checksum
+        tunnel.send_raw(vec![0xBC, 0x11, 0x01, 0x00, 0x0A, 0xE1, 0x00, 0x81, 0x00]);
+        assert_eq!(tunnel.get_outbound_data().map(|(data, _)| data), Some(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x15, 0x00, 0x00,
+            0x10, 0x00, 0xBC, 0x11, 0x01, 0x00, 0x0A, 0xE1, 0x00, 0x81, 0x00][..]));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x15, 0x00, 0x00,
+            0x2F, 0x00, 0xBC, 0x11, 0x01, 0x00, 0x0A, 0xE1, 0x00, 0x81, 0x00]);
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x0D, 0x04, 0x15, 0x01, 0x00, 0x2D, 0x00, 0xCC]);
+        assert_eq!(tunnel.get_raw_event().map(|ev| ev.event_type), Some(RawEventType::Confirmation));
+        assert_eq!(tunnel.get_raw_event(), Some(RawEvent{event_type: RawEventType::Indication, frame: vec![0xCC]}));
+        assert_eq!(tunnel.get_raw_event(), None);
+    }
+
+    #[test]
+    fn t_tunnel_extended_frame() {
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default());
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        assert_eq!(tunnel.max_apdu_length(), 15);
+        // 14 character string of DPT 16 still fits into a standard frame
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: b"KNX is awesome".to_vec()});
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| (d[12], d[18])), Some((0xBE, 15)));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: vec![0; 20]});
+        assert_eq!(tunnel.get_outbound_data(), None);
+
+        tunnel.get_feature(InterfaceFeature::MaxApduLength);
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00]);
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x23, 0x00, 0x0E, 0x04, 0x15, 0x00, 0x00, 0x07, 0x00, 0x00, 0xFE]);
+        tunnel.get_outbound_data();
+        assert_eq!(tunnel.max_apdu_length(), 254);
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: vec![0; 20]});
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| (d[12], d[18], d.len())), Some((0x3E, 21, 41)));
+
+        let config = TunnelConnectionConfig::default().with_max_apdu_length(254);
+        assert_eq!(TunnelConnection::new([192, 168, 1, 100], 3671, config).max_apdu_length(), 254);
+    }
+
+    #[test]
+    fn t_tunnel_oversized_frame() {
+        // APDUs not fitting into the length octet are dropped even if the server claims to support them
+        let config = TunnelConnectionConfig::default().with_max_apdu_length(1000);
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, config);
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        let token = tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: vec![0; 300]});
+        assert_eq!(tunnel.get_outbound_data(), None);
+        assert_eq!(tunnel.get_delivery_event(), Some(DeliveryEvent{token, result: DeliveryResult::Dropped}));
+
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: vec![0; 253]});
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| (d[18], d.len())), Some((254, 274)));
+        assert_eq!(tunnel.get_delivery_event(), None);
+    }
+
+    #[test]
+    fn t_tunnel_nat() {
+        let mut tunnel = TunnelConnection::new_nat(TunnelConnectionConfig::default());
+        assert_eq!(tunnel.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x05, 0x00, 0x1A,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x04, 0x04, 0x02, 0x00][..], Endpoint::Control)));
+        // the data endpoint assigned by the server is its private address behind the NAT, so the control endpoint is used
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x58, 0x04, 0x04, 0x11, 0x0A]);
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(tunnel.get_outbound_data().map(|(_, endpoint)| endpoint), Some(Endpoint::Control));
+    }
+
+    #[test]
+    fn t_tunnel_data_endpoint() {
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default());
+        tunnel.get_outbound_data();
+        // the server assigns a data endpoint
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x58, 0x04, 0x04, 0x11, 0x0A]);
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(tunnel.get_outbound_data().map(|(_, endpoint)| endpoint), Some(Endpoint::Data(SocketAddrV4::new([192, 168, 1, 10].into(), 3672))));
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x09, 0x00, 0x10, 0x15, 0x00, 0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        // the server closed the tunnel, so it is re-established through the control endpoint
+        assert_eq!(tunnel.get_outbound_data().map(|(data, endpoint)| (data[3], endpoint)), Some((0x05, Endpoint::Control)));
+
+        // a route back data endpoint means the control endpoint is used for data as well
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x16, 0x00,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x04, 0x11, 0x0A]);
+        tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(tunnel.get_outbound_data().map(|(_, endpoint)| endpoint), Some(Endpoint::Control));
+    }
+
+    #[test]
+    fn t_tunnel_delivery() {
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default());
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        let first = tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        let second = tunnel.send(GroupEvent{address: 11, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_ne!(first, second);
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00]);
+        // the acknowledgement alone does not mean the frame made it to the bus
+        assert_eq!(tunnel.get_delivery_event(), None);
+
+        // confirmations are matched by their frame, with the source address filled in by the server
+        assert!(tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x15, 0x00, 0x00,
+            0x2E, 0x00, 0xBD, 0xE0, 0x11, 0x0A, 0x00, 0x0B, 0x01, 0x00, 0x00]).is_none());
+        assert!(tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x15, 0x01, 0x00,
+            0x2E, 0x00, 0xBC, 0xE0, 0x11, 0x0A, 0x00, 0x0A, 0x01, 0x00, 0x00]).is_none());
+        assert_eq!(tunnel.get_delivery_event(), Some(DeliveryEvent{token: second, result: DeliveryResult::Failed}));
+        assert_eq!(tunnel.get_delivery_event(), Some(DeliveryEvent{token: first, result: DeliveryResult::Confirmed}));
+        assert_eq!(tunnel.get_delivery_event(), None);
+
+        // too long for a standard frame
+        let dropped = tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueWrite, data: vec![0; 20]});
+        assert_eq!(tunnel.get_delivery_event(), Some(DeliveryEvent{token: dropped, result: DeliveryResult::Dropped}));
+    }
+
+    #[test]
+    fn t_tunnel_mismatched_ack() {
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default());
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        let first = tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        tunnel.send(GroupEvent{address: 11, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[8]), Some(0));
+        // acks of another sequence number or channel leave the frame in flight
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00]);
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x16, 0x00, 0x00]);
+        assert_eq!(tunnel.get_outbound_data(), None);
+
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[8]), Some(1));
+        // a duplicate ack of the first frame does not complete the second one
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        tunnel.send(GroupEvent{address: 12, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(tunnel.get_outbound_data(), None);
+
+        // only the first frame is awaiting its confirmation
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x15, 0x00, 0x00,
+            0x2E, 0x00, 0xBC, 0xE0, 0x11, 0x0A, 0x00, 0x0B, 0x01, 0x00, 0x00]);
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x15, 0x01, 0x00,
+            0x2E, 0x00, 0xBC, 0xE0, 0x11, 0x0A, 0x00, 0x0A, 0x01, 0x00, 0x00]);
+        assert_eq!(tunnel.get_delivery_event(), Some(DeliveryEvent{token: first, result: DeliveryResult::Confirmed}));
+        assert_eq!(tunnel.get_delivery_event(), None);
+
+        // acks of the confirmations go out first
+        tunnel.get_outbound_data();
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x01, 0x00]);
+        assert_eq!(tunnel.get_outbound_data().map(|(d, _)| d[8]), Some(2));
+    }
+
+    #[test]
+    fn t_tunnel_delivery_timeout() {
+        let config = TunnelConnectionConfig::default().with_confirmation_timeout(Duration::ZERO);
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, config);
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        let token = tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x15, 0x00, 0x00]);
+        std::thread::sleep(Duration::from_millis(1));
+        tunnel.handle_time_events();
+        assert_eq!(tunnel.get_delivery_event(), Some(DeliveryEvent{token, result: DeliveryResult::NotConfirmed}));
+
+        // frames still waiting for an ack are dropped when the server closes the tunnel
+        let token = tunnel.send(GroupEvent{address: 10, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x09, 0x00, 0x10, 0x15, 0x00, 0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57]);
+        assert_eq!(tunnel.get_delivery_event(), Some(DeliveryEvent{token, result: DeliveryResult::Dropped}));
+    }
+
+    #[test]
+    fn t_tunnel_dropped_limit() {
+        let mut tunnel = TunnelConnection::new([192, 168, 1, 100], 3671, TunnelConnectionConfig::default());
+        tunnel.get_outbound_data();
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x15, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0x11, 0x0A]);
+        for address in 0..1100 {
+            tunnel.send(GroupEvent{address, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        }
+        // only the latest frames dropped by closing the tunnel are kept
+        tunnel.handle_inbound_message(&[0x06, 0x10, 0x02, 0x09, 0x00, 0x10, 0x15, 0x00, 0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57]);
+        let pending = tunnel.take_pending_l_data();
+        assert_eq!(pending.len(), 1024);
+        assert_eq!(pending.first().map(|l_data| l_data.destination), Some(76));
+        assert_eq!(pending.last().map(|l_data| l_data.destination), Some(1099));
+    }
+}
@@ -0,0 +1,499 @@
+//runtime facing functions:
+
+// get data to be transmitted next together with the client endpoint it is addressed to
+// get next time event
+// handle next time event
+// handle received data of a client endpoint -> returns a group event sent by a client or none
+// send data to all connected clients
+
+use std::cmp::min;
+use std::collections::VecDeque;
+use std::net::SocketAddrV4;
+use std::ops::Add;
+use std::time::{Duration, Instant};
+use crate::address::IndividualAddress;
+use crate::cemi::l_data::{Confirmation, LData};
+use crate::cemi::Message;
+use crate::dpt::DPT;
+use crate::group_event::GroupEvent;
+use crate::knxnet::{cri, crd, Service};
+use crate::knxnet::connect::{ConnectRequest, ConnectResponse};
+use crate::knxnet::connectionstate::ConnectionstateResponse;
+use crate::knxnet::disconnect::{DisconnectRequest, DisconnectResponse};
+use crate::knxnet::hpai::{HPAI, Protocol};
+use crate::knxnet::status::StatusCode;
+use crate::knxnet::tunnel::{TunnelAck, TunnelRequest};
+
+// a tunnelling request is sent twice before the connection is considered broken (See 03/08/04 Tunneling 2.6)
+const MAX_TRANSMISSIONS: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TunnellingServerConfig {
+    individual_address: IndividualAddress,
+    tunnel_addresses: Vec<IndividualAddress>,
+    resent_interval: Duration,
+    heartbeat_timeout: Duration,
+}
+
+impl Default for TunnellingServerConfig {
+    fn default() -> TunnellingServerConfig {
+        TunnellingServerConfig{
+            individual_address: IndividualAddress::new(15, 15, 0),
+            tunnel_addresses: (1..=8).map(|device| IndividualAddress::new(15, 15, device)).collect(),
+            resent_interval: Duration::from_millis(1000),
+            heartbeat_timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+impl TunnellingServerConfig {
+    /// Individual address of the server, used as source of the frames sent with `send`
+    pub fn with_individual_address(mut self, address: IndividualAddress) -> TunnellingServerConfig {
+        self.individual_address = address;
+        self
+    }
+
+    /// Pool of individual addresses assigned to tunnels, which also limits the number of concurrent connections
+    pub fn with_tunnel_addresses(mut self, addresses: Vec<IndividualAddress>) -> TunnellingServerConfig {
+        self.tunnel_addresses = addresses;
+        self
+    }
+
+    /// Time after which an unacknowledged tunnelling request is repeated, and the client disconnected after the repetition
+    pub fn with_resent_interval(mut self, interval: Duration) -> TunnellingServerConfig {
+        self.resent_interval = interval;
+        self
+    }
+
+    /// Time after which a client not sending connection state requests anymore is disconnected
+    pub fn with_heartbeat_timeout(mut self, timeout: Duration) -> TunnellingServerConfig {
+        self.heartbeat_timeout = timeout;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct ServerConnection {
+    channel: u8,
+    address: IndividualAddress,
+    control: SocketAddrV4,
+    data: SocketAddrV4,
+    inbound_seq: u8,
+    outbound_seq: u8,
+    // encoded tunnelling requests, the first one is awaiting its ack
+    out_queue: VecDeque<Vec<u8>>,
+    message_pending: bool,
+    transmissions: u8,
+    next_resent: Instant,
+    last_heartbeat: Instant,
+}
+
+/// Server side of KNXnet/IP tunnelling over UDP, e.g. for software gateways or bus simulators.
+/// Frames sent by a client are confirmed to it, indicated to all other clients and returned to the application,
+/// frames of the application are indicated to all clients.
+#[derive(Debug)]
+pub struct TunnellingServer {
+    endpoint: HPAI,
+    connections: Vec<ServerConnection>,
+    ack_queue: VecDeque<(Vec<u8>, SocketAddrV4)>,
+    current: Vec<u8>,
+    next_channel: u8,
+    config: TunnellingServerConfig,
+}
+
+impl TunnellingServer {
+    /// Create a server, the address and port are announced to clients as data endpoint
+    pub fn new(ipv4: [u8;4], port: u16, config: TunnellingServerConfig) -> TunnellingServer {
+        TunnellingServer{
+            endpoint: HPAI::new(Protocol::Udp4Protocol, ipv4, port),
+            connections: vec![],
+            ack_queue: VecDeque::new(),
+            current: vec![],
+            next_channel: 1,
+            config,
+        }
+    }
+
+    /// Send a group event to all connected clients as if it was received from the bus
+    pub fn send<T: DPT+Default>(&mut self, ev: GroupEvent<T>) {
+        let mut l_data = ev.into_l_data();
+        l_data.source = self.config.individual_address.to_u16();
+        // the frame is indicated to every client, so its APDU is converted to raw data which can be cloned
+        if let Ok(raw) = l_data.to_raw() {
+            self.indicate(raw, None);
+        }
+    }
+
+    /// Individual addresses of the currently connected tunnels
+    pub fn connected_addresses(&self) -> Vec<IndividualAddress> {
+        self.connections.iter().map(|c| c.address).collect()
+    }
+
+    /// Data which needs to be sent to the returned client endpoint
+    pub fn get_outbound_data(&mut self) -> Option<(&[u8], SocketAddrV4)> {
+        if let Some((data, endpoint)) = self.ack_queue.pop_front() {
+            self.current = data;
+            return Some((&self.current, endpoint))
+        }
+        let resent_interval = self.config.resent_interval;
+        let con = self.connections.iter_mut().find(|c| c.message_pending && !c.out_queue.is_empty())?;
+        con.message_pending = false;
+        con.transmissions += 1;
+        con.next_resent = Instant::now().add(resent_interval);
+        Some((&con.out_queue[0], con.data))
+    }
+
+    pub fn get_next_time_event(&self) -> Instant {
+        self.connections.iter().fold(Instant::now().add(self.config.heartbeat_timeout), |next, c| {
+            let next = min(next, c.last_heartbeat.add(self.config.heartbeat_timeout));
+            if c.out_queue.is_empty() || c.message_pending { next } else { min(next, c.next_resent) }
+        })
+    }
+
+    pub fn handle_time_events(&mut self) {
+        let now = Instant::now();
+        let mut i = 0;
+        while i < self.connections.len() {
+            let con = &mut self.connections[i];
+            if con.last_heartbeat.add(self.config.heartbeat_timeout) <= now {
+                self.disconnect(i);
+                continue
+            }
+            if !con.out_queue.is_empty() && !con.message_pending && con.next_resent <= now {
+                if con.transmissions >= MAX_TRANSMISSIONS {
+                    // the client did not acknowledge the repetition either
+                    self.disconnect(i);
+                    continue
+                }
+                con.message_pending = true;
+            }
+            i += 1;
+        }
+    }
+
+    /// Handles data received from the given client endpoint,
+    /// requests of a connection are only accepted from the control or data endpoint recorded on connect
+    pub fn handle_inbound_message(&mut self, data: &[u8], source: SocketAddrV4) -> Option<GroupEvent<Vec<u8>>> {
+        let service = match Service::<Vec<u8>>::decoded(data) {
+            Ok(s) => s,
+            Err(_) => return None
+        };
+        match service {
+            Service::ConnectRequest(req) => {
+                self.handle_connect_request(req, source);
+                None
+            }
+            Service::ConnectionstateRequest(req) => {
+                let status = match self.connections.iter_mut().find(|c| c.channel == req.channel && c.control == source) {
+                    Some(con) => {
+                        con.last_heartbeat = Instant::now();
+                        StatusCode::NoError
+                    }
+                    None => StatusCode::ErrConnectionID
+                };
+                let response = Service::<()>::ConnectionstateResponse(ConnectionstateResponse{channel: req.channel, status});
+                self.ack_queue.push_back((response.encoded(), Self::endpoint(req.control, source)));
+                None
+            }
+            Service::DisconnectRequest(req) => {
+                let status = match self.connections.iter().position(|c| c.channel == req.channel && c.control == source) {
+                    Some(i) => {
+                        self.connections.remove(i);
+                        StatusCode::NoError
+                    }
+                    None => StatusCode::ErrConnectionID
+                };
+                let response = Service::<()>::DisconnectResponse(DisconnectResponse{channel: req.channel, status});
+                self.ack_queue.push_back((response.encoded(), Self::endpoint(req.control, source)));
+                None
+            }
+            Service::TunnelAck(ack) => {
+                if let Some(con) = self.connections.iter_mut().find(|c| c.channel == ack.channel && c.data == source) {
+                    // byte 8 is the sequence counter of the tunnelling request awaiting the ack
+                    if ack.status == StatusCode::NoError && con.out_queue.front().is_some_and(|d| d[8] == ack.seq) {
+                        con.out_queue.pop_front();
+                        con.transmissions = 0;
+                        con.message_pending = !con.out_queue.is_empty();
+                    }
+                }
+                None
+            }
+            Service::TunnelRequest(req) => self.handle_tunnel_request(req, source),
+            _ => None
+        }
+    }
+
+    fn handle_connect_request(&mut self, req: ConnectRequest, source: SocketAddrV4) {
+        let control = Self::endpoint(req.control, source);
+        let status = match req.connection_type {
+            _ if req.control.protocol() != Protocol::Udp4Protocol || req.data.protocol() != Protocol::Udp4Protocol => Err(StatusCode::ErrHostProtocolType),
+            cri::ConnectionReqType::TunnelConnection{layer: cri::TunnelingLayer::TunnelLinkLayer, address} => self.allocate_address(address),
+            cri::ConnectionReqType::TunnelConnection{..} => Err(StatusCode::ErrTunnellingLayer),
+            _ => Err(StatusCode::ErrConnectionType),
+        };
+        let status = status.and_then(|address| Ok((address, self.allocate_channel().ok_or(StatusCode::ErrNoMoreConnections)?)));
+        let response = match status {
+            Ok((address, channel)) => {
+                self.connections.push(ServerConnection{
+                    channel,
+                    address,
+                    control,
+                    data: Self::endpoint(req.data, source),
+                    inbound_seq: 0,
+                    outbound_seq: 0,
+                    out_queue: VecDeque::new(),
+                    message_pending: false,
+                    transmissions: 0,
+                    next_resent: Instant::now(),
+                    last_heartbeat: Instant::now(),
+                });
+                ConnectResponse{
+                    channel,
+                    status: StatusCode::NoError,
+                    data: self.endpoint,
+                    connection_type: crd::ConnectionRespType::TunnelConnection{address},
+                }
+            }
+            Err(status) => ConnectResponse{status, ..ConnectResponse::default()}
+        };
+        self.ack_queue.push_back((Service::<()>::ConnectResponse(response).encoded(), control));
+    }
+
+    fn allocate_address(&self, requested: Option<IndividualAddress>) -> Result<IndividualAddress, StatusCode> {
+        let free = |address: &IndividualAddress| !self.connections.iter().any(|c| c.address == *address);
+        match requested {
+            Some(address) if !self.config.tunnel_addresses.contains(&address) => Err(StatusCode::ErrConnectionOption),
+            Some(address) if !free(&address) => Err(StatusCode::ErrNoMoreUniqueConnections),
+            Some(address) => Ok(address),
+            None => self.config.tunnel_addresses.iter().copied().find(free).ok_or(StatusCode::ErrNoMoreConnections),
+        }
+    }
+
+    fn allocate_channel(&mut self) -> Option<u8> {
+        // channel 0 is not used, so the pool may be larger than the number of channels
+        let channel = (0..=u8::MAX).map(|offset| self.next_channel.wrapping_add(offset))
+            .find(|&channel| channel != 0 && !self.connections.iter().any(|c| c.channel == channel))?;
+        self.next_channel = channel.wrapping_add(1);
+        Some(channel)
+    }
+
+    fn handle_tunnel_request(&mut self, req: TunnelRequest<Vec<u8>>, source: SocketAddrV4) -> Option<GroupEvent<Vec<u8>>> {
+        let con = self.connections.iter_mut().find(|c| c.channel == req.channel && c.data == source)?;
+        //requests with the expected seq are processed, repetitions of the previous one only acknowledged. See 03/08/04 Tunneling 2.6
+        let repeated = con.inbound_seq == req.seq.wrapping_add(1);
+        if con.inbound_seq != req.seq && !repeated {
+            return None
+        }
+        let ack = Service::<()>::TunnelAck(TunnelAck{channel: req.channel, seq: req.seq, status: StatusCode::NoError});
+        self.ack_queue.push_back((ack.encoded(), con.data));
+        if repeated {
+            return None
+        }
+        con.inbound_seq = req.seq.wrapping_add(1);
+        match req.data {
+            Message::LDataReq(_, mut l_data) => {
+                if l_data.source == 0 {
+                    l_data.source = con.address.to_u16();
+                }
+                let mut confirmation = l_data.clone();
+                confirmation.confirmation = Confirmation::NoError;
+                Self::push_request(con, Message::LDataCon(vec![], confirmation));
+                let channel = con.channel;
+                self.indicate(l_data.clone(), Some(channel));
+                GroupEvent::from_l_data(l_data)
+            }
+            _ => None
+        }
+    }
+
+    // indicates the frame to all clients apart from the one it came from
+    fn indicate(&mut self, l_data: LData<Vec<u8>>, except: Option<u8>) {
+        for con in self.connections.iter_mut().filter(|c| Some(c.channel) != except) {
+            Self::push_request(con, Message::LDataInd(vec![], l_data.clone()));
+        }
+    }
+
+    fn push_request(con: &mut ServerConnection, msg: Message<Vec<u8>>) {
+        let req = Service::TunnelRequest(TunnelRequest{
+            channel: con.channel,
+            seq: con.outbound_seq,
+            data: msg,
+        });
+        con.outbound_seq = con.outbound_seq.wrapping_add(1);
+        if con.out_queue.is_empty() {
+            con.message_pending = true;
+        }
+        con.out_queue.push_back(req.encoded());
+    }
+
+    fn disconnect(&mut self, index: usize) {
+        let con = self.connections.remove(index);
+        let req = Service::<()>::DisconnectRequest(DisconnectRequest{channel: con.channel, control: self.endpoint});
+        self.ack_queue.push_back((req.encoded(), con.control));
+    }
+
+    // clients behind a NAT send route back HPAIs, so the source of the request is used
+    fn endpoint(hpai: HPAI, source: SocketAddrV4) -> SocketAddrV4 {
+        if hpai.address() == [0, 0, 0, 0] || hpai.port() == 0 {
+            return source
+        }
+        SocketAddrV4::new(hpai.address().into(), hpai.port())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddrV4;
+    use std::time::Duration;
+    use crate::address::IndividualAddress;
+    use crate::group_event::{GroupEvent, GroupEventType};
+    use crate::tunnelling_server::{TunnellingServer, TunnellingServerConfig};
+
+    const CLIENT_1: [u8; 26] = [0x06, 0x10, 0x02, 0x05, 0x00, 0x1A,
+        0x08, 0x01, 0xC0, 0xA8, 0x01, 0x64, 0x0E, 0x57,
+        0x08, 0x01, 0xC0, 0xA8, 0x01, 0x64, 0x0E, 0x57,
+        0x04, 0x04, 0x02, 0x00];
+
+    fn client_1() -> SocketAddrV4 {
+        SocketAddrV4::new([192, 168, 1, 100].into(), 3671)
+    }
+
+    fn client_2() -> SocketAddrV4 {
+        SocketAddrV4::new([10, 0, 0, 2].into(), 50000)
+    }
+
+    fn connected_server(config: TunnellingServerConfig) -> TunnellingServer {
+        let mut server = TunnellingServer::new([192, 168, 1, 10], 3671, config);
+        server.handle_inbound_message(&CLIENT_1, client_1());
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x06, 0x00, 0x14, 0x01, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57, 0x04, 0x04, 0xFF, 0x01][..], client_1())));
+        // NAT client with route back HPAIs requesting an address
+        server.handle_inbound_message(&[0x06, 0x10, 0x02, 0x05, 0x00, 0x1C,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x06, 0x04, 0x02, 0x00, 0xFF, 0x05], client_2());
+        assert_eq!(server.get_outbound_data().map(|(d, endpoint)| (d[6..8].to_vec(), d[18..20].to_vec(), endpoint)),
+                   Some((vec![0x02, 0x00], vec![0xFF, 0x05], client_2())));
+        server
+    }
+
+    #[test]
+    fn t_server_connect() {
+        let config = TunnellingServerConfig::default()
+            .with_tunnel_addresses(vec![IndividualAddress::new(15, 15, 1), IndividualAddress::new(15, 15, 5)]);
+        let mut server = connected_server(config);
+        assert_eq!(server.connected_addresses(), vec![IndividualAddress::new(15, 15, 1), IndividualAddress::new(15, 15, 5)]);
+        // the pool is exhausted
+        server.handle_inbound_message(&CLIENT_1, client_1());
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x06, 0x00, 0x08, 0x00, 0x24][..], client_1())));
+        // busmonitor is not supported
+        let mut busmonitor = CLIENT_1;
+        busmonitor[24] = 0x80;
+        server.handle_inbound_message(&busmonitor, client_1());
+        assert_eq!(server.get_outbound_data().map(|(d, _)| d[7]), Some(0x29));
+
+        server.handle_inbound_message(&[0x06, 0x10, 0x02, 0x07, 0x00, 0x10, 0x01, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x64, 0x0E, 0x57], client_1());
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x08, 0x00, 0x08, 0x01, 0x00][..], client_1())));
+        server.handle_inbound_message(&[0x06, 0x10, 0x02, 0x07, 0x00, 0x10, 0x07, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x64, 0x0E, 0x57], client_1());
+        assert_eq!(server.get_outbound_data().map(|(d, _)| d[7]), Some(0x21));
+
+        server.handle_inbound_message(&[0x06, 0x10, 0x02, 0x09, 0x00, 0x10, 0x01, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x64, 0x0E, 0x57], client_1());
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x0A, 0x00, 0x08, 0x01, 0x00][..], client_1())));
+        assert_eq!(server.connected_addresses(), vec![IndividualAddress::new(15, 15, 5)]);
+        // the requested address is in use
+        server.handle_inbound_message(&[0x06, 0x10, 0x02, 0x05, 0x00, 0x1C,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x06, 0x04, 0x02, 0x00, 0xFF, 0x05], client_1());
+        assert_eq!(server.get_outbound_data().map(|(d, _)| d[7]), Some(0x25));
+    }
+
+    #[test]
+    fn t_server_tunnelling() {
+        let mut server = connected_server(TunnellingServerConfig::default());
+        // group value write of client 1
+        let ev = server.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x16, 0x04, 0x01, 0x00, 0x00,
+            0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x00, 0x0A, 0x02, 0x00, 0x80, 0x01], client_1()).unwrap();
+        assert_eq!(ev.address, 10);
+        assert_eq!(ev.event_type, GroupEventType::GroupValueWrite);
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x01, 0x00, 0x00][..], client_1())));
+        // confirmed to client 1 and indicated to client 2 with the tunnel address as source
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x04, 0x20, 0x00, 0x16, 0x04, 0x01, 0x00, 0x00,
+            0x2E, 0x00, 0xBC, 0xE0, 0xFF, 0x01, 0x00, 0x0A, 0x02, 0x00, 0x80, 0x01][..], client_1())));
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x04, 0x20, 0x00, 0x16, 0x04, 0x02, 0x00, 0x00,
+            0x29, 0x00, 0xBC, 0xE0, 0xFF, 0x01, 0x00, 0x0A, 0x02, 0x00, 0x80, 0x01][..], client_2())));
+        assert_eq!(server.get_outbound_data(), None);
+
+        // a repetition is acknowledged again but not processed
+        assert!(server.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x16, 0x04, 0x01, 0x00, 0x00,
+            0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x00, 0x0A, 0x02, 0x00, 0x80, 0x01], client_1()).is_none());
+        assert_eq!(server.get_outbound_data().map(|(d, _)| d[3]), Some(0x21));
+        assert_eq!(server.get_outbound_data(), None);
+
+        server.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x01, 0x00, 0x00], client_1());
+        server.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x02, 0x00, 0x00], client_2());
+        server.send(GroupEvent{address: 11, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x04, 0x20, 0x00, 0x15, 0x04, 0x01, 0x01, 0x00,
+            0x29, 0x00, 0xBE, 0xE0, 0xFF, 0x00, 0x00, 0x0B, 0x01, 0x00, 0x00][..], client_1())));
+        assert_eq!(server.get_outbound_data().map(|(d, endpoint)| (d[7..9].to_vec(), endpoint)), Some((vec![0x02, 0x01], client_2())));
+    }
+
+    #[test]
+    fn t_server_timeouts() {
+        let mut server = connected_server(TunnellingServerConfig::default().with_resent_interval(Duration::from_millis(10)));
+        server.send(GroupEvent{address: 11, event_type: GroupEventType::GroupValueRead, data: vec![]});
+        server.get_outbound_data();
+        server.get_outbound_data();
+        server.handle_inbound_message(&[0x06, 0x10, 0x04, 0x21, 0x00, 0x0A, 0x04, 0x02, 0x00, 0x00], client_2());
+
+        // client 1 does not acknowledge the request nor its repetition
+        std::thread::sleep(Duration::from_millis(15));
+        server.handle_time_events();
+        assert_eq!(server.get_outbound_data().map(|(d, _)| d[8]), Some(0x00));
+        std::thread::sleep(Duration::from_millis(15));
+        server.handle_time_events();
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x09, 0x00, 0x10, 0x01, 0x00,
+            0x08, 0x01, 0xC0, 0xA8, 0x01, 0x0A, 0x0E, 0x57][..], client_1())));
+        assert_eq!(server.connected_addresses(), vec![IndividualAddress::new(15, 15, 5)]);
+
+        // client 2 stopped sending heartbeats
+        let mut server = connected_server(TunnellingServerConfig::default().with_heartbeat_timeout(Duration::ZERO));
+        server.handle_time_events();
+        assert_eq!(server.connected_addresses(), vec![]);
+        assert_eq!(server.get_outbound_data().map(|(d, endpoint)| (d[6], endpoint)), Some((0x01, client_1())));
+        assert_eq!(server.get_outbound_data().map(|(d, endpoint)| (d[6], endpoint)), Some((0x02, client_2())));
+    }
+
+    #[test]
+    fn t_server_foreign_source() {
+        let mut server = connected_server(TunnellingServerConfig::default());
+        // client 2 uses the channel of client 1
+        assert!(server.handle_inbound_message(&[0x06, 0x10, 0x04, 0x20, 0x00, 0x16, 0x04, 0x01, 0x00, 0x00,
+            0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x00, 0x0A, 0x02, 0x00, 0x80, 0x01], client_2()).is_none());
+        assert_eq!(server.get_outbound_data(), None);
+        server.handle_inbound_message(&[0x06, 0x10, 0x02, 0x07, 0x00, 0x10, 0x01, 0x00,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], client_2());
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x08, 0x00, 0x08, 0x01, 0x21][..], client_2())));
+        server.handle_inbound_message(&[0x06, 0x10, 0x02, 0x09, 0x00, 0x10, 0x01, 0x00,
+            0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], client_2());
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x0A, 0x00, 0x08, 0x01, 0x21][..], client_2())));
+        assert_eq!(server.connected_addresses(), vec![IndividualAddress::new(15, 15, 1), IndividualAddress::new(15, 15, 5)]);
+    }
+
+    #[test]
+    fn t_server_channels_exhausted() {
+        // the pool holds more addresses than there are channels
+        let addresses = (1..=2).flat_map(|line| (1..=255).map(move |device| IndividualAddress::new(15, line, device))).collect();
+        let mut server = TunnellingServer::new([192, 168, 1, 10], 3671, TunnellingServerConfig::default().with_tunnel_addresses(addresses));
+        for channel in 1..=255 {
+            server.handle_inbound_message(&CLIENT_1, client_1());
+            assert_eq!(server.get_outbound_data().map(|(d, _)| (d[6], d[7])), Some((channel, 0x00)));
+        }
+        server.handle_inbound_message(&CLIENT_1, client_1());
+        assert_eq!(server.get_outbound_data(), Some((&[0x06, 0x10, 0x02, 0x06, 0x00, 0x08, 0x00, 0x24][..], client_1())));
+        assert_eq!(server.connected_addresses().len(), 255);
+    }
+}
@@ -0,0 +1,257 @@
+use byteorder::{BigEndian, ByteOrder};
+use strum_macros::FromRepr;
+use crate::emi::EmiType;
+use crate::knxnet::KnxNetIpError;
+
+// HID reports of KNX USB interfaces (See 9/3 KNX USB Interface). A transfer consists of the transfer protocol
+// header and its body, split into reports of 64 bytes with a report header each.
+
+/// Length of every HID report, unused bytes are padded with zeros
+pub const REPORT_LENGTH: usize = 64;
+
+const REPORT_ID: u8 = 0x01;
+const REPORT_HEADER_LENGTH: usize = 3;
+const MAX_REPORT_DATA_LENGTH: usize = REPORT_LENGTH - REPORT_HEADER_LENGTH;
+// the sequence number of the report header has four bits and starts at one
+const MAX_REPORTS: usize = 15;
+
+// packet type of the report header
+const START_PACKET: u8 = 0x01;
+const END_PACKET: u8 = 0x02;
+const PARTIAL_PACKET: u8 = 0x04;
+
+const PROTOCOL_VERSION: u8 = 0x00;
+const TRANSFER_HEADER_LENGTH: u8 = 0x08;
+
+/// Maximum length of the body of a transfer, which is split into at most 15 reports
+pub const MAX_BODY_LENGTH: usize = MAX_REPORTS * MAX_REPORT_DATA_LENGTH - TRANSFER_HEADER_LENGTH as usize;
+
+const PROTOCOL_KNX_TUNNEL: u8 = 0x01;
+const PROTOCOL_BUS_ACCESS_SERVER_FEATURE: u8 = 0x0F;
+
+/// Services of the bus access server to access device features of the interface
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FeatureService {
+    Get = 0x01,
+    Response = 0x02,
+    Set = 0x03,
+    Info = 0x04,
+}
+
+/// Device features of the interface, the first byte of the body of feature services
+#[derive(FromRepr, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DeviceFeature {
+    /// Bit set of the supported EMI types: EMI1 (0x01), EMI2 (0x02) and cEMI (0x04)
+    SupportedEmiType = 0x01,
+    DeviceDescriptorType0 = 0x02,
+    BusConnectionStatus = 0x03,
+    ManufacturerCode = 0x04,
+    /// EMI type used for tunnel transfers, which can be changed with `FeatureService::Set`
+    ActiveEmiType = 0x05,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UsbProtocol {
+    /// KNX frames in the given EMI format
+    Tunnel(EmiType),
+    /// Device feature services
+    Feature(FeatureService),
+}
+
+/// Transfer of the KNX USB transfer protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsbTransfer {
+    pub protocol: UsbProtocol,
+    /// Only used by manufacturer specific transfers, zero otherwise
+    pub manufacturer_code: u16,
+    pub body: Vec<u8>,
+}
+
+impl UsbTransfer {
+    /// Transfer of an EMI message, e.g. created with `knx_rust::emi::encode_group_event`
+    pub fn tunnel(emi: EmiType, body: Vec<u8>) -> UsbTransfer {
+        UsbTransfer{protocol: UsbProtocol::Tunnel(emi), manufacturer_code: 0, body}
+    }
+
+    /// Transfer of a device feature service, `data` is empty for `FeatureService::Get`
+    pub fn feature(service: FeatureService, feature: DeviceFeature, data: &[u8]) -> UsbTransfer {
+        let mut body = vec![feature as u8];
+        body.extend_from_slice(data);
+        UsbTransfer{protocol: UsbProtocol::Feature(service), manufacturer_code: 0, body}
+    }
+
+    /// Splits the transfer into the HID reports to be written to the interface,
+    /// returns `InvalidSize` if the body exceeds `MAX_BODY_LENGTH`
+    pub fn encode(&self) -> Result<Vec<[u8; REPORT_LENGTH]>, KnxNetIpError> {
+        if self.body.len() > MAX_BODY_LENGTH {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        let (protocol_id, id) = match self.protocol {
+            UsbProtocol::Tunnel(emi) => (PROTOCOL_KNX_TUNNEL, emi as u8),
+            UsbProtocol::Feature(service) => (PROTOCOL_BUS_ACCESS_SERVER_FEATURE, service as u8),
+        };
+        let mut data = vec![PROTOCOL_VERSION, TRANSFER_HEADER_LENGTH];
+        data.extend((self.body.len() as u16).to_be_bytes());
+        data.extend([protocol_id, id]);
+        data.extend(self.manufacturer_code.to_be_bytes());
+        data.extend_from_slice(&self.body);
+
+        let chunks = data.chunks(MAX_REPORT_DATA_LENGTH).count();
+        Ok(data.chunks(MAX_REPORT_DATA_LENGTH).enumerate().map(|(i, chunk)| {
+            let mut packet_type = if i == 0 {START_PACKET} else {0} | if i == chunks - 1 {END_PACKET} else {0};
+            if chunks > 1 {
+                packet_type |= PARTIAL_PACKET;
+            }
+            let mut report = [0; REPORT_LENGTH];
+            report[0] = REPORT_ID;
+            report[1] = (((i + 1) as u8) << 4) | packet_type;
+            report[2] = chunk.len() as u8;
+            report[REPORT_HEADER_LENGTH..REPORT_HEADER_LENGTH + chunk.len()].copy_from_slice(chunk);
+            report
+        }).collect())
+    }
+
+    fn decode(buf: &[u8]) -> Result<UsbTransfer, KnxNetIpError> {
+        if buf.len() < TRANSFER_HEADER_LENGTH as usize {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        if buf[0] != PROTOCOL_VERSION {
+            return Err(KnxNetIpError::UnknownVersion(buf[0]))
+        }
+        if buf[1] != TRANSFER_HEADER_LENGTH {
+            return Err(KnxNetIpError::UnknownHeaderSize(buf[1]))
+        }
+        let body_length = BigEndian::read_u16(&buf[2..4]) as usize;
+        if buf.len() < TRANSFER_HEADER_LENGTH as usize + body_length {
+            return Err(KnxNetIpError::MessageTooShort(buf.len()))
+        }
+        let protocol = match buf[4] {
+            PROTOCOL_KNX_TUNNEL => UsbProtocol::Tunnel(EmiType::from_repr(buf[5]).ok_or(KnxNetIpError::Unknown)?),
+            PROTOCOL_BUS_ACCESS_SERVER_FEATURE => UsbProtocol::Feature(FeatureService::from_repr(buf[5]).ok_or(KnxNetIpError::Unknown)?),
+            _ => return Err(KnxNetIpError::UnknownProtocol(buf[4])),
+        };
+        Ok(UsbTransfer{
+            protocol,
+            manufacturer_code: BigEndian::read_u16(&buf[6..8]),
+            body: buf[8..8 + body_length].to_vec(),
+        })
+    }
+}
+
+/// Joins the HID reports read from the interface into transfers
+#[derive(Debug, Default)]
+pub struct UsbReassembler {
+    buf: Vec<u8>,
+    // sequence number of the last report of the current transfer, zero if there is none
+    seq: u8,
+}
+
+impl UsbReassembler {
+    pub fn new() -> UsbReassembler {
+        UsbReassembler::default()
+    }
+
+    /// Append a report, which returns the transfer once its last report was received.
+    /// On an error the current transfer is dropped, a new one starts with the next start report.
+    pub fn push(&mut self, report: &[u8]) -> Result<Option<UsbTransfer>, KnxNetIpError> {
+        if report.len() < REPORT_HEADER_LENGTH {
+            return Err(KnxNetIpError::MessageTooShort(report.len()))
+        }
+        if report[0] != REPORT_ID {
+            return Err(KnxNetIpError::Unknown)
+        }
+        let length = report[2] as usize;
+        if length > MAX_REPORT_DATA_LENGTH || report.len() < REPORT_HEADER_LENGTH + length {
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        let seq = report[1] >> 4;
+        let packet_type = report[1] & 0x0F;
+        if packet_type & START_PACKET != 0 {
+            self.buf.clear();
+            self.seq = 0;
+        }
+        if seq != self.seq + 1 || (self.seq == 0 && packet_type & START_PACKET == 0) {
+            self.buf.clear();
+            self.seq = 0;
+            return Err(KnxNetIpError::InvalidSize)
+        }
+        self.seq = seq;
+        self.buf.extend_from_slice(&report[REPORT_HEADER_LENGTH..REPORT_HEADER_LENGTH + length]);
+        if packet_type & END_PACKET == 0 {
+            return Ok(None)
+        }
+        self.seq = 0;
+        let transfer = UsbTransfer::decode(&self.buf);
+        self.buf.clear();
+        transfer.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::emi::EmiType;
+    use crate::knxnet::KnxNetIpError;
+    use crate::usb::{DeviceFeature, FeatureService, UsbProtocol, UsbReassembler, UsbTransfer, MAX_BODY_LENGTH, REPORT_LENGTH};
+
+    fn report(data: &[u8]) -> [u8; REPORT_LENGTH] {
+        let mut report = [0; REPORT_LENGTH];
+        report[..data.len()].copy_from_slice(data);
+        report
+    }
+
+    #[test]
+    fn t_usb_single_report() {
+        // cEMI L_Data.req of a group value write to 9/0/8
+        let recorded = report(&[0x01, 0x13, 0x13, 0x00, 0x08, 0x00, 0x0B, 0x01, 0x03, 0x00, 0x00,
+            0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x48, 0x08, 0x01, 0x00, 0x81]);
+        let transfer = UsbTransfer::tunnel(EmiType::Cemi, vec![0x11, 0x00, 0xBC, 0xE0, 0x00, 0x00, 0x48, 0x08, 0x01, 0x00, 0x81]);
+        assert_eq!(transfer.encode(), Ok(vec![recorded]));
+        assert_eq!(UsbReassembler::new().push(&recorded), Ok(Some(transfer)));
+
+        // switch the interface to cEMI
+        let transfer = UsbTransfer::feature(FeatureService::Set, DeviceFeature::ActiveEmiType, &[EmiType::Cemi as u8]);
+        assert_eq!(transfer.encode(), Ok(vec![report(&[0x01, 0x13, 0x0A, 0x00, 0x08, 0x00, 0x02, 0x0F, 0x03, 0x00, 0x00, 0x05, 0x03])]));
+    }
+
+    #[test]
+    fn t_usb_fragmented() {
+        let transfer = UsbTransfer::tunnel(EmiType::Emi2, (0..100).collect());
+        let reports = transfer.encode().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0][..3], [0x01, 0x15, 0x3D]);
+        assert_eq!(reports[1][..3], [0x01, 0x26, 0x2F]);
+
+        let mut reassembler = UsbReassembler::new();
+        assert_eq!(reassembler.push(&reports[0]), Ok(None));
+        assert_eq!(reassembler.push(&reports[1]), Ok(Some(transfer.clone())));
+
+        // a lost report drops the transfer
+        assert_eq!(reassembler.push(&reports[1]), Err(KnxNetIpError::InvalidSize));
+        assert_eq!(reassembler.push(&reports[0]), Ok(None));
+        assert_eq!(reassembler.push(&reports[0]), Ok(None));
+        assert_eq!(reassembler.push(&reports[1]), Ok(Some(transfer)));
+
+        // the largest transfer uses all sequence numbers
+        let transfer = UsbTransfer::tunnel(EmiType::Cemi, vec![0x55; MAX_BODY_LENGTH]);
+        let reports = transfer.encode().unwrap();
+        assert_eq!(reports.len(), 15);
+        assert_eq!(reports[14][..3], [0x01, 0xF6, 0x3D]);
+        let mut reassembler = UsbReassembler::new();
+        reports[..14].iter().for_each(|r| assert_eq!(reassembler.push(r), Ok(None)));
+        assert_eq!(reassembler.push(&reports[14]), Ok(Some(transfer)));
+        assert_eq!(UsbTransfer::tunnel(EmiType::Cemi, vec![0x55; MAX_BODY_LENGTH + 1]).encode(), Err(KnxNetIpError::InvalidSize));
+    }
+
+    #[test]
+    fn t_usb_feature_response() {
+        let response = report(&[0x01, 0x13, 0x0B, 0x00, 0x08, 0x00, 0x03, 0x0F, 0x02, 0x00, 0x00, 0x01, 0x00, 0x05]);
+        let transfer = UsbReassembler::new().push(&response).unwrap().unwrap();
+        assert_eq!(transfer.protocol, UsbProtocol::Feature(FeatureService::Response));
+        assert_eq!(transfer.body, vec![DeviceFeature::SupportedEmiType as u8, 0x00, 0x05]);
+
+        assert_eq!(UsbReassembler::new().push(&report(&[0x01, 0x13, 0x08, 0x00, 0x08, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00])),
+            Err(KnxNetIpError::UnknownProtocol(0x02)));
+    }
+}